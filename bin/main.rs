@@ -9,12 +9,15 @@ use std::io::{BufReader, Read};
 
 fn main() {
     //NOTE: 1- Nodes
-    let nodes = get_html(); //Vector of Dom nodes
-    for n in nodes.iter() {
-        dom::pretty_print(n, 0);    //indent size of 0
-    }
+    let document = get_html(); //Document node, holds the doctype and the <html> element
+    dom::pretty_print(&document, 0);    //indent size of 0
 
-    let ref root_node = nodes[0]; //the root node is organised to be the 0 index 
+    //the root node is the <html> element, the parser always creates one even when the markup leaves it out
+    let root_node = document
+        .children
+        .iter()
+        .find(|n| matches!(n.node_type, dom::NodeType::Element(_)))
+        .unwrap();
 
     //NOTE: 2- Stylesheet
     let stylesheet = get_css();
@@ -22,7 +25,7 @@ fn main() {
 
     //NOTE: 3- Style tree
     //get style node from our style tree, pass root_node which is root of the dom node, 
-    let style_tree_root = style::StyledNode::new(&root_node, &stylesheet);
+    let style_tree_root = style::StyledNode::new(root_node, &stylesheet);
     style::pretty_print(&style_tree_root, 0);   //indent size of 0
 
     //The size of actuall HTML that we are rendering, we have the size for the window b
@@ -42,7 +45,7 @@ fn main() {
 
 //will be the function that we use to grab our html file and convert it into a string
 //then Dom node and then pass it back to our main function
-fn get_html() -> Vec<dom::Node> {
+fn get_html() -> dom::Node {
     let mut path = env::current_dir().unwrap(); //current path
     path.push("example/example1.html");  //add the path we want
 
@@ -51,13 +54,19 @@ fn get_html() -> Vec<dom::Node> {
         Err(e) => panic!("file: {}, error: {}", path.display(), e); //display file name and error
     };
 
-    let mut html_input = String::new(); //read all our HTML into it
-    file_reader.read_to_string(&mut html_input).unwrap(); //take file convert all content into string
+    let mut html_input = Vec::new(); //read all our HTML into it, as bytes bec. the page doesn't have to be UTF-8
+    file_reader.read_to_end(&mut html_input).unwrap(); //take file read all of its content
+
+    //call 'html_parse HtmlParser from_bytes' which is our entry point into our HTML parser, it works out the encoding & decodes the bytes,
+    //then call parse_document so it will actually traverse the string and grab all of the information from it
+    let mut parser = html_parse::HtmlParser::from_bytes(&html_input);
+    let document = parser.parse_document();
 
-    //call 'html_parse HtmlParser new' which is our entry point into our HTML parser, pass string for html input, 
-    //then call parse_node so it will actually traverse the string and grab all of the information from it
-    let nodes = html_parse::HtmlParser::new(&html_input).parse_nodes();
-    nodes
+    //the parser fixes broken markup on its own, tell the author what it had to fix
+    for error in parser.errors() {
+        eprintln!("{}:{}", path.display(), error);
+    }
+    document
 }
 
 fn get_css() -> css:Stylesheet {
@@ -74,6 +83,12 @@ fn get_css() -> css:Stylesheet {
 
     //call 'css_parser CssParser new' which is our entry point into our css_parser, pass string for html input, 
     //then call parse_stylesheet so it traverse the string and grab all of the information from it
-    let stylesheet = css_parser::CssParser::new(&css_input).parse_stylesheet();
+    let mut parser = css_parser::CssParser::new(&css_input);
+    let stylesheet = parser.parse_stylesheet();
+
+    //same for the stylesheet, everything that was skipped
+    for error in parser.errors() {
+        eprintln!("{}:{}", path.display(), error);
+    }
     stylesheet
 }
\ No newline at end of file
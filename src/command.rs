@@ -3,6 +3,7 @@
 //For instance one for render some text, another one for draw rectange, we put these commands into our display list then we can use that list to search out items that might
 //be completely covered up by later operations & remove them to eliminate wasteful rendering
 //You can also modify & reuse display list incase where u know only certain items have been changed, libraries like react that use Dom diffing and take advantage of this type of behavior
+//dom_diff.rs can tell which parts of the Dom changed between two versions of a page
 //1st type of our display list is going to be our command module
 
 use crate::css::{Color, Value};
@@ -1,8 +1,8 @@
 /* CSS is a series of rules it include one or more selectors which are separated by commas followed by series of declarations which are enclosed in braces
  * these selectors can be simple selector or chain of selectors joined by 'Combinators'
- * our implementation is only going to support simple selection & not going to support cascading, cascading is when we can have multiple CSS sheets
+ * our implementation supports simple selectors & the combinators between them (' ', '>', '+', '~') but not cascading, cascading is when we can have multiple CSS sheets
  * and they can override one another and bec. its fairy difficult function to put into our browser engine we're going to just ignore it for now
- * in our browser engine a simple selector can include a tag_name an Id prefixed by number sign and any number of class names prefixed by period or combination
+ * in our browser engine a simple selector can include a tag_name an Id prefixed by number sign, any number of class names prefixed by period, attribute selectors in [] or combination
  * of the above if tag name is empty or has asterix in it then it is a universal selector which will match it with any tag 
  * there are many types of selectors in CSS particular in CSS3 but we will deal with these for now
  */
@@ -10,43 +10,237 @@
 use std::fmt;   //bec. we gonna implement debug for some of our data structures
 use std::default::Default; //allow us to put default values inside of our data structures
 
+use crate::source::Span;  //where rules and declarations came from in the stylesheet
+use crate::css_parser::{is_color, is_length}; //what @supports accepts as a value
+use crate::style::WhiteSpace;
+
 // NOTE 1- Data Structures
 
 pub struct Stylesheet {
-    pub rules: Vec<Rule>,
+    pub rules: Vec<CssRule>, //in the order they were written, later rules win
+}
+
+//A style rule or one of the at-rules we understand
+pub enum CssRule {
+    Style(Rule),
+    Media(MediaRule),
+    Supports(SupportsRule),
+    Import(ImportRule),
+    FontFace(FontFaceRule),
+    Keyframes(KeyframesRule),
 }
 
 pub struct Rule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    pub span: Span,
+}
+
+//@media screen and (min-width: 600px) { rules }
+#[derive(Debug)]
+pub struct MediaRule {
+    pub media: Vec<MediaQuery>,
+    pub rules: Vec<CssRule>,
+    pub span: Span,
+}
+
+//@supports (display: grid) { rules }
+#[derive(Debug)]
+pub struct SupportsRule {
+    pub condition: SupportsCondition,
+    pub rules: Vec<CssRule>,
+    pub span: Span,
+}
+
+//@import url("print.css") print; the stylesheet itself isn't fetched, whoever loads pages can do that
+#[derive(Debug)]
+pub struct ImportRule {
+    pub url: String,
+    pub media: Vec<MediaQuery>, //empty when it applies everywhere
+    pub span: Span,
+}
+
+//@font-face { font-family: ...; src: ... }, the declarations are descriptors of the font
+#[derive(Debug)]
+pub struct FontFaceRule {
+    pub descriptors: Vec<Declaration>,
+    pub span: Span,
+}
+
+//@keyframes name { from { ... } 50% { ... } to { ... } }
+#[derive(Debug)]
+pub struct KeyframesRule {
+    pub name: String,
+    pub keyframes: Vec<Keyframe>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Keyframe {
+    pub offsets: Vec<f32>, //percentages, from is 0 and to is 100
+    pub declarations: Vec<Declaration>,
+    pub span: Span,
+}
+
+//One query of a media list, "not screen and (max-width: 600px)"
+//A query we can't read becomes "not all" like in the browser, so it never matches
+#[derive(Debug)]
+pub struct MediaQuery {
+    pub negated: bool,
+    pub media_type: Option<String>, //None is the same as all
+    pub features: Vec<MediaFeature>,
 }
 
+//(min-width: 600px) or just (color)
+#[derive(Debug)]
+pub struct MediaFeature {
+    pub name: String,
+    pub value: Option<Value>,
+}
+
+#[derive(Debug)]
+pub enum SupportsCondition {
+    Declaration(String, String), //(property: value)
+    Not(Box<SupportsCondition>),
+    And(Vec<SupportsCondition>),
+    Or(Vec<SupportsCondition>),
+    Unknown(String), //selector(), a function or anything else we can't check, always false
+}
+
+//What the page is shown on, the media queries of @media and @import are checked against it
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub media_type: String, //screen or print
+    pub width: f32,         //of the viewport in px
+    pub height: f32,
+}
+
+#[derive(PartialEq)]
 pub struct Selector {
     pub simple: Vec<SimpleSelector>,
     pub combinators: Vec<char>,
-    //simple: to account for simple selectors
+    pub pseudo_element: Option<PseudoElement>, //p::first-line, the rule styles that part of the element instead of the element
+    //simple: the compound selectors from left to right, "ul.nav > li a" is ul.nav, li and a
+    //combinators: what's between simple[i] and simple[i + 1], ' ' (descendant), '>' (child), '+' (next sibling)
+    //or '~' (any later sibling), so there's always one less of them than of simple selectors
 }
 
+#[derive(PartialEq)]
 pub struct SimpleSelector {
     pub tag_name: Option<String>,
     pub id: Option<String>,
     pub classes: Vec<String>,
+    pub attributes: Vec<AttributeSelector>,
+    pub pseudo_classes: Vec<PseudoClass>,
     //Selectors can have multiple classes on them & we want them to gave single id as well as their tag name
 }
 
+//[name], [name=value], [name~=value] ... with an optional i or s flag at the end
+#[derive(PartialEq)]
+pub struct AttributeSelector {
+    pub name: String,
+    pub operator: Option<AttrOperator>, //None for [name], the attribute just has to be there
+    pub value: String,
+    pub case: AttrCase,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AttrOperator {
+    Equals,    //=  exactly the value
+    Includes,  //~= one of the whitespace separated words is the value
+    DashMatch, //|= the value or the value followed by '-' (lang codes, [lang|=en] matches en-US)
+    Prefix,    //^= starts with the value
+    Suffix,    //$= ends with the value
+    Substring, //*= has the value somewhere in it
+}
+
+//:name or :name(...) after the rest of a compound selector
+#[derive(PartialEq)]
+pub enum PseudoClass {
+    //where the element is in the tree
+    Root,
+    Empty, //no element or text children, comments don't count
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    FirstOfType,
+    LastOfType,
+    OnlyOfType,
+    NthChild(Nth, Vec<Selector>), //:nth-child(2n+1 of .item), only siblings matching the list are counted, empty without "of"
+    NthLastChild(Nth, Vec<Selector>),
+    NthOfType(Nth),
+    NthLastOfType(Nth),
+    //selectors inside selectors
+    Not(Vec<Selector>),
+    Is(Vec<Selector>),
+    Where(Vec<Selector>), //same as :is, it only differs in specificity which we don't have
+    Has(Vec<RelativeSelector>),
+    //the element's state, see dom::ElementState
+    Hover,
+    Focus,
+    Active,
+    Checked,
+    Disabled,
+    Enabled, //a form control that isn't disabled
+}
+
+//Parts of an element that aren't in the Dom, a ::before or ::after box with generated content, the bullet of
+//a list item, or the first line or letter of the element's text
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PseudoElement {
+    Before,
+    After,
+    Marker,
+    FirstLine,
+    FirstLetter,
+}
+
+//The an+b of :nth-child, odd is 2n+1, even is 2n, 3 is 0n+3
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Nth {
+    pub a: i32,
+    pub b: i32,
+}
+
+//A selector inside :has(), starting from the element it's on, "> img" or "+ p" (just "img" is the descendant ' ')
+#[derive(PartialEq)]
+pub struct RelativeSelector {
+    pub combinator: char,
+    pub selector: Selector,
+}
+
+//How values are compared, without a flag HTML decides per attribute (type="TEXT" is the same as type="text")
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AttrCase {
+    Default,
+    Insensitive, //the i flag
+    Sensitive,   //the s flag
+}
+
 pub struct Declaration {
     pub property: String,
     pub value: Value,
+    pub important: bool, //written with !important at the end
+    pub span: Span,
     //This is similar to like a HashMap u have property that u want to affect & then the value u want to set in that property
 }
 
 pub enum Value {
     Color(Color),
     Length(f32, Unit),
+    Content(Vec<ContentItem>), //the content property of ::before, ::after and ::marker, none and normal are Other
     Other(String),
     //In our CSS implementation our values can be Colors, Length, Other
 }
 
+//One piece of a content value, put together they're the text of the generated box
+pub enum ContentItem {
+    String(String),
+    Attr(String), //attr(title), the value of the element's attribute, nothing when it doesn't have it
+    OpenQuote,
+    CloseQuote,
+}
+
 pub enum Unit {
     Em, //calculated or inherited font size
     Ex, //the height of fonts x character
@@ -77,9 +271,17 @@ pub struct Color {
 // NOTE 2- Implement Methods For These DataStructures
 
 impl Stylesheet {
-    pub fn new (rules: Vec<Rule>) -> Stylesheet {
+    pub fn new (rules: Vec<CssRule>) -> Stylesheet {
         Stylesheet { rules }
     }
+
+    //Every style rule that applies on this device, in order, the ones inside @media and @supports blocks included
+    //when their condition holds (@import isn't loaded, so nothing comes from there)
+    pub fn style_rules(&self, device: &Device) -> Vec<&Rule> {
+        let mut result = Vec::new();
+        collect_style_rules(&self.rules, device, &mut result);
+        result
+    }
 }
 impl Default for Stylesheet {   //Implement Deafult trait so we can put default values in it
     fn default() -> Self { //these 'Self' refer to Stylesheet, we will output stylesheet object with a empty vector for the rule
@@ -88,14 +290,127 @@ impl Default for Stylesheet {   //Implement Deafult trait so we can put default
 }
 impl fmt::Debug for Stylesheet {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut rule_result = String::new();
-        for rule in &self.rules { //self.rules is vector of rules
-            if rule_result.len() > 0 {  //Add new line if its the second line or more
-                rule_result.push_str("\n\n");
+        write!(f, "{}", rules_text(&self.rules))
+    }
+}
+
+impl fmt::Debug for CssRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CssRule::Style(ref rule) => write!(f, "{:?}", rule),
+            CssRule::Media(ref rule) => write!(f, "@media {} {{\n{}\n}}", media_list_text(&rule.media), rules_text(&rule.rules)),
+            CssRule::Supports(ref rule) => write!(f, "@supports {:?} {{\n{}\n}}", rule.condition, rules_text(&rule.rules)),
+            CssRule::Import(ref rule) if rule.media.is_empty() => write!(f, "@import {:?};", rule.url),
+            CssRule::Import(ref rule) => write!(f, "@import {:?} {};", rule.url, media_list_text(&rule.media)),
+            CssRule::FontFace(ref rule) => write!(f, "@font-face {{\n{}}}", declarations_text(&rule.descriptors)),
+            CssRule::Keyframes(ref rule) => {
+                let mut result = String::new();
+                for keyframe in &rule.keyframes {
+                    let offsets: Vec<String> = keyframe.offsets.iter().map(|o| format!("{}%", o)).collect();
+                    result.push_str(&format!("{} {{\n{}}}\n", offsets.join(", "), declarations_text(&keyframe.declarations)));
+                }
+                write!(f, "@keyframes {} {{\n{}}}", rule.name, result)
+            }
+        }
+    }
+}
+
+impl MediaQuery {
+    //"not all", what a query we can't read turns into
+    pub fn never() -> MediaQuery {
+        MediaQuery { negated: true, media_type: None, features: Vec::new() }
+    }
+
+    pub fn matches(&self, device: &Device) -> bool {
+        let type_matches = match self.media_type {
+            Some(ref t) => t == "all" || *t == device.media_type,
+            None => true,
+        };
+        (type_matches && self.features.iter().all(|feature| feature.matches(device))) != self.negated
+    }
+}
+impl fmt::Display for MediaQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.negated {
+            parts.push(String::from("not"));
+        }
+        match self.media_type {
+            Some(ref t) => parts.push(t.clone()),
+            None if self.features.is_empty() => parts.push(String::from("all")),
+            None => {}
+        }
+        for feature in &self.features {
+            if !parts.is_empty() {
+                parts.push(String::from("and"));
+            }
+            match feature.value {
+                Some(ref value) => parts.push(format!("({}: {:?})", feature.name, value)),
+                None => parts.push(format!("({})", feature.name)),
             }
-            rule_result.push_str(&format!("{:?}", rule)); //add our rule debug to string
         }
-        write!(f, "{}", rule_result)
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+impl MediaFeature {
+    //Only the features a window has, width, height, their min-/max- versions and orientation
+    //Anything else is false so the rules behind it don't apply
+    pub fn matches(&self, device: &Device) -> bool {
+        let value = match self.value {
+            Some(ref value) => value,
+            None => {
+                //(width) alone is true when the width isn't 0
+                return match self.name.as_str() {
+                    "width" => device.width > 0.0,
+                    "height" => device.height > 0.0,
+                    "color" | "orientation" => true,
+                    _ => false,
+                };
+            }
+        };
+        if self.name == "orientation" {
+            let portrait = device.height >= device.width;
+            return match *value {
+                Value::Other(ref o) => match o.as_str() {
+                    "portrait" => portrait,
+                    "landscape" => !portrait,
+                    _ => false,
+                },
+                _ => false,
+            };
+        }
+        let length = match length_in_px(value) {
+            Some(length) => length,
+            None => return false,
+        };
+        match self.name.as_str() {
+            "width" => device.width == length,
+            "min-width" => device.width >= length,
+            "max-width" => device.width <= length,
+            "height" => device.height == length,
+            "min-height" => device.height >= length,
+            "max-height" => device.height <= length,
+            _ => false,
+        }
+    }
+}
+
+impl SupportsCondition {
+    pub fn matches(&self) -> bool {
+        match *self {
+            SupportsCondition::Declaration(ref property, ref value) => is_supported_declaration(property, value),
+            SupportsCondition::Not(ref condition) => !condition.matches(),
+            SupportsCondition::And(ref conditions) => conditions.iter().all(|c| c.matches()),
+            SupportsCondition::Or(ref conditions) => conditions.iter().any(|c| c.matches()),
+            SupportsCondition::Unknown(_) => false,
+        }
+    }
+}
+
+impl Default for Device {
+    fn default() -> Self { //the window main.rs opens
+        Device { media_type: String::from("screen"), width: 1024.0, height: 768.0 }
     }
 }
 
@@ -104,6 +419,7 @@ impl Rule {
         Rule {
             selectors,
             declarations,
+            span: Span::default(),
         }
     }
 }
@@ -112,6 +428,7 @@ impl Default for Rule {   //Implement Deafult trait so we can put default values
         Rule {
             selectors: Vec::new(),
             declarations: Vec::new(),
+            span: Span::default(),
         }
     }
 }
@@ -143,6 +460,7 @@ impl Selector {
         Selector {
             simple,
             combinators,
+            pseudo_element: None,
         }
     }
 }
@@ -151,6 +469,7 @@ impl Default for Selector {   //Implement Deafult trait so we can put default va
         Selector {
             simple: Vec::new(),
             combinators: Vec::new(),
+            pseudo_element: None,
         }
     }
 }
@@ -158,13 +477,21 @@ impl fmt::Debug for Selector {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut result = String::new();
 
-        for sel in &self.simple {
-            if result.len() > 0 {   //if second selector or more add commna and whitespace
-                result.push_str(", ");
+        for (i, sel) in self.simple.iter().enumerate() {
+            if i > 0 {   //if second selector or more add the combinator before it
+                match self.combinators.get(i - 1) {
+                    Some(' ') | None => result.push(' '),
+                    Some(c) => result.push_str(&format!(" {} ", c)),
+                }
             }
             result.push_str(&format!("{:?}", sel)); //add it to the string
         }
 
+        if let Some(pseudo_element) = self.pseudo_element {
+            result.push_str("::");
+            result.push_str(pseudo_element.name());
+        }
+
         write!(f, "{}", result) 
     }
 }
@@ -175,6 +502,8 @@ impl SimpleSelector {
             tag_name,
             id,
             classes,
+            attributes: Vec::new(),
+            pseudo_classes: Vec::new(),
         }
     }
 }
@@ -184,6 +513,8 @@ impl Default for SimpleSelector {   //Implement Deafult trait so we can put defa
             tag_name: None,
             id: None,
             classes: Vec::new(),
+            attributes: Vec::new(),
+            pseudo_classes: Vec::new(),
         }
     }
 }
@@ -209,15 +540,138 @@ impl fmt::Debug for SimpleSelector {
             result.push_str(class); //add the class to our string
         }
 
+        for attribute in &self.attributes {
+            result.push_str(&format!("{:?}", attribute));
+        }
+
+        for pseudo_class in &self.pseudo_classes {
+            result.push_str(&format!("{:?}", pseudo_class));
+        }
+
+        if result.is_empty() {  //nothing to match on, the universal selector
+            result.push('*');
+        }
+
         write!(f, "{}", result)
     }
 } 
 
+impl AttributeSelector {
+    //Does an element's value for the attribute match, `html` is whether the element is an HTML element
+    pub fn value_matches(&self, value: &str, html: bool) -> bool {
+        let insensitive = match self.case {
+            AttrCase::Insensitive => true,
+            AttrCase::Sensitive => false,
+            AttrCase::Default => html && is_case_insensitive_attribute(&self.name),
+        };
+        let (value, wanted) = if insensitive {
+            (value.to_ascii_lowercase(), self.value.to_ascii_lowercase())
+        } else {
+            (value.to_string(), self.value.clone())
+        };
+        let operator = match self.operator {
+            Some(operator) => operator,
+            None => return true,
+        };
+        match operator {
+            AttrOperator::Equals => value == wanted,
+            AttrOperator::Includes => {
+                !wanted.is_empty()
+                    && !wanted.contains(|c: char| c.is_ascii_whitespace())
+                    && value.split(|c: char| c.is_ascii_whitespace()).any(|word| word == wanted)
+            }
+            AttrOperator::DashMatch => value == wanted || value.starts_with(&format!("{}-", wanted)),
+            //an empty value never matches these, [href^=""] matches nothing
+            AttrOperator::Prefix => !wanted.is_empty() && value.starts_with(&wanted),
+            AttrOperator::Suffix => !wanted.is_empty() && value.ends_with(&wanted),
+            AttrOperator::Substring => !wanted.is_empty() && value.contains(&wanted),
+        }
+    }
+}
+impl fmt::Debug for AttributeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operator = match self.operator {
+            Some(AttrOperator::Equals) => "=",
+            Some(AttrOperator::Includes) => "~=",
+            Some(AttrOperator::DashMatch) => "|=",
+            Some(AttrOperator::Prefix) => "^=",
+            Some(AttrOperator::Suffix) => "$=",
+            Some(AttrOperator::Substring) => "*=",
+            None => return write!(f, "[{}]", self.name),
+        };
+        let flag = match self.case {
+            AttrCase::Default => "",
+            AttrCase::Insensitive => " i",
+            AttrCase::Sensitive => " s",
+        };
+        write!(f, "[{}{}{:?}{}]", self.name, operator, self.value, flag)
+    }
+}
+
+impl PseudoElement {
+    //As it's written after the "::"
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PseudoElement::Before => "before",
+            PseudoElement::After => "after",
+            PseudoElement::Marker => "marker",
+            PseudoElement::FirstLine => "first-line",
+            PseudoElement::FirstLetter => "first-letter",
+        }
+    }
+}
+
+impl Nth {
+    //Is the element at this position (counting from 1) one of an+b for some n >= 0
+    //i64 since a and b can be anything an i32 holds, position - b doesn't always fit in one
+    pub fn matches(&self, position: i32) -> bool {
+        let (a, b) = (i64::from(self.a), i64::from(self.b));
+        if a == 0 {
+            return i64::from(position) == b;
+        }
+        let steps = i64::from(position) - b;
+        steps % a == 0 && steps / a >= 0
+    }
+}
+impl fmt::Debug for PseudoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PseudoClass::Root => write!(f, ":root"),
+            PseudoClass::Empty => write!(f, ":empty"),
+            PseudoClass::FirstChild => write!(f, ":first-child"),
+            PseudoClass::LastChild => write!(f, ":last-child"),
+            PseudoClass::OnlyChild => write!(f, ":only-child"),
+            PseudoClass::FirstOfType => write!(f, ":first-of-type"),
+            PseudoClass::LastOfType => write!(f, ":last-of-type"),
+            PseudoClass::OnlyOfType => write!(f, ":only-of-type"),
+            PseudoClass::NthChild(nth, ref of) => write!(f, ":nth-child({}{})", nth_text(nth), of_text(of)),
+            PseudoClass::NthLastChild(nth, ref of) => write!(f, ":nth-last-child({}{})", nth_text(nth), of_text(of)),
+            PseudoClass::NthOfType(nth) => write!(f, ":nth-of-type({})", nth_text(nth)),
+            PseudoClass::NthLastOfType(nth) => write!(f, ":nth-last-of-type({})", nth_text(nth)),
+            PseudoClass::Not(ref list) => write!(f, ":not({})", selectors_text(list)),
+            PseudoClass::Is(ref list) => write!(f, ":is({})", selectors_text(list)),
+            PseudoClass::Where(ref list) => write!(f, ":where({})", selectors_text(list)),
+            PseudoClass::Has(ref list) => {
+                let list: Vec<String> = list.iter().map(|r| format!("{} {:?}", r.combinator, r.selector).trim_start().to_string()).collect();
+                write!(f, ":has({})", list.join(", "))
+            }
+            PseudoClass::Hover => write!(f, ":hover"),
+            PseudoClass::Focus => write!(f, ":focus"),
+            PseudoClass::Active => write!(f, ":active"),
+            PseudoClass::Checked => write!(f, ":checked"),
+            PseudoClass::Disabled => write!(f, ":disabled"),
+            PseudoClass::Enabled => write!(f, ":enabled"),
+        }
+    }
+}
+
 impl Declaration {
     pub fn new(property: String, value: Value) -> Declaration {
         Declaration {
             property,
             value,
+            important: false,
+            span: Span::default(),
         }
     }
 }
@@ -225,13 +679,16 @@ impl Default for Declaration {    //Implement Deafult trait so we can put defaul
     fn default() -> Self {
         Declaration {
             property: String::from(""),
-            value: Value::Other(String::from(""))
+            value: Value::Other(String::from("")),
+            important: false,
+            span: Span::default(),
         }
     }
 }
 impl fmt::Debug for Declaration {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}: {:?}", self.property, self.value)
+        let important = if self.important { " !important" } else { "" };
+        write!(f, "{}: {:?}{}", self.property, self.value, important)
     }
 }
 
@@ -240,6 +697,15 @@ impl fmt::Debug for Value { //Debug for our types
         match *self {
             Value::Color(ref c) => write!(f, "{:?}", c),
             Value::Length(l, _) => write!(f, "{:?}", l),
+            Value::Content(ref items) => {
+                let items: Vec<String> = items.iter().map(|item| match *item {
+                    ContentItem::String(ref s) => format!("{:?}", s),
+                    ContentItem::Attr(ref name) => format!("attr({})", name),
+                    ContentItem::OpenQuote => String::from("open-quote"),
+                    ContentItem::CloseQuote => String::from("close-quote"),
+                }).collect();
+                write!(f, "{}", items.join(" "))
+            }
             Value::Other(ref s) => write!(f, "{:?}", s),
         }
     }
@@ -266,4 +732,106 @@ impl fmt::Debug for Color {
 
 
 
-// NOTE 3- Helper Methods
\ No newline at end of file
+// NOTE 3- Helper Methods
+
+fn nth_text(nth: Nth) -> String {
+    match (nth.a, nth.b) {
+        (0, b) => format!("{}", b),
+        (a, 0) => format!("{}n", a),
+        (a, b) if b < 0 => format!("{}n{}", a, b),
+        (a, b) => format!("{}n+{}", a, b),
+    }
+}
+
+fn of_text(of: &[Selector]) -> String {
+    if of.is_empty() {
+        return String::new();
+    }
+    format!(" of {}", selectors_text(of))
+}
+
+fn selectors_text(selectors: &[Selector]) -> String {
+    let list: Vec<String> = selectors.iter().map(|selector| format!("{:?}", selector)).collect();
+    list.join(", ")
+}
+
+//Rules one after the other with an empty line between them, for the Debug output
+fn rules_text(rules: &[CssRule]) -> String {
+    let mut rule_result = String::new();
+    for rule in rules {
+        if !rule_result.is_empty() {  //Add new line if its the second line or more
+            rule_result.push_str("\n\n");
+        }
+        rule_result.push_str(&format!("{:?}", rule)); //add our rule debug to string
+    }
+    rule_result
+}
+
+fn declarations_text(declarations: &[Declaration]) -> String {
+    let mut result = String::new();
+    for declaration in declarations {
+        result.push_str(&format!("     {:?}\n", declaration));
+    }
+    result
+}
+
+fn media_list_text(media: &[MediaQuery]) -> String {
+    let queries: Vec<String> = media.iter().map(|query| query.to_string()).collect();
+    queries.join(", ")
+}
+
+fn collect_style_rules<'a>(rules: &'a [CssRule], device: &Device, result: &mut Vec<&'a Rule>) {
+    for rule in rules {
+        match *rule {
+            CssRule::Style(ref rule) => result.push(rule),
+            CssRule::Media(ref rule) if media_matches(&rule.media, device) => collect_style_rules(&rule.rules, device, result),
+            CssRule::Supports(ref rule) if rule.condition.matches() => collect_style_rules(&rule.rules, device, result),
+            _ => {}
+        }
+    }
+}
+
+//A media list matches when any of its queries does, an empty list is the same as "all"
+pub fn media_matches(media: &[MediaQuery], device: &Device) -> bool {
+    media.is_empty() || media.iter().any(|query| query.matches(device))
+}
+
+//Lengths of media features in px, em and rem are the default 16px font size since there's no element to ask
+fn length_in_px(value: &Value) -> Option<f32> {
+    match *value {
+        Value::Length(n, Unit::Px) => Some(n),
+        Value::Length(n, Unit::Em) | Value::Length(n, Unit::Rem) => Some(n * 16.0),
+        Value::Length(n, Unit::In) => Some(n * 96.0),
+        Value::Length(n, Unit::Cm) => Some(n * 96.0 / 2.54),
+        Value::Length(n, Unit::Mm) => Some(n * 96.0 / 25.4),
+        Value::Length(n, Unit::Pt) => Some(n * 96.0 / 72.0),
+        Value::Length(n, Unit::Pc) => Some(n * 16.0),
+        _ => None,
+    }
+}
+
+//Attributes whose values HTML compares without caring about case, unless the selector has the s flag
+//https://html.spec.whatwg.org/multipage/semantics-other.html#case-sensitivity-of-selectors
+fn is_case_insensitive_attribute(name: &str) -> bool {
+    const NAMES: &[&str] = &[
+        "accept", "accept-charset", "align", "alink", "axis", "bgcolor", "charset", "checked", "clear", "codetype",
+        "color", "compact", "declare", "defer", "dir", "direction", "disabled", "enctype", "face", "frame", "hreflang",
+        "http-equiv", "lang", "language", "link", "media", "method", "multiple", "nohref", "noresize", "noshade",
+        "nowrap", "readonly", "rel", "rev", "rules", "scope", "scrolling", "selected", "shape", "target", "text",
+        "type", "valign", "valuetype", "vlink",
+    ];
+    NAMES.contains(&name.to_ascii_lowercase().as_str())
+}
+
+//The properties style and layout actually do something with, with a value they can read, what @supports answers yes to
+fn is_supported_declaration(property: &str, value: &str) -> bool {
+    match property {
+        "display" => matches!(value.to_ascii_lowercase().as_str(), "block" | "inline" | "inline-block" | "list-item" | "none"),
+        "white-space" => WhiteSpace::from_keyword(value).is_some(),
+        "color" | "background-color" | "border-color" => is_color(value),
+        "width" | "height" | "margin-top" | "margin-right" | "margin-bottom" | "margin-left" | "padding-top"
+        | "padding-right" | "padding-bottom" | "padding-left" | "border-top-width" | "border-right-width"
+        | "border-bottom-width" | "border-left-width" => is_length(value),
+        _ => property.starts_with("--"), //custom properties are always kept, whatever is in them
+    }
+}
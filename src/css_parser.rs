@@ -3,205 +3,1108 @@
  * unlike a language like rust for instance if it runs into error it won't automatically just stop parsing this is useful because it
  * allows us to have multiple different syntaxes maybee syntaxes that are recognized for older browsers and newer browsers
  * so our CSS parser is going to be very similar to our HTML parser 
+ * Just like there, the css_tokenizer cuts the stylesheet into tokens first and the parser only ever looks at tokens,
+ * rules and declarations are read with the algorithms of CSS Syntax Level 3: https://www.w3.org/TR/css-syntax-3/#parsing
+ * Tokens are grouped into component values first, a {} [] () block or a function always comes with everything up to
+ * its matching close, so a ';' or '}' inside a string, url or nested block can never end a declaration or a rule early
  */
-use css::{Color, Declaration, Rule, Selector, SimpleSelector, Stylesheet, Unit, Value};
+use crate::css::{
+    AttrCase, AttrOperator, AttributeSelector, Color, ContentItem, CssRule, Declaration, FontFaceRule, ImportRule, Keyframe, KeyframesRule,
+    MediaFeature, MediaQuery, MediaRule, Nth, PseudoClass, PseudoElement, RelativeSelector, Rule, Selector, SimpleSelector, Stylesheet,
+    SupportsCondition, SupportsRule, Unit, Value,
+};
+use crate::css_tokenizer::{CssToken, CssTokenizer, CssTokenizerError};
+use crate::source::Span;
 
-use std::iter::Peekable;
-use std::str::Chars;
+use std::fmt;
 
-//NOTE: Main structure for our CSS parser will have lifetime a
-pub struct CssParser<'a> {
-    chars: Peekable<Chars<'a>>, //iterator of charachters that is pickable
+//NOTE: Parse errors, the parser still skips over the broken part and keeps going but callers can see what was dropped
+//The span of each error is the whole part that was skipped (the rule, the declaration ...)
+#[derive(Debug, Clone, PartialEq)]
+pub enum CssErrorKind {
+    InvalidSelector(String),    //part of a selector we don't understand, the whole rule is skipped
+    InvalidDeclaration(String), //declaration without a property, a ':' or a value, skipped up to the next ';'
+    UnsupportedAtRule(String),  //an at-rule we don't know, skipped up to its ';' or through its block
+    InvalidAtRule(String),      //one we know but with a prelude or block it can't have, skipped the same way
+    UnexpectedEof,              //the stylesheet ended before the '}' of a rule
+    BadToken(CssTokenizerError), //broken comment, string, url or escape, the tokenizer already recovered
 }
 
-impl <'a> CssParser<'a> {
+#[derive(Debug, Clone, PartialEq)]
+pub struct CssParseError {
+    pub kind: CssErrorKind,
+    pub span: Span,
+}
+
+impl CssParseError {
+    pub fn new(kind: CssErrorKind, span: Span) -> CssParseError {
+        CssParseError { kind, span }
+    }
+}
+impl fmt::Display for CssParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+impl fmt::Display for CssErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CssErrorKind::InvalidSelector(ref s) => write!(f, "invalid selector `{}`, skipped", s),
+            CssErrorKind::InvalidDeclaration(ref s) => write!(f, "invalid declaration `{}`, skipped", s),
+            CssErrorKind::UnsupportedAtRule(ref s) => write!(f, "unsupported at-rule @{}, skipped", s),
+            CssErrorKind::InvalidAtRule(ref s) => write!(f, "invalid @{} rule, skipped", s),
+            CssErrorKind::UnexpectedEof => write!(f, "unexpected end of stylesheet"),
+            CssErrorKind::BadToken(ref e) => write!(f, "{:?}", e),
+        }
+    }
+}
+
+//A single token, or a block or function with everything up to its matching close inside of it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ComponentValue {
+    Token(CssToken, Span),
+    Block { open: CssToken, contents: Vec<ComponentValue>, span: Span }, //open is '{', '[' or '('
+    Function { name: String, arguments: Vec<ComponentValue>, span: Span },
+}
+
+//NOTE: Main structure for our CSS parser
+pub struct CssParser {
+    tokens: Vec<(CssToken, Span)>, //the whole stylesheet already tokenized, ends with Eof
+    pos: usize,
+    errors: Vec<CssParseError>,
+    open_blocks: usize, //blocks and functions being read right now, nested ones included
+}
+
+impl CssParser {
     pub fn new(full_css: &str) -> CssParser {
+        let mut tokenizer = CssTokenizer::new(full_css);
+        let tokens = tokenizer.tokenize();
+        //what the tokenizer had to fix up comes first, it's reported where it happened
+        let errors = tokenizer.take_errors().into_iter().map(|(e, span)| CssParseError::new(CssErrorKind::BadToken(e), span)).collect();
         CssParser {
-            chars: full_css.chars().peekable(),
+            tokens,
+            pos: 0,
+            errors,
+            open_blocks: 0,
         }
     }
 
-    //Main entry point for our stylesheet parser
+    //Main entry point for our stylesheet parser, "consume a list of rules" at the top level
     pub fn parse_stylesheet(&mut self) -> Stylesheet {
-        let mut stylesheet = Stylesheet::default(); //create default one
+        let values = self.consume_rest();
+        let rules = self.parse_rules(&values, true);
+        //blocks were all read before any rule was looked at, put the errors back in stylesheet order
+        self.errors.sort_by_key(|error| error.span.start);
+        Stylesheet::new(rules)
+    }
+
+    //Just a comma separated list of selectors without a rule around it, like the argument of querySelector
+    //An invalid list gives back no selectors at all, with an InvalidSelector error
+    pub fn parse_selector_list(&mut self) -> Vec<Selector> {
+        let start = self.span();
+        let values = self.consume_rest();
+        match self.parse_selectors(&values) {
+            Ok(selectors) => selectors,
+            Err(text) => {
+                let span = start.to(self.span());
+                self.error(CssErrorKind::InvalidSelector(text), span);
+                Vec::new()
+            }
+        }
+    }
+
+    //Everything the parser had to skip, in the order it was found
+    pub fn errors(&self) -> &[CssParseError] {
+        &self.errors
+    }
+
+    fn error(&mut self, kind: CssErrorKind, span: Span) {
+        self.errors.push(CssParseError::new(kind, span));
+    }
+
+    //ANCHOR Tokens
+    fn peek(&self) -> &CssToken {
+        &self.tokens[self.pos].0
+    }
+
+    fn span(&self) -> Span {
+        self.tokens[self.pos].1
+    }
+
+    //Eof is never stepped over, so peek always has something to look at
+    fn advance(&mut self) {
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+    }
 
-        whilte self.chars.peek().is_some() {    //iterate through each characters while it is Some no NONE
-            let selectors = self.parse_selectors(); //identify our selectors
-            let styles = self.parse_declarations(); //identify our styles
-            let rule = Rule::new(selectors, style); //our rules
+    //ANCHOR Rules
+    //"Consume a list of rules" over the whole stylesheet or the block of an @media or @supports
+    //A rule ends with its {} block, an at-rule with its ';' or its block
+    fn parse_rules(&mut self, values: &[ComponentValue], top_level: bool) -> Vec<CssRule> {
+        let mut rules = Vec::new();
+        let mut i = 0;
 
-            stylesheet.rules.push(rule);   //put them in stylesheet rules
+        while i < values.len() {
+            match values[i] {
+                ComponentValue::Token(CssToken::Whitespace, _) | ComponentValue::Token(CssToken::Cdo, _) | ComponentValue::Token(CssToken::Cdc, _) => i += 1,
+                ComponentValue::Token(CssToken::AtKeyword(ref name), _) => {
+                    let mut end = i + 1;
+                    while end < values.len() && !is_token(&values[end], &CssToken::Semicolon) && curly_block(&values[end]).is_none() {
+                        end += 1;
+                    }
+                    let block = values.get(end).and_then(curly_block);
+                    let span = values[i].span().to(values[end.min(values.len() - 1)].span());
+                    //@import has to come before every other rule of the stylesheet
+                    let import_allowed = top_level && rules.iter().all(|rule| matches!(*rule, CssRule::Import(_)));
+                    if let Some(rule) = self.parse_at_rule(&name.to_ascii_lowercase(), &values[i + 1..end.min(values.len())], block, span, import_allowed) {
+                        rules.push(rule);
+                    }
+                    i = end + 1;
+                }
+                _ => match (i..values.len()).find(|&j| curly_block(&values[j]).is_some()) {
+                    Some(end) => {
+                        let span = values[i].span().to(values[end].span());
+                        if let Some(rule) = self.parse_style_rule(&values[i..end], curly_block(&values[end]).unwrap(), span) {
+                            rules.push(CssRule::Style(rule));
+                        }
+                        i = end + 1;
+                    }
+                    None => {
+                        //a selector with no block after it, nothing to keep
+                        let span = values[i].span().to(values[values.len() - 1].span());
+                        if top_level {
+                            self.error(CssErrorKind::UnexpectedEof, span);
+                        } else {
+                            self.error(CssErrorKind::InvalidSelector(serialize(trim_whitespace(&values[i..]))), span);
+                        }
+                        break;
+                    }
+                },
+            }
         }
 
-        stylesheet
+        rules
     }
 
-    fn parse_selectors(&mut self) -> Vec<Selector> {
-        let mut selectors = Vec::new(); //create empty selector
+    //A selector list and its {} block, a rule with a selector we can't read is skipped as a whole
+    fn parse_style_rule(&mut self, prelude: &[ComponentValue], contents: &[ComponentValue], span: Span) -> Option<Rule> {
+        let selectors = match self.parse_selectors(prelude) { //identify our selectors
+            Ok(selectors) => selectors,
+            Err(text) => {
+                self.error(CssErrorKind::InvalidSelector(text), span);
+                return None;
+            }
+        };
+        let styles = self.parse_declarations(contents); //identify our styles
+        let mut rule = Rule::new(selectors, styles); //our rules
+        rule.span = span;
+        Some(rule)
+    }
+
+    //The at-rules we understand, an unknown one (or a known one written wrong) is skipped with an error
+    fn parse_at_rule(&mut self, name: &str, prelude: &[ComponentValue], block: Option<&[ComponentValue]>, span: Span, import_allowed: bool) -> Option<CssRule> {
+        let prelude = trim_whitespace(prelude);
+        let rule = match (name, block) {
+            ("media", Some(block)) => CssRule::Media(MediaRule {
+                media: parse_media_list(prelude),
+                rules: self.parse_rules(block, false),
+                span,
+            }),
+            ("supports", Some(block)) if !prelude.is_empty() => CssRule::Supports(SupportsRule {
+                condition: parse_supports_condition(prelude),
+                rules: self.parse_rules(block, false),
+                span,
+            }),
+            ("import", None) if import_allowed => match parse_import(prelude) {
+                Some((url, media)) => CssRule::Import(ImportRule { url, media, span }),
+                None => return self.invalid_at_rule(name, span),
+            },
+            ("font-face", Some(block)) if prelude.is_empty() => CssRule::FontFace(FontFaceRule {
+                descriptors: self.parse_declarations(block),
+                span,
+            }),
+            (_, Some(block)) if is_keyframes(name) => match keyframes_name(prelude) {
+                Some(keyframes_name) => CssRule::Keyframes(KeyframesRule {
+                    name: keyframes_name,
+                    keyframes: self.parse_keyframes(block),
+                    span,
+                }),
+                None => return self.invalid_at_rule(name, span),
+            },
+            ("charset", None) => return None, //the encoding was already picked before the stylesheet was decoded
+            ("media", _) | ("supports", _) | ("import", _) | ("font-face", _) => return self.invalid_at_rule(name, span),
+            (_, _) if is_keyframes(name) => return self.invalid_at_rule(name, span),
+            _ => {
+                self.error(CssErrorKind::UnsupportedAtRule(name.to_string()), span);
+                return None;
+            }
+        };
+        Some(rule)
+    }
+
+    fn invalid_at_rule(&mut self, name: &str, span: Span) -> Option<CssRule> {
+        self.error(CssErrorKind::InvalidAtRule(name.to_string()), span);
+        None
+    }
 
-        while self.chars.peek().map_or(false, |c| *c != '{') { //iterate through our characters & make sure we don't run into our '{'
-            let selector = self.parse_selector();              //everything before '{' make it our selector
+    //The block of @keyframes, a list of "from, 50%, to { declarations }"
+    fn parse_keyframes(&mut self, values: &[ComponentValue]) -> Vec<Keyframe> {
+        let mut keyframes = Vec::new();
+        let mut i = skip_whitespace(values, 0);
 
-            if selector != selector::default() { //not default, it actually exist
-                selectors.push(selector);        //add it to our selectors
+        while i < values.len() {
+            let end = match (i..values.len()).find(|&j| curly_block(&values[j]).is_some()) {
+                Some(end) => end,
+                None => {
+                    let span = values[i].span().to(values[values.len() - 1].span());
+                    self.error(CssErrorKind::InvalidSelector(serialize(trim_whitespace(&values[i..]))), span);
+                    break;
+                }
+            };
+            let span = values[i].span().to(values[end].span());
+            match keyframe_offsets(&values[i..end]) {
+                Some(offsets) => {
+                    let declarations = self.parse_declarations(curly_block(&values[end]).unwrap());
+                    keyframes.push(Keyframe { offsets, declarations, span });
+                }
+                None => self.error(CssErrorKind::InvalidSelector(serialize(trim_whitespace(&values[i..end]))), span),
             }
+            i = skip_whitespace(values, end + 1);
+        }
+
+        keyframes
+    }
+
+    //Everything left in the stylesheet as component values
+    fn consume_rest(&mut self) -> Vec<ComponentValue> {
+        let mut values = Vec::new();
+        while *self.peek() != CssToken::Eof {
+            values.push(self.consume_component_value());
+        }
+        values
+    }
 
-            self.consume_white(char::is_whitespace);
-            if self.chars.peek().map_or(false, |c| *c == ',') { //If have ',' then keep iterating, and this while loop will loop again
-                self.chars.next();
+    fn consume_component_value(&mut self) -> ComponentValue {
+        let (token, span) = self.tokens[self.pos].clone();
+        match token {
+            CssToken::OpenCurly | CssToken::OpenSquare | CssToken::OpenParen => {
+                let (contents, span) = self.consume_simple_block();
+                ComponentValue::Block { open: token, contents, span }
+            }
+            CssToken::Function(name) => {
+                self.advance();
+                let (arguments, end) = self.consume_until(CssToken::CloseParen, span);
+                ComponentValue::Function { name, arguments, span: span.to(end) }
+            }
+            token => {
+                self.advance();
+                ComponentValue::Token(token, span)
             }
         }
+    }
 
-        self.chars.next();
-        selectors
+    //The block starting at the current '{', '[' or '(', gives back what's inside and the span of the whole block
+    fn consume_simple_block(&mut self) -> (Vec<ComponentValue>, Span) {
+        let start = self.span();
+        let close = match *self.peek() {
+            CssToken::OpenCurly => CssToken::CloseCurly,
+            CssToken::OpenSquare => CssToken::CloseSquare,
+            _ => CssToken::CloseParen,
+        };
+        self.advance();
+        let (contents, end) = self.consume_until(close, start);
+        (contents, start.to(end))
     }
 
-   
-    fn parse_selector(&mut self) -> Selector {
-        let mut sselector = SimpleSelector::default();  //create simple selector 
+    //Component values up to the closing token, which is consumed too, a stylesheet that ends first closes everything
+    //`start` is where the block or function began
+    fn consume_until(&mut self, close: CssToken, start: Span) -> (Vec<ComponentValue>, Span) {
+        let mut contents = Vec::new();
+        self.open_blocks += 1;
+        loop {
+            if *self.peek() == close {
+                let end = self.span();
+                self.advance();
+                self.open_blocks -= 1;
+                return (contents, end);
+            }
+            if *self.peek() == CssToken::Eof {
+                let end = self.span();
+                self.open_blocks -= 1;
+                //every block still open ends here, "a(((( {" is one error for the outermost one, not five
+                if self.open_blocks == 0 {
+                    self.error(CssErrorKind::UnexpectedEof, start.to(end));
+                }
+                return (contents, end);
+            }
+            contents.push(self.consume_component_value());
+        }
+    }
+
+    //ANCHOR Selectors
+    //Like a browser, one selector we don't understand makes the whole list invalid, Err has the part we couldn't read
+    fn parse_selectors(&self, values: &[ComponentValue]) -> Result<Vec<Selector>, String> {
+        let mut selectors = Vec::new(); //create empty selector
+        let mut i = 0;
+
+        loop {
+            let (selector, next) = self.parse_selector(values, i)?; //everything up to ',' is our selector
+            selectors.push(selector);    //add it to our selectors
+            i = skip_whitespace(values, next);
+
+            if i < values.len() && is_token(&values[i], &CssToken::Comma) { //If have ',' then keep going with the next one
+                i += 1;
+            } else {
+                return Ok(selectors);
+            }
+        }
+    }
+
+    //One selector starting at values[i], compound selectors joined by combinators ("ul.nav > li a"), gives back where it stopped
+    fn parse_selector(&self, values: &[ComponentValue], mut i: usize) -> Result<(Selector, usize), String> {
         let mut selector = Selector::default();         //create selector
+        i = skip_whitespace(values, i);                 //skip all white space
+        let start = i;
 
-        self.consume_while(char::is_whitespace);        //consume all white space
+        loop {
+            let (sselector, pseudo_element, next) = match self.parse_compound_selector(values, i) {
+                Ok(compound) => compound,
+                //nothing at all where a compound should be ("a >", "> a"), the whole selector is what's wrong
+                Err(ref text) if text.is_empty() => {
+                    let end = (start..values.len()).find(|&j| is_token(&values[j], &CssToken::Comma)).unwrap_or(values.len());
+                    return Err(serialize(trim_whitespace(&values[start..end])));
+                }
+                Err(text) => return Err(text),
+            };
+            selector.simple.push(sselector);
+            i = next;
 
-        sselector.tag_name = match self.chars.peek() {  //check if we have simple selector
-            Some(&c) if is_valid_start_ident(c) => Some(self.parse_identifier()),
-            _ => None,
-        };
+            //a pseudo-element can only be at the very end ("p::before span" isn't a thing)
+            let after = skip_whitespace(values, i);
+            if pseudo_element.is_some() {
+                if after < values.len() && !is_token(&values[after], &CssToken::Comma) {
+                    return Err(unreadable(values, after));
+                }
+                selector.pseudo_element = pseudo_element;
+                return Ok((selector, i));
+            }
+
+            //a combinator, or whitespace alone which is the descendant combinator
+            let combinator = match combinator_at(values, after) {
+                Some(c) => {
+                    i = skip_whitespace(values, after + 1);
+                    c
+                }
+                None if after > i && after < values.len() && !is_token(&values[after], &CssToken::Comma) => {
+                    i = after;
+                    ' '
+                }
+                None => return Ok((selector, i)),
+            };
+            selector.combinators.push(combinator);
+        }
+    }
+
+    //A tag name (or '*') followed by any number of #id, .class, [attribute] and :pseudo-class, up to whitespace, a combinator or ','
+    //It can end with a ::pseudo-element, which is given back on its own since it isn't part of what the element has to match
+    fn parse_compound_selector(&self, values: &[ComponentValue], mut i: usize) -> Result<(SimpleSelector, Option<PseudoElement>, usize), String> {
+        let mut sselector = SimpleSelector::default();  //create simple selector 
+        let mut universal = false;
+        let mut pseudo_element = None;
+
+        match values.get(i) {
+            Some(ComponentValue::Token(CssToken::Ident(ref name), _)) => { //check if we have a tag name
+                sselector.tag_name = Some(name.clone()); //as written, only HTML elements compare it without case
+                i += 1;
+            }
+            Some(ComponentValue::Token(CssToken::Delim('*'), _)) => { //'*' matches any tag, same as no tag name
+                universal = true;
+                i += 1;
+            }
+            _ => {}
+        }
 
         let mut multiple_ids = false;
-        while self.chars
-            .peek()
-            .map_or(false, |c| *c != ',' && *c != '{' && !(*c).is_whitespace())
-            //not getting ',' or '{' and its not a whitespace
-        {
-            match self.chars.peek() {
-                Some(&c) if c == '#' => { //if its id with number sign & selector name
-                    self.chars.next();
+        while i < values.len() && !is_token(&values[i], &CssToken::Whitespace) && !is_token(&values[i], &CssToken::Comma) && combinator_at(values, i).is_none() {
+            if pseudo_element.is_some() { //nothing can come after the pseudo-element
+                return Err(unreadable(values, i));
+            }
+            match values[i] {
+                ComponentValue::Token(CssToken::Hash { ref value, id: true }, _) => { //if its id with number sign & selector name
                     if sselector.id.is_some() || multiple_ids {
                         sselector.id = None;
                         multiple_ids = true;
-                        self.parse_id();
                     } else {
-                        sselector.id = self.parse_id();
+                        sselector.id = Some(value.clone());
                     }
+                    i += 1;
                 }
-                Some(&c) if c == '.' => {  //if its a class
-                    self.chars.next();
-                    let class_name = self.parse_identifier();
-
-                    if class_name != String::from("") {
-                        sselector.classes.push(class_name);
+                ComponentValue::Token(CssToken::Delim('.'), _) if next_ident(values, i).is_some() => { //if its a class
+                    sselector.classes.push(next_ident(values, i).unwrap().to_string());
+                    i += 2;
+                }
+                ComponentValue::Block { open: CssToken::OpenSquare, ref contents, .. } if parse_attribute_selector(contents).is_some() => {
+                    sselector.attributes.push(parse_attribute_selector(contents).unwrap());
+                    i += 1;
+                }
+                ComponentValue::Token(CssToken::Colon, _) if values.get(i + 1).is_some_and(|value| is_token(value, &CssToken::Colon)) => {
+                    match next_ident(values, i + 1).and_then(|name| parse_pseudo_element(name, false)) {
+                        Some(pseudo) => {
+                            pseudo_element = Some(pseudo);
+                            i += 3;
+                        }
+                        None => return Err(unreadable(values, i)),
                     }
                 }
-                _ => {  //anything else ignore it
-                    self.consume_while(|c| c != ',' && c != '{');
+                //the pseudo-elements from CSS 2 still work with one ':'
+                ComponentValue::Token(CssToken::Colon, _) if next_ident(values, i).and_then(|name| parse_pseudo_element(name, true)).is_some() => {
+                    pseudo_element = next_ident(values, i).and_then(|name| parse_pseudo_element(name, true));
+                    i += 2;
                 }
+                ComponentValue::Token(CssToken::Colon, _) => match values.get(i + 1).and_then(|value| self.parse_pseudo_class(value)) {
+                    Some(pseudo_class) => {
+                        sselector.pseudo_classes.push(pseudo_class);
+                        i += 2;
+                    }
+                    None => return Err(unreadable(values, i)),
+                },
+                _ => return Err(unreadable(values, i)), //anything else, the part up to the next selector is what we couldn't read
             }
         }
 
-        //make sure our simpleselector isn't default
-        if sselector != SimpleSelector::default() {
-            selector.simple.push(sselector);
+        //make sure our simpleselector isn't default, an empty selector (like the first one in ", p") is invalid
+        if sselector == SimpleSelector::default() && !universal && pseudo_element.is_none() {
+            return Err(String::new());
         }
+        Ok((sselector, pseudo_element, i))
+    }
 
-        selector
+    //What comes after ':', a name or a function with its arguments, None for one we don't know
+    fn parse_pseudo_class(&self, value: &ComponentValue) -> Option<PseudoClass> {
+        let pseudo_class = match *value {
+            ComponentValue::Token(CssToken::Ident(ref name), _) => match name.to_ascii_lowercase().as_str() {
+                "root" => PseudoClass::Root,
+                "empty" => PseudoClass::Empty,
+                "first-child" => PseudoClass::FirstChild,
+                "last-child" => PseudoClass::LastChild,
+                "only-child" => PseudoClass::OnlyChild,
+                "first-of-type" => PseudoClass::FirstOfType,
+                "last-of-type" => PseudoClass::LastOfType,
+                "only-of-type" => PseudoClass::OnlyOfType,
+                "hover" => PseudoClass::Hover,
+                "focus" => PseudoClass::Focus,
+                "active" => PseudoClass::Active,
+                "checked" => PseudoClass::Checked,
+                "disabled" => PseudoClass::Disabled,
+                "enabled" => PseudoClass::Enabled,
+                _ => return None,
+            },
+            ComponentValue::Function { ref name, ref arguments, .. } => match name.to_ascii_lowercase().as_str() {
+                "nth-child" | "nth-last-child" => {
+                    //an+b, then optionally "of" and a selector list
+                    let of = arguments.iter().position(|a| match *a {
+                        ComponentValue::Token(CssToken::Ident(ref word), _) => word.eq_ignore_ascii_case("of"),
+                        _ => false,
+                    });
+                    let nth = parse_nth(&arguments[..of.unwrap_or(arguments.len())])?;
+                    let list = match of {
+                        Some(of) => self.parse_selectors(&arguments[of + 1..]).ok()?,
+                        None => Vec::new(),
+                    };
+                    if name.eq_ignore_ascii_case("nth-child") {
+                        PseudoClass::NthChild(nth, list)
+                    } else {
+                        PseudoClass::NthLastChild(nth, list)
+                    }
+                }
+                "nth-of-type" => PseudoClass::NthOfType(parse_nth(arguments)?),
+                "nth-last-of-type" => PseudoClass::NthLastOfType(parse_nth(arguments)?),
+                "not" => PseudoClass::Not(self.parse_selectors(arguments).ok()?),
+                "is" => PseudoClass::Is(self.parse_forgiving_selectors(arguments)),
+                "where" => PseudoClass::Where(self.parse_forgiving_selectors(arguments)),
+                "has" => PseudoClass::Has(self.parse_relative_selectors(arguments)?),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(pseudo_class)
     }
 
-    //When Deadling with normal identifier
-    fn parse_identifier(&mut self) -> String {
-        let mut ident = String::new();
+    //The list of :is() and :where(), a selector we can't read is just left out instead of breaking the whole list
+    fn parse_forgiving_selectors(&self, values: &[ComponentValue]) -> Vec<Selector> {
+        let mut selectors = Vec::new();
+        for part in split_commas(values) {
+            if let Ok(mut list) = self.parse_selectors(part) {
+                selectors.append(&mut list);
+            }
+        }
+        selectors
+    }
 
-        match self.chars.peek() {   //match through our itertaion through our characters
-            Some(&c) => if is_valid_start_ident(c) {    //if our start is valid
-                ident.push_str(&self.consume_while(is_valid_ident)) //if its take it, consume it, push it to our ident string
-            },
-            None => {}
+    //The list of :has(), each one can start with a combinator ("> img, + p")
+    fn parse_relative_selectors(&self, values: &[ComponentValue]) -> Option<Vec<RelativeSelector>> {
+        let mut selectors = Vec::new();
+        for part in split_commas(values) {
+            let (combinator, rest) = match combinator_at(part, 0) {
+                Some(c) => (c, &part[1..]),
+                None => (' ', part),
+            };
+            let (selector, _) = self.parse_selector(rest, 0).ok()?;
+            selectors.push(RelativeSelector { combinator, selector });
+        }
+        Some(selectors)
+    }
+
+    //ANCHOR Declarations
+    //"Consume a list of declarations" over the contents of a rule's {} block
+    fn parse_declarations(&mut self, values: &[ComponentValue]) -> Vec<Declaration> {
+        let mut declarations = Vec::<Declaration>::new();   //create vector of Declaration
+        let mut i = 0;
+
+        while i < values.len() {
+            match values[i] {
+                ComponentValue::Token(CssToken::Whitespace, _) | ComponentValue::Token(CssToken::Semicolon, _) => i += 1,
+                ComponentValue::Token(CssToken::AtKeyword(ref name), start) => {
+                    //an at-rule inside a rule, skipped up to its ';' or through its block
+                    let mut end = start;
+                    while i < values.len() && !is_token(&values[i], &CssToken::Semicolon) {
+                        end = values[i].span();
+                        i += 1;
+                        if let Some(ComponentValue::Block { open: CssToken::OpenCurly, .. }) = values.get(i - 1) {
+                            break;
+                        }
+                    }
+                    self.error(CssErrorKind::UnsupportedAtRule(name.clone()), start.to(end));
+                }
+                _ => {
+                    //everything up to the next ';' is one declaration, a ';' inside a block or function doesn't count
+                    let end = (i..values.len()).find(|&j| is_token(&values[j], &CssToken::Semicolon)).unwrap_or(values.len());
+                    if let Some(declaration) = self.parse_declaration(&values[i..end]) {
+                        declarations.push(declaration);
+                    }
+                    i = end;
+                }
+            }
         }
 
-        ident.to_lowercase()    //convert it to lowercase and return it
+        declarations
     }
 
-    fn parse_id(&mut self) -> Option<String> {
-        match &self.parse_identifier()[..] { //match on our slice identifier and the string we get return from it
-            "" => None, //check if its empty string
-            s @ _ => Some(s.to_string()),   //otherwise wrap our identifier into an option
+    //"Consume a declaration": a property name, ':' and the value, with !important taken off the end
+    fn parse_declaration(&mut self, values: &[ComponentValue]) -> Option<Declaration> {
+        let values = trim_whitespace(values);
+        let span = values[0].span().to(values[values.len() - 1].span());
+        let property = match values[0] {
+            //custom properties (--name) keep their case, everything else doesn't care
+            ComponentValue::Token(CssToken::Ident(ref name), _) if name.starts_with("--") => name.clone(),
+            ComponentValue::Token(CssToken::Ident(ref name), _) => name.to_ascii_lowercase(),
+            _ => {
+                self.error(CssErrorKind::InvalidDeclaration(serialize(values)), span);
+                return None;
+            }
+        };
+
+        let colon = skip_whitespace(values, 1);
+        if colon >= values.len() || !is_token(&values[colon], &CssToken::Colon) {
+            self.error(CssErrorKind::InvalidDeclaration(serialize(values)), span);
+            return None;
         }
+        let mut value = trim_whitespace(&values[colon + 1..]);
+
+        //"! important" is fine too, whitespace can go between the two
+        let mut important = false;
+        if let [ref rest @ .., ComponentValue::Token(CssToken::Ident(ref name), _)] = *value {
+            if let [ref before @ .., ComponentValue::Token(CssToken::Delim('!'), _)] = *trim_whitespace(rest) {
+                if name.eq_ignore_ascii_case("important") {
+                    important = true;
+                    value = trim_whitespace(before);
+                }
+            }
+        }
+        //a broken string or url can't be part of a valid value
+        let broken = value.iter().any(|v| is_token(v, &CssToken::BadString) || is_token(v, &CssToken::BadUrl));
+        if value.is_empty() || broken {
+            self.error(CssErrorKind::InvalidDeclaration(serialize(values)), span);
+            return None;
+        }
+
+        let mut declaration = Declaration::new(property.clone(), translate_value(&property, value)); //create new declaration and put our property and our value
+        declaration.important = important;
+        declaration.span = span;
+        Some(declaration)
     }
+}
 
+impl ComponentValue {
+    pub(crate) fn span(&self) -> Span {
+        match *self {
+            ComponentValue::Token(_, span) | ComponentValue::Block { span, .. } | ComponentValue::Function { span, .. } => span,
+        }
+    }
+}
 
-    fn parse_declarations(&mut self) -> Vec<Declaration> {
-        let mut declarations = Vec::<Declaration>::new();   //create vector of Declaration
+//NOTE: Helper Functions
+fn is_token(value: &ComponentValue, token: &CssToken) -> bool {
+    match *value {
+        ComponentValue::Token(ref t, _) => t == token,
+        _ => false,
+    }
+}
 
-        while self.chars.peek().map_or(false, |c| *c != '}') {  //go through our characters iterator make sure we r not hitting closing brace '}'
-            self.consume_while(char::is_whitespace);            //consume all the whitespace
+fn skip_whitespace(values: &[ComponentValue], mut i: usize) -> usize {
+    while i < values.len() && is_token(&values[i], &CssToken::Whitespace) {
+        i += 1;
+    }
+    i
+}
 
-            let property = self.consume_while(|x| x != ':').to_lowercase(); //find all the properties, consume everything before the colon, convert it to lowercase
+fn trim_whitespace(mut values: &[ComponentValue]) -> &[ComponentValue] {
+    while let [ComponentValue::Token(CssToken::Whitespace, _), ref rest @ ..] = *values {
+        values = rest;
+    }
+    while let [ref rest @ .., ComponentValue::Token(CssToken::Whitespace, _)] = *values {
+        values = rest;
+    }
+    values
+}
 
-            self.chars.next();
-            self.consume_while(char::is_whitespace);            //consume all the whitespace
+//What's inside [...]: a name, then optionally an operator, an ident or string value and an i or s flag
+fn parse_attribute_selector(values: &[ComponentValue]) -> Option<AttributeSelector> {
+    let values = trim_whitespace(values);
+    let name = match values.first()? {
+        ComponentValue::Token(CssToken::Ident(ref name), _) => name.clone(),
+        _ => return None,
+    };
+    let mut i = skip_whitespace(values, 1);
+    if i >= values.len() {
+        return Some(AttributeSelector { name, operator: None, value: String::new(), case: AttrCase::Default });
+    }
 
-            let value = self.consume_while(|x| x != ';' && x != '\n' && x != '}')
-                .to_lowercase();    //Everything after the colon, and before ';' and 'carriage return \n' and '}'
+    //'=' alone or one of ~ | ^ $ * right before it
+    let operator = match (values.get(i), values.get(i + 1)) {
+        (Some(ComponentValue::Token(CssToken::Delim('='), _)), _) => AttrOperator::Equals,
+        (Some(ComponentValue::Token(CssToken::Delim(c), _)), Some(ComponentValue::Token(CssToken::Delim('='), _))) => {
+            i += 1;
+            match *c {
+                '~' => AttrOperator::Includes,
+                '|' => AttrOperator::DashMatch,
+                '^' => AttrOperator::Prefix,
+                '$' => AttrOperator::Suffix,
+                '*' => AttrOperator::Substring,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    i = skip_whitespace(values, i + 1);
+    let value = match values.get(i) {
+        Some(ComponentValue::Token(CssToken::Ident(ref value), _)) | Some(ComponentValue::Token(CssToken::String(ref value), _)) => value.clone(),
+        _ => return None,
+    };
+    i = skip_whitespace(values, i + 1);
+    let case = match ident_at(values, i).as_deref() {
+        None if i >= values.len() => AttrCase::Default,
+        Some("i") => AttrCase::Insensitive,
+        Some("s") => AttrCase::Sensitive,
+        _ => return None,
+    };
+    if case != AttrCase::Default && skip_whitespace(values, i + 1) < values.len() {
+        return None; //something after the flag
+    }
+    Some(AttributeSelector { name, operator: Some(operator), value, case })
+}
 
-            //These are different properties that we can deal with inside of our CSS
-            let value_enum = match property.as_ref() {              //match each of our properties
-                "background-color" | "border-color" | "color" => {  //Deal with Colors
-                    Value::Color(translate_color(&value))           //allows us to translate the color
-                }
-                "margin-right" |                                    //Stuff that deal with size, anything else we'll go into our other value
-                "margin-bottom" |                                   //Most of our CSS will be moving things around and chaning colors atleast
-                "margin-left" |
-                "margin-top" |
-                "padding-right" |
-                "padding-bottom" |
-                "padding-left" |
-                "padding-top" |
-                "border-right-width" |
-                "border-bottom-width" |
-                "border-left-width" |
-                "border-top-width" |
-                "height" |
-                "width" => translate_length(&value),
-                _ => Value::Other(value),
-            };
+//The part of a selector from values[i] up to the next ',', for the error when it can't be read
+fn unreadable(values: &[ComponentValue], i: usize) -> String {
+    let end = (i..values.len()).find(|&j| is_token(&values[j], &CssToken::Comma)).unwrap_or(values.len());
+    serialize(trim_whitespace(&values[i..end]))
+}
 
-            let declaration = Declaration::new(property, value_enum);   //create new declaration and put our property and our enum 
+//The an+b of :nth-child() and friends, "odd", "even", "3", "2n+1", "-n + 3" ...
+//https://www.w3.org/TR/css-syntax-3/#anb-microsyntax, the tokenizer already cut "2n-1" into a number and "n-1" so
+//the 'n' and whatever got stuck to it after the number have to be pulled apart here
+//Numbers too big for an i32 are clamped to one, like browsers do
+fn parse_nth(values: &[ComponentValue]) -> Option<Nth> {
+    let values = trim_whitespace(values);
+    //the number in front of the 'n', what was left in the same token after it and where the rest starts
+    let (a, rest, mut i) = match *values.first()? {
+        ComponentValue::Token(CssToken::Ident(ref word), _) => {
+            let word = word.to_ascii_lowercase();
+            match word.as_str() {
+                "odd" if values.len() == 1 => return Some(Nth { a: 2, b: 1 }),
+                "even" if values.len() == 1 => return Some(Nth { a: 2, b: 0 }),
+                _ => {}
+            }
+            match (word.strip_prefix("-n"), word.strip_prefix('n')) {
+                (Some(rest), _) => (-1, rest.to_string(), 1),
+                (None, Some(rest)) => (1, rest.to_string(), 1),
+                _ => return None,
+            }
+        }
+        //+n, the tokenizer keeps the '+' apart from the ident
+        ComponentValue::Token(CssToken::Delim('+'), _) => match values.get(1) {
+            Some(ComponentValue::Token(CssToken::Ident(ref word), _)) if word.to_ascii_lowercase().starts_with('n') => {
+                (1, word.to_ascii_lowercase()[1..].to_string(), 2)
+            }
+            _ => return None,
+        },
+        ComponentValue::Token(CssToken::Number(n), _) if n.integer && values.len() == 1 => return Some(Nth { a: 0, b: n.value as i32 }),
+        ComponentValue::Token(CssToken::Dimension(n, ref unit), _) if n.integer => match unit.to_ascii_lowercase().strip_prefix('n') {
+            Some(rest) => (n.value as i32, rest.to_string(), 1),
+            None => return None,
+        },
+        _ => return None,
+    };
 
-            if self.chars.peek().map_or(false, |c| *c == ';') { //put all these declaration inside declarations as long as we r before ';'
-                declarations.push(declaration);
-                self.chars.next();
-            } else {                                            //else if we dont have ';' we just wanna w8 until we hit '}' 
-                self.consume_while(char::is_whitespace);        //consume all whitespace
-                if self.chars.peek().map_or(false, |c| *c == '}') { //consume everything before '}'
-                    declarations.push(declaration);
+    let b = match rest.as_str() {
+        //2n, 2n+1, 2n + 1, 2n - 1
+        "" => {
+            i = skip_whitespace(values, i);
+            match values.get(i) {
+                None => 0,
+                //"2n +1" or "2n -1", b needs its sign ("2n 1" isn't an+b)
+                Some(ComponentValue::Token(CssToken::Number(n), _)) if n.integer && n.signed => {
+                    i += 1;
+                    n.value as i32
                 }
+                Some(ComponentValue::Token(CssToken::Delim(sign @ ('+' | '-')), _)) => {
+                    i = skip_whitespace(values, i + 1);
+                    let b = unsigned_integer(values.get(i))?;
+                    i += 1;
+                    (if *sign == '-' { -b } else { b }) as i32
+                }
+                _ => return None,
             }
-            self.consume_while(char::is_whitespace); //consume all rest of whitespace
         }
+        //2n- 1
+        "-" => {
+            i = skip_whitespace(values, i);
+            let b = unsigned_integer(values.get(i))?;
+            i += 1;
+            -b as i32
+        }
+        //2n-1
+        rest => match rest.strip_prefix('-') {
+            Some(digits) if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => -digits.parse::<f64>().ok()? as i32,
+            _ => return None,
+        },
+    };
 
-        self.chars.next();
-        declarations
+    if skip_whitespace(values, i) < values.len() {
+        return None;
+    }
+    Some(Nth { a, b })
+}
+
+//The b after a '+' or '-' that stands apart, "2n + 1", it can't have a sign of its own
+//Still a float, the sign has to go on before it's clamped to an i32
+fn unsigned_integer(value: Option<&ComponentValue>) -> Option<f32> {
+    match value {
+        Some(ComponentValue::Token(CssToken::Number(n), _)) if n.integer && !n.signed => Some(n.value),
+        _ => None,
+    }
+}
+
+//'>', '+' or '~' at values[i], the descendant combinator is just whitespace so it's not here
+fn combinator_at(values: &[ComponentValue], i: usize) -> Option<char> {
+    match values.get(i) {
+        Some(ComponentValue::Token(CssToken::Delim(c @ ('>' | '+' | '~')), _)) => Some(*c),
+        _ => None,
+    }
+}
+
+//The name after "::", legacy is for the ones CSS 2 wrote with a single ':' (":before")
+fn parse_pseudo_element(name: &str, legacy: bool) -> Option<PseudoElement> {
+    match name.to_ascii_lowercase().as_str() {
+        "before" => Some(PseudoElement::Before),
+        "after" => Some(PseudoElement::After),
+        "first-line" => Some(PseudoElement::FirstLine),
+        "first-letter" => Some(PseudoElement::FirstLetter),
+        "marker" if !legacy => Some(PseudoElement::Marker),
+        _ => None,
     }
+}
 
-    //like we had in HTML Parser - Takes condition which is a function 
-    fn consume_while<F>(&mut self, condition: F) -> String 
-    where
-        F: Fn(char) -> bool,    //Must take character and output bool
-    {
-        let mut result = String::new();
+//The ident right after values[i], for ".name"
+fn next_ident(values: &[ComponentValue], i: usize) -> Option<&str> {
+    match values.get(i + 1) {
+        Some(ComponentValue::Token(CssToken::Ident(ref name), _)) => Some(name),
+        _ => None,
+    }
+}
 
-        //Loop through characters & 'map or', we want to check if this condition was false
-        //& if its not then return default values, in this case its white-space character
-        while self.chars.peek().map_or(false, |c| condition(*c)) {
-            //Take result & push it on to our characters. then we go to next character and unwrap it
-            result.push(self.chars.next().unwrap());
+//Component values written back out as CSS the way the author wrote them, any whitespace is one space
+//Font families, custom properties and @keyframes names care about case, so whatever matches keywords lowercases them itself
+fn serialize(values: &[ComponentValue]) -> String {
+    let mut result = String::new();
+    for value in values {
+        match *value {
+            ComponentValue::Token(ref token, _) => result.push_str(&token.to_string()),
+            ComponentValue::Block { ref open, ref contents, .. } => {
+                let close = match *open {
+                    CssToken::OpenCurly => '}',
+                    CssToken::OpenSquare => ']',
+                    _ => ')',
+                };
+                result.push_str(&format!("{}{}{}", open, serialize(contents), close));
+            }
+            ComponentValue::Function { ref name, ref arguments, .. } => {
+                result.push_str(&format!("{}({})", name, serialize(arguments)));
+            }
         }
+    }
+    result
+}
 
-        result
+//The contents of a {} block, None for anything else
+fn curly_block(value: &ComponentValue) -> Option<&[ComponentValue]> {
+    match *value {
+        ComponentValue::Block { open: CssToken::OpenCurly, ref contents, .. } => Some(contents),
+        _ => None,
     }
 }
 
-//NOTE: Helper Functions
+fn ident_at(values: &[ComponentValue], i: usize) -> Option<String> {
+    match values.get(i) {
+        Some(ComponentValue::Token(CssToken::Ident(ref name), _)) => Some(name.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+//Parts of a list separated by top-level commas, each one without the whitespace around it
+fn split_commas(values: &[ComponentValue]) -> Vec<&[ComponentValue]> {
+    values.split(|v| is_token(v, &CssToken::Comma)).map(trim_whitespace).collect()
+}
+
+//NOTE: At-rule Preludes
+//"screen, print and (min-width: 600px)", a query we can't read becomes "not all" but the others still count
+fn parse_media_list(values: &[ComponentValue]) -> Vec<MediaQuery> {
+    if values.is_empty() {
+        return Vec::new();
+    }
+    split_commas(values).into_iter().map(|query| parse_media_query(query).unwrap_or_else(MediaQuery::never)).collect()
+}
+
+//[not | only] type [and (feature)]*, or just (feature) [and (feature)]*
+fn parse_media_query(values: &[ComponentValue]) -> Option<MediaQuery> {
+    let mut query = MediaQuery { negated: false, media_type: None, features: Vec::new() };
+    let mut i = 0;
+
+    match ident_at(values, i).as_deref() {
+        Some("not") => {
+            query.negated = true;
+            i = skip_whitespace(values, i + 1);
+        }
+        Some("only") => i = skip_whitespace(values, i + 1), //only there to hide the query from very old browsers
+        _ => {}
+    }
+    let mut need_and = false;
+    if let Some(media_type) = ident_at(values, i) {
+        if media_type == "and" || media_type == "or" || media_type == "not" || media_type == "only" {
+            return None;
+        }
+        query.media_type = Some(media_type);
+        i += 1;
+        need_and = true;
+    }
+
+    loop {
+        i = skip_whitespace(values, i);
+        if i >= values.len() {
+            break;
+        }
+        if need_and {
+            if ident_at(values, i).as_deref() != Some("and") {
+                return None;
+            }
+            i = skip_whitespace(values, i + 1);
+        }
+        match values.get(i) {
+            Some(ComponentValue::Block { open: CssToken::OpenParen, ref contents, .. }) => query.features.push(parse_media_feature(contents)?),
+            _ => return None,
+        }
+        i += 1;
+        need_and = true;
+    }
+
+    if query.media_type.is_none() && query.features.is_empty() {
+        return None;
+    }
+    Some(query)
+}
+
+//"min-width: 600px" or "color", range syntax like "width >= 600px" isn't supported
+fn parse_media_feature(values: &[ComponentValue]) -> Option<MediaFeature> {
+    let values = trim_whitespace(values);
+    let name = ident_at(values, 0)?;
+    let colon = skip_whitespace(values, 1);
+    if colon >= values.len() {
+        return Some(MediaFeature { name, value: None });
+    }
+    if !is_token(&values[colon], &CssToken::Colon) {
+        return None;
+    }
+    let value = match *trim_whitespace(&values[colon + 1..]) {
+        [ComponentValue::Token(CssToken::Dimension(n, ref unit), _)] => Value::Length(n.value, translate_unit(&unit.to_ascii_lowercase())),
+        [ComponentValue::Token(CssToken::Number(n), _)] => Value::Length(n.value, Unit::Px),
+        [ComponentValue::Token(CssToken::Ident(ref keyword), _)] => Value::Other(keyword.to_ascii_lowercase()),
+        [] => return None,
+        ref value => Value::Other(serialize(value)),
+    };
+    Some(MediaFeature { name, value: Some(value) })
+}
+
+//not (..), (..) and (..) and ..., (..) or (..) or ..., and/or can't be mixed without parentheses
+//Anything we can't read is Unknown, which is false, so the rules behind it don't apply
+fn parse_supports_condition(values: &[ComponentValue]) -> SupportsCondition {
+    let values = trim_whitespace(values);
+    let unknown = || SupportsCondition::Unknown(serialize(values));
+
+    if ident_at(values, 0).as_deref() == Some("not") {
+        let rest = trim_whitespace(&values[1..]);
+        return match *rest {
+            [ref condition] => SupportsCondition::Not(Box::new(parse_supports_in_parens(condition))),
+            _ => unknown(),
+        };
+    }
+
+    let mut conditions = Vec::new();
+    let mut joiner: Option<String> = None;
+    let mut i = 0;
+    loop {
+        match values.get(i) {
+            Some(value) => conditions.push(parse_supports_in_parens(value)),
+            None => return unknown(),
+        }
+        i = skip_whitespace(values, i + 1);
+        if i >= values.len() {
+            break;
+        }
+        match ident_at(values, i) {
+            Some(ref word) if (word == "and" || word == "or") && joiner.as_ref().is_none_or(|j| j == word) => joiner = Some(word.clone()),
+            _ => return unknown(),
+        }
+        i = skip_whitespace(values, i + 1);
+    }
+
+    match joiner.as_deref() {
+        None => conditions.pop().unwrap_or_else(unknown),
+        Some("and") => SupportsCondition::And(conditions),
+        _ => SupportsCondition::Or(conditions),
+    }
+}
+
+//(property: value) or a whole condition in parentheses
+fn parse_supports_in_parens(value: &ComponentValue) -> SupportsCondition {
+    if let ComponentValue::Block { open: CssToken::OpenParen, ref contents, .. } = *value {
+        let contents = trim_whitespace(contents);
+        let colon = skip_whitespace(contents, 1);
+        if let Some(property) = ident_at(contents, 0) {
+            if colon < contents.len() && is_token(&contents[colon], &CssToken::Colon) {
+                return SupportsCondition::Declaration(property, serialize(trim_whitespace(&contents[colon + 1..])));
+            }
+        }
+        return parse_supports_condition(contents);
+    }
+    SupportsCondition::Unknown(serialize(std::slice::from_ref(value)))
+}
+
+//"url.css" or url(url.css) and then the media list, None without a url
+fn parse_import(values: &[ComponentValue]) -> Option<(String, Vec<MediaQuery>)> {
+    let url = match *values.first()? {
+        ComponentValue::Token(CssToken::String(ref url), _) | ComponentValue::Token(CssToken::Url(ref url), _) => url.clone(),
+        ComponentValue::Function { ref name, ref arguments, .. } if name.eq_ignore_ascii_case("url") => match *trim_whitespace(arguments) {
+            [ComponentValue::Token(CssToken::String(ref url), _)] => url.clone(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((url, parse_media_list(trim_whitespace(&values[1..]))))
+}
+
+fn is_keyframes(name: &str) -> bool {
+    name == "keyframes" || name == "-webkit-keyframes" || name == "-moz-keyframes"
+}
+
+//An ident or a string, "none" can't be the name since animation-name: none means no animation
+fn keyframes_name(values: &[ComponentValue]) -> Option<String> {
+    match *values {
+        [ComponentValue::Token(CssToken::Ident(ref name), _)] if !name.eq_ignore_ascii_case("none") => Some(name.clone()),
+        [ComponentValue::Token(CssToken::String(ref name), _)] => Some(name.clone()),
+        _ => None,
+    }
+}
+
+//"from, 50%, to" as percentages, None if any of them isn't one
+fn keyframe_offsets(values: &[ComponentValue]) -> Option<Vec<f32>> {
+    let mut offsets = Vec::new();
+    for offset in split_commas(trim_whitespace(values)) {
+        match *offset {
+            [ComponentValue::Token(CssToken::Percentage(n), _)] if n.value >= 0.0 && n.value <= 100.0 => offsets.push(n.value),
+            [ComponentValue::Token(CssToken::Ident(ref word), _)] if word.eq_ignore_ascii_case("from") => offsets.push(0.0),
+            [ComponentValue::Token(CssToken::Ident(ref word), _)] if word.eq_ignore_ascii_case("to") => offsets.push(100.0),
+            _ => return None,
+        }
+    }
+    Some(offsets)
+}
+
+//These are different properties that we can deal with inside of our CSS
+fn translate_value(property: &str, value: &[ComponentValue]) -> Value {
+    let text = serialize(value);
+    match property {                                            //match each of our properties
+        "background-color" | "border-color" | "color" => {     //Deal with Colors
+            Value::Color(translate_color(&text.to_ascii_lowercase())) //allows us to translate the color, names and hex digits don't care about case
+        }
+        "margin-right" |                                        //Stuff that deal with size, anything else we'll go into our other value
+        "margin-bottom" |                                       //Most of our CSS will be moving things around and chaning colors atleast
+        "margin-left" |
+        "margin-top" |
+        "padding-right" |
+        "padding-bottom" |
+        "padding-left" |
+        "padding-top" |
+        "border-right-width" |
+        "border-bottom-width" |
+        "border-left-width" |
+        "border-top-width" |
+        "height" |
+        "width" => match *value {
+            //a single number the tokenizer already read, otherwise fall back to reading the text
+            [ComponentValue::Token(CssToken::Dimension(n, ref unit), _)] => Value::Length(n.value, translate_unit(&unit.to_ascii_lowercase())),
+            [ComponentValue::Token(CssToken::Percentage(n), _)] => Value::Length(n.value, Unit::Pct),
+            [ComponentValue::Token(CssToken::Number(n), _)] => Value::Length(n.value, Unit::Px),
+            _ => translate_length(&text.to_ascii_lowercase()),
+        },
+        "content" => translate_content(value).unwrap_or(Value::Other(text)),
+        _ => Value::Other(text),
+    }
+}
+
+//"content: open-quote attr(title) ': '", None for none, normal or anything we can't generate (counters, images)
+fn translate_content(value: &[ComponentValue]) -> Option<Value> {
+    let mut items = Vec::new();
+    for value in value.iter().filter(|value| !is_token(value, &CssToken::Whitespace)) {
+        let item = match *value {
+            ComponentValue::Token(CssToken::String(ref s), _) => ContentItem::String(s.clone()),
+            ComponentValue::Token(CssToken::Ident(ref name), _) => match name.to_ascii_lowercase().as_str() {
+                "open-quote" => ContentItem::OpenQuote,
+                "close-quote" => ContentItem::CloseQuote,
+                "no-open-quote" | "no-close-quote" => continue,
+                _ => return None,
+            },
+            ComponentValue::Function { ref name, ref arguments, .. } if name.eq_ignore_ascii_case("attr") => match *trim_whitespace(arguments) {
+                [ComponentValue::Token(CssToken::Ident(ref attribute), _)] => ContentItem::Attr(attribute.to_ascii_lowercase()),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        items.push(item);
+    }
+    Some(Value::Content(items))
+}
+
 //takes slice of string & output new value
 fn translate_length(value: &str) -> Value {
     let mut num_str = String::new();
@@ -218,51 +1121,51 @@ fn translate_length(value: &str) -> Value {
     }
 
     let number = num_str.parse().unwrap_or(0.0);    //parse our number string, unwrap it and turn it into a float
-
-    match unit.as_ref() {   //match our unit, take these from a string and convert them into our enum type 
-        "em" => Value::Length(number, Unit::Em),
-        "ex" => Value::Length(number, Unit::Ex),
-        "ch" => Value::Length(number, Unit::Ch),
-        "rem" => Value::Length(number, Unit::Rem),
-        "vh" => Value::Length(number, Unit::Vh),
-        "vw" => Value::Length(number, Unit::Vw),
-        "vmin" => Value::Length(number, Unit::Vmin),
-        "vmax" => Value::Length(number, Unit::Vmax),
-        "px" | "" => Value::Length(number, Unit::Px),
-        "mm" => Value::Length(number, Unit::Mm),
-        "q" => Value::Length(number, Unit::Q),
-        "cm" => Value::Length(number, Unit::Cm),
-        "in" => Value::Length(number, Unit::In),
-        "pt" => Value::Length(number, Unit::Pt),
-        "pc" => Value::Length(number, Unit::Pc),
-        "%" => Value::Length(number, Unit::Pct),
-        _ => Value::Length(number, Unit::Px),
-    }
-}
-
-//NOTE: Booleans to check to see if we were parsing properly
-fn is_valid_ident(c: char) -> bool {
-    is_valid_start_ident(c) || c.is_digit(10) || c == '-'
+    Value::Length(number, translate_unit(&unit))
 }
 
-fn is_valid_start_ident(c: char) -> bool {
-    is_letter(c) || is_non_ascii(c) || c == '_'
+//match our unit, take these from a string and convert them into our enum type 
+fn translate_unit(unit: &str) -> Unit {
+    known_unit(unit).unwrap_or(Unit::Px) //units we don't know are read as px
 }
 
-fn is_letter(c: char) -> bool {
-    is_upper_letter(c) || is_lower_letter(c)
+fn known_unit(unit: &str) -> Option<Unit> {
+    Some(match unit {
+        "em" => Unit::Em,
+        "ex" => Unit::Ex,
+        "ch" => Unit::Ch,
+        "rem" => Unit::Rem,
+        "vh" => Unit::Vh,
+        "vw" => Unit::Vw,
+        "vmin" => Unit::Vmin,
+        "vmax" => Unit::Vmax,
+        "px" | "" => Unit::Px,
+        "mm" => Unit::Mm,
+        "q" => Unit::Q,
+        "cm" => Unit::Cm,
+        "in" => Unit::In,
+        "pt" => Unit::Pt,
+        "pc" => Unit::Pc,
+        "%" => Unit::Pct,
+        _ => return None,
+    })
 }
 
-fn is_upper_letter(c: char) -> bool {
-    c >= 'A' && c <= 'Z'
+//"10px", "1.5em", "50%" or a bare number, what @supports accepts for the sizes translate_value reads
+pub(crate) fn is_length(value: &str) -> bool {
+    let value = value.to_ascii_lowercase();
+    let unit = value.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.' || c == '-' || c == '+');
+    let number = &value[..value.len() - unit.len()];
+    number.parse::<f32>().is_ok() && known_unit(unit).is_some()
 }
 
-fn is_lower_letter(c: char) -> bool {
-    c >= 'a' && c <= 'z'
-}
-
-fn is_non_ascii(c: char) -> bool {
-    c >= '\u{0080}'
+//"#abc", "#aabbcc" or one of the names translate_color knows, anything else it would have to guess
+pub(crate) fn is_color(color: &str) -> bool {
+    let color = color.to_ascii_lowercase();
+    match color.strip_prefix('#') {
+        Some(hex) => (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => named_color(&color).is_some(),
+    }
 }
 
 //NOTE: Translate The Colors
@@ -307,499 +1210,504 @@ fn translate_color(color: &str) -> Color {
     } else if color.starts_with("hsl") {    //Color that starts with hsl, we can't parse it return color default (WHITE)
         return Color::default();
     } else {                                //ELSE MATCH ON COLOR
-        return match color {
-            "black" => Color::new(0.0, 0.0, 0.0, 1.0),
-            "silver" => Color::new(
-                0.7529411764705882,
-                0.7529411764705882,
-                0.7529411764705882,
-                1.0,
-            ),
-            "gray" | "grey" => Color::new(
-                0.5019607843137255,
-                0.5019607843137255,
-                0.5019607843137255,
-                1.0,
-            ),
-            "white" => Color::new(1.0, 1.0, 1.0, 1.0),
-            "maroon" => Color::new(0.5019607843137255, 0.0, 0.0, 1.0),
-            "red" => Color::new(1.0, 0.0, 0.0, 1.0),
-            "purple" => Color::new(0.5019607843137255, 0.0, 0.5019607843137255, 1.0),
-            "fuchsia" => Color::new(1.0, 0.0, 1.0, 1.0),
-            "green" => Color::new(0.0, 0.5019607843137255, 0.0, 1.0),
-            "lime" => Color::new(0.0, 1.0, 0.0, 1.0),
-            "olive" => Color::new(0.5019607843137255, 0.5019607843137255, 0.0, 1.0),
-            "yellow" => Color::new(1.0, 1.0, 0.0, 1.0),
-            "navy" => Color::new(0.0, 0.0, 0.5019607843137255, 1.0),
-            "blue" => Color::new(0.0, 0.0, 1.0, 1.0),
-            "teal" => Color::new(0.0, 0.5019607843137255, 0.5019607843137255, 1.0),
-            "aqua" => Color::new(0.0, 1.0, 1.0, 1.0),
-            "orange" => Color::new(1.0, 0.6470588235294118, 0.0, 1.0),
-            "aliceblue" => Color::new(0.9411764705882353, 0.9725490196078431, 1.0, 1.0),
-            "antiquewhite" => Color::new(
-                0.9803921568627451,
-                0.9215686274509803,
-                0.8431372549019608,
-                1.0,
-            ),
-            "aquamarine" => Color::new(0.4980392156862745, 1.0, 0.8313725490196079, 1.0),
-            "azure" => Color::new(0.9411764705882353, 1.0, 1.0, 1.0),
-            "beige" => Color::new(
-                0.9607843137254902,
-                0.9607843137254902,
-                0.8627450980392157,
-                1.0,
-            ),
-            "bisque" => Color::new(1.0, 0.8941176470588236, 0.7686274509803922, 1.0),
-            "blanchedalmond" => Color::new(1.0, 0.9215686274509803, 0.803921568627451, 1.0),
-            "blueviolet" => Color::new(
-                0.5411764705882353,
-                0.16862745098039217,
-                0.8862745098039215,
-                1.0,
-            ),
-            "brown" => Color::new(
-                0.6470588235294118,
-                0.16470588235294117,
-                0.16470588235294117,
-                1.0,
-            ),
-            "burlywood" => Color::new(
-                0.8705882352941177,
-                0.7215686274509804,
-                0.5294117647058824,
-                1.0,
-            ),
-            "cadetblue" => Color::new(
-                0.37254901960784315,
-                0.6196078431372549,
-                0.6274509803921569,
-                1.0,
-            ),
-            "chartreuse" => Color::new(0.4980392156862745, 1.0, 0.0, 1.0),
-            "chocolate" => Color::new(
-                0.8235294117647058,
-                0.4117647058823529,
-                0.11764705882352941,
-                1.0,
-            ),
-            "coral" => Color::new(1.0, 0.4980392156862745, 0.3137254901960784, 1.0),
-            "cornflowerblue" => Color::new(
-                0.39215686274509803,
-                0.5843137254901961,
-                0.9294117647058824,
-                1.0,
-            ),
-            "cornsilk" => Color::new(1.0, 0.9725490196078431, 0.8627450980392157, 1.0),
-            "crimson" => Color::new(
-                0.8627450980392157,
-                0.0784313725490196,
-                0.23529411764705882,
-                1.0,
-            ),
-            "darkblue" => Color::new(0.0, 0.0, 0.5450980392156862, 1.0),
-            "darkcyan" => Color::new(0.0, 0.5450980392156862, 0.5450980392156862, 1.0),
-            "darkgoldenrod" => Color::new(
-                0.7215686274509804,
-                0.5254901960784314,
-                0.043137254901960784,
-                1.0,
-            ),
-            "darkgray" | "darkgrey" => Color::new(
-                0.6627450980392157,
-                0.6627450980392157,
-                0.6627450980392157,
-                1.0,
-            ),
-            "darkgreen" => Color::new(0.0, 0.39215686274509803, 0.0, 1.0),
-            "darkkhaki" => Color::new(
-                0.7411764705882353,
-                0.7176470588235294,
-                0.4196078431372549,
-                1.0,
-            ),
-            "darkmagenta" => Color::new(0.5450980392156862, 0.0, 0.5450980392156862, 1.0),
-            "darkolivegreen" => Color::new(
-                0.3333333333333333,
-                0.4196078431372549,
-                0.1843137254901961,
-                1.0,
-            ),
-            "darkorange" => Color::new(1.0, 0.5490196078431373, 0.0, 1.0),
-            "darkorchid" => Color::new(0.6, 0.19607843137254902, 0.8, 1.0),
-            "darkred" => Color::new(0.5450980392156862, 0.0, 0.0, 1.0),
-            "darksalmon" => Color::new(
-                0.9137254901960784,
-                0.5882352941176471,
-                0.47843137254901963,
-                1.0,
-            ),
-            "darkseagreen" => Color::new(
-                0.5607843137254902,
-                0.7372549019607844,
-                0.5607843137254902,
-                1.0,
-            ),
-            "darkslateblue" => Color::new(
-                0.2823529411764706,
-                0.23921568627450981,
-                0.5450980392156862,
-                1.0,
-            ),
-            "darkslategray" | "darkslategrey" => Color::new(
-                0.1843137254901961,
-                0.30980392156862746,
-                0.30980392156862746,
-                1.0,
-            ),
-            "darkturquoise" => Color::new(0.0, 0.807843137254902, 0.8196078431372549, 1.0),
-            "darkviolet" => Color::new(0.5803921568627451, 0.0, 0.8274509803921568, 1.0),
-            "deeppink" => Color::new(1.0, 0.0784313725490196, 0.5764705882352941, 1.0),
-            "deepskyblue" => Color::new(0.0, 0.7490196078431373, 1.0, 1.0),
-            "dimgray" | "dimgrey" => Color::new(
-                0.4117647058823529,
-                0.4117647058823529,
-                0.4117647058823529,
-                1.0,
-            ),
-            "dodgerblue" => Color::new(0.11764705882352941, 0.5647058823529412, 1.0, 1.0),
-            "firebrick" => Color::new(
-                0.6980392156862745,
-                0.13333333333333333,
-                0.13333333333333333,
-                1.0,
-            ),
-            "floralwhite" => Color::new(1.0, 0.9803921568627451, 0.9411764705882353, 1.0),
-            "forestgreen" => Color::new(
-                0.13333333333333333,
-                0.5450980392156862,
-                0.13333333333333333,
-                1.0,
-            ),
-            "gainsboro" => Color::new(
-                0.8627450980392157,
-                0.8627450980392157,
-                0.8627450980392157,
-                1.0,
-            ),
-            "ghostwhite" => Color::new(0.9725490196078431, 0.9725490196078431, 1.0, 1.0),
-            "gold" => Color::new(1.0, 0.8431372549019608, 0.0, 1.0),
-            "goldenrod" => Color::new(
-                0.8549019607843137,
-                0.6470588235294118,
-                0.12549019607843137,
-                1.0,
-            ),
-            "greenyellow" => Color::new(0.6784313725490196, 1.0, 0.1843137254901961, 1.0),
-            "honeydew" => Color::new(0.9411764705882353, 1.0, 0.9411764705882353, 1.0),
-            "hotpink" => Color::new(1.0, 0.4117647058823529, 0.7058823529411765, 1.0),
-            "indianred" => Color::new(
-                0.803921568627451,
-                0.3607843137254902,
-                0.3607843137254902,
-                1.0,
-            ),
-            "indigo" => Color::new(0.29411764705882354, 0.0, 0.5098039215686274, 1.0),
-            "ivory" => Color::new(1.0, 1.0, 0.9411764705882353, 1.0),
-            "khaki" => Color::new(
-                0.9411764705882353,
-                0.9019607843137255,
-                0.5490196078431373,
-                1.0,
-            ),
-            "lavender" => Color::new(
-                0.9019607843137255,
-                0.9019607843137255,
-                0.9803921568627451,
-                1.0,
-            ),
-            "lavenderblush" => Color::new(1.0, 0.9411764705882353, 0.9607843137254902, 1.0),
-            "lawngreen" => Color::new(0.48627450980392156, 0.9882352941176471, 0.0, 1.0),
-            "lemonchiffon" => Color::new(1.0, 0.9803921568627451, 0.803921568627451, 1.0),
-            "lightblue" => Color::new(
-                0.6784313725490196,
-                0.8470588235294118,
-                0.9019607843137255,
-                1.0,
-            ),
-            "lightcoral" => Color::new(
-                0.9411764705882353,
-                0.5019607843137255,
-                0.5019607843137255,
-                1.0,
-            ),
-            "lightcyan" => Color::new(0.8784313725490196, 1.0, 1.0, 1.0),
-            "lightgoldenrodyellow" => Color::new(
-                0.9803921568627451,
-                0.9803921568627451,
-                0.8235294117647058,
-                1.0,
-            ),
-            "lightgray" | "lightgrey" => Color::new(
-                0.8274509803921568,
-                0.8274509803921568,
-                0.8274509803921568,
-                1.0,
-            ),
-            "lightgreen" => Color::new(
-                0.5647058823529412,
-                0.9333333333333333,
-                0.5647058823529412,
-                1.0,
-            ),
-            "lightpink" => Color::new(1.0, 0.7137254901960784, 0.7568627450980392, 1.0),
-            "lightsalmon" => Color::new(1.0, 0.6274509803921569, 0.47843137254901963, 1.0),
-            "lightseagreen" => Color::new(
-                0.12549019607843137,
-                0.6980392156862745,
-                0.6666666666666666,
-                1.0,
-            ),
-            "lightskyblue" => Color::new(
-                0.5294117647058824,
-                0.807843137254902,
-                0.9803921568627451,
-                1.0,
-            ),
-            "lightslategray" | "lightslategrey" => {
-                Color::new(0.4666666666666667, 0.5333333333333333, 0.6, 1.0)
-            }
-            "lightsteelblue" => Color::new(
-                0.6901960784313725,
-                0.7686274509803922,
-                0.8705882352941177,
-                1.0,
-            ),
-            "lightyellow" => Color::new(1.0, 1.0, 0.8784313725490196, 1.0),
-            "limegreen" => Color::new(
-                0.19607843137254902,
-                0.803921568627451,
-                0.19607843137254902,
-                1.0,
-            ),
-            "linen" => Color::new(
-                0.9803921568627451,
-                0.9411764705882353,
-                0.9019607843137255,
-                1.0,
-            ),
-            "mediumaquamarine" => Color::new(0.4, 0.803921568627451, 0.6666666666666666, 1.0),
-            "mediumblue" => Color::new(0.0, 0.0, 0.803921568627451, 1.0),
-            "mediumorchid" => Color::new(
-                0.7294117647058823,
-                0.3333333333333333,
-                0.8274509803921568,
-                1.0,
-            ),
-            "mediumpurple" => Color::new(
-                0.5764705882352941,
-                0.4392156862745098,
-                0.8588235294117647,
-                1.0,
-            ),
-            "mediumseagreen" => Color::new(
-                0.23529411764705882,
-                0.7019607843137254,
-                0.44313725490196076,
-                1.0,
-            ),
-            "mediumslateblue" => Color::new(
-                0.4823529411764706,
-                0.40784313725490196,
-                0.9333333333333333,
-                1.0,
-            ),
-            "mediumspringgreen" => Color::new(0.0, 0.9803921568627451, 0.6039215686274509, 1.0),
-            "mediumturquoise" => Color::new(0.2823529411764706, 0.8196078431372549, 0.8, 1.0),
-            "mediumvioletred" => Color::new(
-                0.7803921568627451,
-                0.08235294117647059,
-                0.5215686274509804,
-                1.0,
-            ),
-            "midnightblue" => Color::new(
-                0.09803921568627451,
-                0.09803921568627451,
-                0.4392156862745098,
-                1.0,
-            ),
-            "mintcream" => Color::new(0.9607843137254902, 1.0, 0.9803921568627451, 1.0),
-            "mistyrose" => Color::new(1.0, 0.8941176470588236, 0.8823529411764706, 1.0),
-            "moccasin" => Color::new(1.0, 0.8941176470588236, 0.7098039215686275, 1.0),
-            "navajowhite" => Color::new(1.0, 0.8705882352941177, 0.6784313725490196, 1.0),
-            "oldlace" => Color::new(
-                0.9921568627450981,
-                0.9607843137254902,
-                0.9019607843137255,
-                1.0,
-            ),
-            "olivedrab" => Color::new(
-                0.4196078431372549,
-                0.5568627450980392,
-                0.13725490196078433,
-                1.0,
-            ),
-            "orangered" => Color::new(1.0, 0.27058823529411763, 0.0, 1.0),
-            "orchid" => Color::new(
-                0.8549019607843137,
-                0.4392156862745098,
-                0.8392156862745098,
-                1.0,
-            ),
-            "palegoldenrod" => Color::new(
-                0.9333333333333333,
-                0.9098039215686274,
-                0.6666666666666666,
-                1.0,
-            ),
-            "palegreen" => Color::new(0.596078431372549, 0.984313725490196, 0.596078431372549, 1.0),
-            "paleturquoise" => Color::new(
-                0.6862745098039216,
-                0.9333333333333333,
-                0.9333333333333333,
-                1.0,
-            ),
-            "palevioletred" => Color::new(
-                0.8588235294117647,
-                0.4392156862745098,
-                0.5764705882352941,
-                1.0,
-            ),
-            "papayawhip" => Color::new(1.0, 0.9372549019607843, 0.8352941176470589, 1.0),
-            "peachpuff" => Color::new(1.0, 0.8549019607843137, 0.7254901960784313, 1.0),
-            "peru" => Color::new(
-                0.803921568627451,
-                0.5215686274509804,
-                0.24705882352941178,
-                1.0,
-            ),
-            "pink" => Color::new(1.0, 0.7529411764705882, 0.796078431372549, 1.0),
-            "plum" => Color::new(
-                0.8666666666666667,
-                0.6274509803921569,
-                0.8666666666666667,
-                1.0,
-            ),
-            "powderblue" => Color::new(
-                0.6901960784313725,
-                0.8784313725490196,
-                0.9019607843137255,
-                1.0,
-            ),
-            "rosybrown" => Color::new(
-                0.7372549019607844,
-                0.5607843137254902,
-                0.5607843137254902,
-                1.0,
-            ),
-            "royalblue" => Color::new(
-                0.2549019607843137,
-                0.4117647058823529,
-                0.8823529411764706,
-                1.0,
-            ),
-            "saddlebrown" => Color::new(
-                0.5450980392156862,
-                0.27058823529411763,
-                0.07450980392156863,
-                1.0,
-            ),
-            "salmon" => Color::new(
-                0.9803921568627451,
-                0.5019607843137255,
-                0.4470588235294118,
-                1.0,
-            ),
-            "sandybrown" => Color::new(
-                0.9568627450980393,
-                0.6431372549019608,
-                0.3764705882352941,
-                1.0,
-            ),
-            "seagreen" => Color::new(
-                0.1803921568627451,
-                0.5450980392156862,
-                0.3411764705882353,
-                1.0,
-            ),
-            "seashell" => Color::new(1.0, 0.9607843137254902, 0.9333333333333333, 1.0),
-            "sienna" => Color::new(
-                0.6274509803921569,
-                0.3215686274509804,
-                0.17647058823529413,
-                1.0,
-            ),
-            "skyblue" => Color::new(
-                0.5294117647058824,
-                0.807843137254902,
-                0.9215686274509803,
-                1.0,
-            ),
-            "slateblue" => Color::new(
-                0.41568627450980394,
-                0.35294117647058826,
-                0.803921568627451,
-                1.0,
-            ),
-            "slategray" | "slategrey" => Color::new(
-                0.4392156862745098,
-                0.5019607843137255,
-                0.5647058823529412,
-                1.0,
-            ),
-            "snow" => Color::new(1.0, 0.9803921568627451, 0.9803921568627451, 1.0),
-            "springgreen" => Color::new(0.0, 1.0, 0.4980392156862745, 1.0),
-            "steelblue" => Color::new(
-                0.27450980392156865,
-                0.5098039215686274,
-                0.7058823529411765,
-                1.0,
-            ),
-            "tan" => Color::new(
-                0.8235294117647058,
-                0.7058823529411765,
-                0.5490196078431373,
-                1.0,
-            ),
-            "thistle" => Color::new(
-                0.8470588235294118,
-                0.7490196078431373,
-                0.8470588235294118,
-                1.0,
-            ),
-            "tomato" => Color::new(1.0, 0.38823529411764707, 0.2784313725490196, 1.0),
-            "turquoise" => Color::new(
-                0.25098039215686274,
-                0.8784313725490196,
-                0.8156862745098039,
-                1.0,
-            ),
-            "violet" => Color::new(
-                0.9333333333333333,
-                0.5098039215686274,
-                0.9333333333333333,
-                1.0,
-            ),
-            "wheat" => Color::new(
-                0.9607843137254902,
-                0.8705882352941177,
-                0.7019607843137254,
-                1.0,
-            ),
-            "whitesmoke" => Color::new(
-                0.9607843137254902,
-                0.9607843137254902,
-                0.9607843137254902,
-                1.0,
-            ),
-            "yellowgreen" => Color::new(
-                0.6039215686274509,
-                0.803921568627451,
-                0.19607843137254902,
-                1.0,
-            ),
-            "rebeccapurple" => Color::new(0.4, 0.2, 0.6, 1.0),
-            _ => Color::new(0.0, 0.0, 0.0, 1.0), //ELSE RETURN BLACK!!!
-        };
+        named_color(color).unwrap_or(Color::new(0.0, 0.0, 0.0, 1.0)) //ELSE RETURN BLACK!!!
     }
+}
+
+fn named_color(color: &str) -> Option<Color> {
+    let color = match color {
+        "black" => Color::new(0.0, 0.0, 0.0, 1.0),
+        "silver" => Color::new(
+            0.7529411764705882,
+            0.7529411764705882,
+            0.7529411764705882,
+            1.0,
+        ),
+        "gray" | "grey" => Color::new(
+            0.5019607843137255,
+            0.5019607843137255,
+            0.5019607843137255,
+            1.0,
+        ),
+        "white" => Color::new(1.0, 1.0, 1.0, 1.0),
+        "maroon" => Color::new(0.5019607843137255, 0.0, 0.0, 1.0),
+        "red" => Color::new(1.0, 0.0, 0.0, 1.0),
+        "purple" => Color::new(0.5019607843137255, 0.0, 0.5019607843137255, 1.0),
+        "fuchsia" => Color::new(1.0, 0.0, 1.0, 1.0),
+        "green" => Color::new(0.0, 0.5019607843137255, 0.0, 1.0),
+        "lime" => Color::new(0.0, 1.0, 0.0, 1.0),
+        "olive" => Color::new(0.5019607843137255, 0.5019607843137255, 0.0, 1.0),
+        "yellow" => Color::new(1.0, 1.0, 0.0, 1.0),
+        "navy" => Color::new(0.0, 0.0, 0.5019607843137255, 1.0),
+        "blue" => Color::new(0.0, 0.0, 1.0, 1.0),
+        "teal" => Color::new(0.0, 0.5019607843137255, 0.5019607843137255, 1.0),
+        "aqua" => Color::new(0.0, 1.0, 1.0, 1.0),
+        "orange" => Color::new(1.0, 0.6470588235294118, 0.0, 1.0),
+        "aliceblue" => Color::new(0.9411764705882353, 0.9725490196078431, 1.0, 1.0),
+        "antiquewhite" => Color::new(
+            0.9803921568627451,
+            0.9215686274509803,
+            0.8431372549019608,
+            1.0,
+        ),
+        "aquamarine" => Color::new(0.4980392156862745, 1.0, 0.8313725490196079, 1.0),
+        "azure" => Color::new(0.9411764705882353, 1.0, 1.0, 1.0),
+        "beige" => Color::new(
+            0.9607843137254902,
+            0.9607843137254902,
+            0.8627450980392157,
+            1.0,
+        ),
+        "bisque" => Color::new(1.0, 0.8941176470588236, 0.7686274509803922, 1.0),
+        "blanchedalmond" => Color::new(1.0, 0.9215686274509803, 0.803921568627451, 1.0),
+        "blueviolet" => Color::new(
+            0.5411764705882353,
+            0.16862745098039217,
+            0.8862745098039215,
+            1.0,
+        ),
+        "brown" => Color::new(
+            0.6470588235294118,
+            0.16470588235294117,
+            0.16470588235294117,
+            1.0,
+        ),
+        "burlywood" => Color::new(
+            0.8705882352941177,
+            0.7215686274509804,
+            0.5294117647058824,
+            1.0,
+        ),
+        "cadetblue" => Color::new(
+            0.37254901960784315,
+            0.6196078431372549,
+            0.6274509803921569,
+            1.0,
+        ),
+        "chartreuse" => Color::new(0.4980392156862745, 1.0, 0.0, 1.0),
+        "chocolate" => Color::new(
+            0.8235294117647058,
+            0.4117647058823529,
+            0.11764705882352941,
+            1.0,
+        ),
+        "coral" => Color::new(1.0, 0.4980392156862745, 0.3137254901960784, 1.0),
+        "cornflowerblue" => Color::new(
+            0.39215686274509803,
+            0.5843137254901961,
+            0.9294117647058824,
+            1.0,
+        ),
+        "cornsilk" => Color::new(1.0, 0.9725490196078431, 0.8627450980392157, 1.0),
+        "crimson" => Color::new(
+            0.8627450980392157,
+            0.0784313725490196,
+            0.23529411764705882,
+            1.0,
+        ),
+        "darkblue" => Color::new(0.0, 0.0, 0.5450980392156862, 1.0),
+        "darkcyan" => Color::new(0.0, 0.5450980392156862, 0.5450980392156862, 1.0),
+        "darkgoldenrod" => Color::new(
+            0.7215686274509804,
+            0.5254901960784314,
+            0.043137254901960784,
+            1.0,
+        ),
+        "darkgray" | "darkgrey" => Color::new(
+            0.6627450980392157,
+            0.6627450980392157,
+            0.6627450980392157,
+            1.0,
+        ),
+        "darkgreen" => Color::new(0.0, 0.39215686274509803, 0.0, 1.0),
+        "darkkhaki" => Color::new(
+            0.7411764705882353,
+            0.7176470588235294,
+            0.4196078431372549,
+            1.0,
+        ),
+        "darkmagenta" => Color::new(0.5450980392156862, 0.0, 0.5450980392156862, 1.0),
+        "darkolivegreen" => Color::new(
+            0.3333333333333333,
+            0.4196078431372549,
+            0.1843137254901961,
+            1.0,
+        ),
+        "darkorange" => Color::new(1.0, 0.5490196078431373, 0.0, 1.0),
+        "darkorchid" => Color::new(0.6, 0.19607843137254902, 0.8, 1.0),
+        "darkred" => Color::new(0.5450980392156862, 0.0, 0.0, 1.0),
+        "darksalmon" => Color::new(
+            0.9137254901960784,
+            0.5882352941176471,
+            0.47843137254901963,
+            1.0,
+        ),
+        "darkseagreen" => Color::new(
+            0.5607843137254902,
+            0.7372549019607844,
+            0.5607843137254902,
+            1.0,
+        ),
+        "darkslateblue" => Color::new(
+            0.2823529411764706,
+            0.23921568627450981,
+            0.5450980392156862,
+            1.0,
+        ),
+        "darkslategray" | "darkslategrey" => Color::new(
+            0.1843137254901961,
+            0.30980392156862746,
+            0.30980392156862746,
+            1.0,
+        ),
+        "darkturquoise" => Color::new(0.0, 0.807843137254902, 0.8196078431372549, 1.0),
+        "darkviolet" => Color::new(0.5803921568627451, 0.0, 0.8274509803921568, 1.0),
+        "deeppink" => Color::new(1.0, 0.0784313725490196, 0.5764705882352941, 1.0),
+        "deepskyblue" => Color::new(0.0, 0.7490196078431373, 1.0, 1.0),
+        "dimgray" | "dimgrey" => Color::new(
+            0.4117647058823529,
+            0.4117647058823529,
+            0.4117647058823529,
+            1.0,
+        ),
+        "dodgerblue" => Color::new(0.11764705882352941, 0.5647058823529412, 1.0, 1.0),
+        "firebrick" => Color::new(
+            0.6980392156862745,
+            0.13333333333333333,
+            0.13333333333333333,
+            1.0,
+        ),
+        "floralwhite" => Color::new(1.0, 0.9803921568627451, 0.9411764705882353, 1.0),
+        "forestgreen" => Color::new(
+            0.13333333333333333,
+            0.5450980392156862,
+            0.13333333333333333,
+            1.0,
+        ),
+        "gainsboro" => Color::new(
+            0.8627450980392157,
+            0.8627450980392157,
+            0.8627450980392157,
+            1.0,
+        ),
+        "ghostwhite" => Color::new(0.9725490196078431, 0.9725490196078431, 1.0, 1.0),
+        "gold" => Color::new(1.0, 0.8431372549019608, 0.0, 1.0),
+        "goldenrod" => Color::new(
+            0.8549019607843137,
+            0.6470588235294118,
+            0.12549019607843137,
+            1.0,
+        ),
+        "greenyellow" => Color::new(0.6784313725490196, 1.0, 0.1843137254901961, 1.0),
+        "honeydew" => Color::new(0.9411764705882353, 1.0, 0.9411764705882353, 1.0),
+        "hotpink" => Color::new(1.0, 0.4117647058823529, 0.7058823529411765, 1.0),
+        "indianred" => Color::new(
+            0.803921568627451,
+            0.3607843137254902,
+            0.3607843137254902,
+            1.0,
+        ),
+        "indigo" => Color::new(0.29411764705882354, 0.0, 0.5098039215686274, 1.0),
+        "ivory" => Color::new(1.0, 1.0, 0.9411764705882353, 1.0),
+        "khaki" => Color::new(
+            0.9411764705882353,
+            0.9019607843137255,
+            0.5490196078431373,
+            1.0,
+        ),
+        "lavender" => Color::new(
+            0.9019607843137255,
+            0.9019607843137255,
+            0.9803921568627451,
+            1.0,
+        ),
+        "lavenderblush" => Color::new(1.0, 0.9411764705882353, 0.9607843137254902, 1.0),
+        "lawngreen" => Color::new(0.48627450980392156, 0.9882352941176471, 0.0, 1.0),
+        "lemonchiffon" => Color::new(1.0, 0.9803921568627451, 0.803921568627451, 1.0),
+        "lightblue" => Color::new(
+            0.6784313725490196,
+            0.8470588235294118,
+            0.9019607843137255,
+            1.0,
+        ),
+        "lightcoral" => Color::new(
+            0.9411764705882353,
+            0.5019607843137255,
+            0.5019607843137255,
+            1.0,
+        ),
+        "lightcyan" => Color::new(0.8784313725490196, 1.0, 1.0, 1.0),
+        "lightgoldenrodyellow" => Color::new(
+            0.9803921568627451,
+            0.9803921568627451,
+            0.8235294117647058,
+            1.0,
+        ),
+        "lightgray" | "lightgrey" => Color::new(
+            0.8274509803921568,
+            0.8274509803921568,
+            0.8274509803921568,
+            1.0,
+        ),
+        "lightgreen" => Color::new(
+            0.5647058823529412,
+            0.9333333333333333,
+            0.5647058823529412,
+            1.0,
+        ),
+        "lightpink" => Color::new(1.0, 0.7137254901960784, 0.7568627450980392, 1.0),
+        "lightsalmon" => Color::new(1.0, 0.6274509803921569, 0.47843137254901963, 1.0),
+        "lightseagreen" => Color::new(
+            0.12549019607843137,
+            0.6980392156862745,
+            0.6666666666666666,
+            1.0,
+        ),
+        "lightskyblue" => Color::new(
+            0.5294117647058824,
+            0.807843137254902,
+            0.9803921568627451,
+            1.0,
+        ),
+        "lightslategray" | "lightslategrey" => {
+            Color::new(0.4666666666666667, 0.5333333333333333, 0.6, 1.0)
+        }
+        "lightsteelblue" => Color::new(
+            0.6901960784313725,
+            0.7686274509803922,
+            0.8705882352941177,
+            1.0,
+        ),
+        "lightyellow" => Color::new(1.0, 1.0, 0.8784313725490196, 1.0),
+        "limegreen" => Color::new(
+            0.19607843137254902,
+            0.803921568627451,
+            0.19607843137254902,
+            1.0,
+        ),
+        "linen" => Color::new(
+            0.9803921568627451,
+            0.9411764705882353,
+            0.9019607843137255,
+            1.0,
+        ),
+        "mediumaquamarine" => Color::new(0.4, 0.803921568627451, 0.6666666666666666, 1.0),
+        "mediumblue" => Color::new(0.0, 0.0, 0.803921568627451, 1.0),
+        "mediumorchid" => Color::new(
+            0.7294117647058823,
+            0.3333333333333333,
+            0.8274509803921568,
+            1.0,
+        ),
+        "mediumpurple" => Color::new(
+            0.5764705882352941,
+            0.4392156862745098,
+            0.8588235294117647,
+            1.0,
+        ),
+        "mediumseagreen" => Color::new(
+            0.23529411764705882,
+            0.7019607843137254,
+            0.44313725490196076,
+            1.0,
+        ),
+        "mediumslateblue" => Color::new(
+            0.4823529411764706,
+            0.40784313725490196,
+            0.9333333333333333,
+            1.0,
+        ),
+        "mediumspringgreen" => Color::new(0.0, 0.9803921568627451, 0.6039215686274509, 1.0),
+        "mediumturquoise" => Color::new(0.2823529411764706, 0.8196078431372549, 0.8, 1.0),
+        "mediumvioletred" => Color::new(
+            0.7803921568627451,
+            0.08235294117647059,
+            0.5215686274509804,
+            1.0,
+        ),
+        "midnightblue" => Color::new(
+            0.09803921568627451,
+            0.09803921568627451,
+            0.4392156862745098,
+            1.0,
+        ),
+        "mintcream" => Color::new(0.9607843137254902, 1.0, 0.9803921568627451, 1.0),
+        "mistyrose" => Color::new(1.0, 0.8941176470588236, 0.8823529411764706, 1.0),
+        "moccasin" => Color::new(1.0, 0.8941176470588236, 0.7098039215686275, 1.0),
+        "navajowhite" => Color::new(1.0, 0.8705882352941177, 0.6784313725490196, 1.0),
+        "oldlace" => Color::new(
+            0.9921568627450981,
+            0.9607843137254902,
+            0.9019607843137255,
+            1.0,
+        ),
+        "olivedrab" => Color::new(
+            0.4196078431372549,
+            0.5568627450980392,
+            0.13725490196078433,
+            1.0,
+        ),
+        "orangered" => Color::new(1.0, 0.27058823529411763, 0.0, 1.0),
+        "orchid" => Color::new(
+            0.8549019607843137,
+            0.4392156862745098,
+            0.8392156862745098,
+            1.0,
+        ),
+        "palegoldenrod" => Color::new(
+            0.9333333333333333,
+            0.9098039215686274,
+            0.6666666666666666,
+            1.0,
+        ),
+        "palegreen" => Color::new(0.596078431372549, 0.984313725490196, 0.596078431372549, 1.0),
+        "paleturquoise" => Color::new(
+            0.6862745098039216,
+            0.9333333333333333,
+            0.9333333333333333,
+            1.0,
+        ),
+        "palevioletred" => Color::new(
+            0.8588235294117647,
+            0.4392156862745098,
+            0.5764705882352941,
+            1.0,
+        ),
+        "papayawhip" => Color::new(1.0, 0.9372549019607843, 0.8352941176470589, 1.0),
+        "peachpuff" => Color::new(1.0, 0.8549019607843137, 0.7254901960784313, 1.0),
+        "peru" => Color::new(
+            0.803921568627451,
+            0.5215686274509804,
+            0.24705882352941178,
+            1.0,
+        ),
+        "pink" => Color::new(1.0, 0.7529411764705882, 0.796078431372549, 1.0),
+        "plum" => Color::new(
+            0.8666666666666667,
+            0.6274509803921569,
+            0.8666666666666667,
+            1.0,
+        ),
+        "powderblue" => Color::new(
+            0.6901960784313725,
+            0.8784313725490196,
+            0.9019607843137255,
+            1.0,
+        ),
+        "rosybrown" => Color::new(
+            0.7372549019607844,
+            0.5607843137254902,
+            0.5607843137254902,
+            1.0,
+        ),
+        "royalblue" => Color::new(
+            0.2549019607843137,
+            0.4117647058823529,
+            0.8823529411764706,
+            1.0,
+        ),
+        "saddlebrown" => Color::new(
+            0.5450980392156862,
+            0.27058823529411763,
+            0.07450980392156863,
+            1.0,
+        ),
+        "salmon" => Color::new(
+            0.9803921568627451,
+            0.5019607843137255,
+            0.4470588235294118,
+            1.0,
+        ),
+        "sandybrown" => Color::new(
+            0.9568627450980393,
+            0.6431372549019608,
+            0.3764705882352941,
+            1.0,
+        ),
+        "seagreen" => Color::new(
+            0.1803921568627451,
+            0.5450980392156862,
+            0.3411764705882353,
+            1.0,
+        ),
+        "seashell" => Color::new(1.0, 0.9607843137254902, 0.9333333333333333, 1.0),
+        "sienna" => Color::new(
+            0.6274509803921569,
+            0.3215686274509804,
+            0.17647058823529413,
+            1.0,
+        ),
+        "skyblue" => Color::new(
+            0.5294117647058824,
+            0.807843137254902,
+            0.9215686274509803,
+            1.0,
+        ),
+        "slateblue" => Color::new(
+            0.41568627450980394,
+            0.35294117647058826,
+            0.803921568627451,
+            1.0,
+        ),
+        "slategray" | "slategrey" => Color::new(
+            0.4392156862745098,
+            0.5019607843137255,
+            0.5647058823529412,
+            1.0,
+        ),
+        "snow" => Color::new(1.0, 0.9803921568627451, 0.9803921568627451, 1.0),
+        "springgreen" => Color::new(0.0, 1.0, 0.4980392156862745, 1.0),
+        "steelblue" => Color::new(
+            0.27450980392156865,
+            0.5098039215686274,
+            0.7058823529411765,
+            1.0,
+        ),
+        "tan" => Color::new(
+            0.8235294117647058,
+            0.7058823529411765,
+            0.5490196078431373,
+            1.0,
+        ),
+        "thistle" => Color::new(
+            0.8470588235294118,
+            0.7490196078431373,
+            0.8470588235294118,
+            1.0,
+        ),
+        "tomato" => Color::new(1.0, 0.38823529411764707, 0.2784313725490196, 1.0),
+        "turquoise" => Color::new(
+            0.25098039215686274,
+            0.8784313725490196,
+            0.8156862745098039,
+            1.0,
+        ),
+        "violet" => Color::new(
+            0.9333333333333333,
+            0.5098039215686274,
+            0.9333333333333333,
+            1.0,
+        ),
+        "wheat" => Color::new(
+            0.9607843137254902,
+            0.8705882352941177,
+            0.7019607843137254,
+            1.0,
+        ),
+        "whitesmoke" => Color::new(
+            0.9607843137254902,
+            0.9607843137254902,
+            0.9607843137254902,
+            1.0,
+        ),
+        "yellowgreen" => Color::new(
+            0.6039215686274509,
+            0.803921568627451,
+            0.19607843137254902,
+            1.0,
+        ),
+        "rebeccapurple" => Color::new(0.4, 0.2, 0.6, 1.0),
+        _ => return None,
+    };
+    Some(color)
 }
\ No newline at end of file
@@ -0,0 +1,544 @@
+/* The CSS tokenizer, the first half of parsing a stylesheet like the html tokenizer is for HTML
+ * It follows CSS Syntax Level 3: https://www.w3.org/TR/css-syntax-3/#tokenization
+ * The input is cut into idents, functions, at-keywords, hashes, strings, urls, numbers, percentages, dimensions,
+ * whitespace and single characters (delimiters, brackets, ':' ';' ','), comments are dropped on the way
+ * Like in HTML there is no invalid input, a broken string or url becomes a bad-string or bad-url token and the
+ * tokenizer keeps going, the parser decides what to throw away
+ */
+
+use std::fmt;
+
+use crate::source::{LineIndex, Span};
+
+// NOTE 1- Tokens
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CssToken {
+    Ident(String),
+    Function(String), //the name of a function, the '(' is part of the token
+    AtKeyword(String),
+    Hash { value: String, id: bool }, //id: the part after '#' is a valid ident, #main is but #123 isn't
+    String(String),
+    BadString, //a string with a newline in it
+    Url(String), //url(...) without quotes, with quotes it's the url function and a string
+    BadUrl,
+    Delim(char),
+    Number(Number),
+    Percentage(Number),
+    Dimension(Number, String), //the unit as it was written, 10PX is 10 and "PX"
+    Whitespace,
+    Cdo, //<!--
+    Cdc, //-->
+    Colon,
+    Semicolon,
+    Comma,
+    OpenSquare,
+    CloseSquare,
+    OpenParen,
+    CloseParen,
+    OpenCurly,
+    CloseCurly,
+    Eof,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Number {
+    pub value: f32,
+    pub integer: bool, //written without a '.' or an exponent
+    pub signed: bool,  //written with a '+' or '-' in front, an+b cares ("2n+1" is fine, "2n 1" isn't)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CssTokenizerError {
+    EofInComment,
+    EofInString,
+    NewlineInString,
+    EofInUrl,
+    InvalidUrl,    //a quote, '(' or whitespace in the middle of url(...)
+    InvalidEscape, //a '\' followed by a newline outside of a string
+    EofInEscape,
+}
+
+// NOTE 2- Tokenizer
+
+pub struct CssTokenizer {
+    chars: Vec<char>,
+    pos: usize,
+    lines: LineIndex, //turns an index into the stylesheet into a line and column
+    errors: Vec<(CssTokenizerError, Span)>,
+}
+
+impl CssTokenizer {
+    pub fn new(input: &str) -> CssTokenizer {
+        let chars = preprocess(input);
+        let lines = LineIndex::new(&chars);
+        CssTokenizer { chars, pos: 0, lines, errors: Vec::new() }
+    }
+
+    //Every token up to and including Eof, with where it came from
+    pub fn tokenize(&mut self) -> Vec<(CssToken, Span)> {
+        let mut tokens = Vec::new();
+        loop {
+            let start = self.pos;
+            let token = self.next_token();
+            let span = self.lines.span(start, self.pos);
+            let done = token == CssToken::Eof;
+            tokens.push((token, span));
+            if done {
+                return tokens;
+            }
+        }
+    }
+
+    pub fn take_errors(&mut self) -> Vec<(CssTokenizerError, Span)> {
+        std::mem::take(&mut self.errors)
+    }
+
+    //"Consume a token"
+    pub fn next_token(&mut self) -> CssToken {
+        self.consume_comments();
+        let c = match self.next_char() {
+            Some(c) => c,
+            None => return CssToken::Eof,
+        };
+        match c {
+            c if is_whitespace(c) => {
+                while self.peek(0).is_some_and(is_whitespace) {
+                    self.pos += 1;
+                }
+                CssToken::Whitespace
+            }
+            '"' | '\'' => self.consume_string(c),
+            '#' => {
+                if self.peek(0).is_some_and(is_name_char) || self.is_valid_escape(0) {
+                    let id = self.starts_ident(0);
+                    CssToken::Hash { value: self.consume_name(), id }
+                } else {
+                    CssToken::Delim('#')
+                }
+            }
+            '(' => CssToken::OpenParen,
+            ')' => CssToken::CloseParen,
+            '+' | '.' => {
+                if self.starts_number(-1) {
+                    self.pos -= 1;
+                    self.consume_numeric()
+                } else {
+                    CssToken::Delim(c)
+                }
+            }
+            ',' => CssToken::Comma,
+            '-' => {
+                if self.starts_number(-1) {
+                    self.pos -= 1;
+                    self.consume_numeric()
+                } else if self.peek(0) == Some('-') && self.peek(1) == Some('>') {
+                    self.pos += 2;
+                    CssToken::Cdc
+                } else if self.starts_ident(-1) {
+                    self.pos -= 1;
+                    self.consume_ident_like()
+                } else {
+                    CssToken::Delim('-')
+                }
+            }
+            ':' => CssToken::Colon,
+            ';' => CssToken::Semicolon,
+            '<' => {
+                if self.peek(0) == Some('!') && self.peek(1) == Some('-') && self.peek(2) == Some('-') {
+                    self.pos += 3;
+                    CssToken::Cdo
+                } else {
+                    CssToken::Delim('<')
+                }
+            }
+            '@' => {
+                if self.starts_ident(0) {
+                    CssToken::AtKeyword(self.consume_name())
+                } else {
+                    CssToken::Delim('@')
+                }
+            }
+            '[' => CssToken::OpenSquare,
+            '\\' => {
+                if self.is_valid_escape(-1) {
+                    self.pos -= 1;
+                    self.consume_ident_like()
+                } else {
+                    self.error(CssTokenizerError::InvalidEscape);
+                    CssToken::Delim('\\')
+                }
+            }
+            ']' => CssToken::CloseSquare,
+            '{' => CssToken::OpenCurly,
+            '}' => CssToken::CloseCurly,
+            c if c.is_ascii_digit() => {
+                self.pos -= 1;
+                self.consume_numeric()
+            }
+            c if is_name_start_char(c) => {
+                self.pos -= 1;
+                self.consume_ident_like()
+            }
+            c => CssToken::Delim(c),
+        }
+    }
+
+    //ANCHOR Helpers
+    fn peek(&self, offset: isize) -> Option<char> {
+        let index = self.pos as isize + offset;
+        if index < 0 {
+            return None;
+        }
+        self.chars.get(index as usize).cloned()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).cloned();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn error(&mut self, error: CssTokenizerError) {
+        let start = if self.pos > 0 { self.pos - 1 } else { 0 };
+        let span = self.lines.span(start, self.pos);
+        self.errors.push((error, span));
+    }
+
+    //ANCHOR Comments
+    fn consume_comments(&mut self) {
+        while self.peek(0) == Some('/') && self.peek(1) == Some('*') {
+            self.pos += 2;
+            loop {
+                match self.next_char() {
+                    Some('*') if self.peek(0) == Some('/') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(_) => {}
+                    None => {
+                        self.error(CssTokenizerError::EofInComment);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    //ANCHOR Checks
+    //"Check if two code points are a valid escape", starting at the character `offset` away from the next one
+    fn is_valid_escape(&self, offset: isize) -> bool {
+        self.peek(offset) == Some('\\') && self.peek(offset + 1).is_some_and(|c| c != '\n')
+    }
+
+    //"Check if three code points would start an ident sequence"
+    fn starts_ident(&self, offset: isize) -> bool {
+        match self.peek(offset) {
+            Some('-') => {
+                self.peek(offset + 1).is_some_and(|c| is_name_start_char(c) || c == '-') || self.is_valid_escape(offset + 1)
+            }
+            Some('\\') => self.is_valid_escape(offset),
+            Some(c) => is_name_start_char(c),
+            None => false,
+        }
+    }
+
+    //"Check if three code points would start a number"
+    fn starts_number(&self, offset: isize) -> bool {
+        let is_digit = |o: isize| self.peek(o).is_some_and(|c| c.is_ascii_digit());
+        match self.peek(offset) {
+            Some('+') | Some('-') => is_digit(offset + 1) || (self.peek(offset + 1) == Some('.') && is_digit(offset + 2)),
+            Some('.') => is_digit(offset + 1),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    //ANCHOR Consume
+    fn consume_string(&mut self, quote: char) -> CssToken {
+        let mut value = String::new();
+        loop {
+            match self.next_char() {
+                Some(c) if c == quote => return CssToken::String(value),
+                None => {
+                    self.error(CssTokenizerError::EofInString);
+                    return CssToken::String(value);
+                }
+                Some('\n') => {
+                    //the newline isn't part of the string, it's whitespace after it
+                    self.pos -= 1;
+                    self.error(CssTokenizerError::NewlineInString);
+                    return CssToken::BadString;
+                }
+                Some('\\') => match self.peek(0) {
+                    None => {}
+                    Some('\n') => self.pos += 1, //an escaped newline continues the string on the next line
+                    Some(_) => value.push(self.consume_escape()),
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn consume_numeric(&mut self) -> CssToken {
+        let number = self.consume_number();
+        if self.starts_ident(0) {
+            CssToken::Dimension(number, self.consume_name())
+        } else if self.peek(0) == Some('%') {
+            self.pos += 1;
+            CssToken::Percentage(number)
+        } else {
+            CssToken::Number(number)
+        }
+    }
+
+    fn consume_number(&mut self) -> Number {
+        let mut repr = String::new();
+        let mut integer = true;
+        let sign = self.peek(0).filter(|&c| c == '+' || c == '-');
+        if let Some(sign) = sign {
+            repr.push(sign);
+            self.pos += 1;
+        }
+        self.consume_digits(&mut repr);
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            repr.push('.');
+            self.pos += 1;
+            self.consume_digits(&mut repr);
+            integer = false;
+        }
+        let exponent_digits = match (self.peek(1), self.peek(2)) {
+            (Some('+'), Some(d)) | (Some('-'), Some(d)) if d.is_ascii_digit() => 2,
+            (Some(d), _) if d.is_ascii_digit() => 1,
+            _ => 0,
+        };
+        if exponent_digits > 0 && (self.peek(0) == Some('e') || self.peek(0) == Some('E')) {
+            for _ in 0..exponent_digits {
+                repr.push(self.next_char().unwrap());
+            }
+            self.consume_digits(&mut repr);
+            integer = false;
+        }
+        Number { value: repr.parse().unwrap_or(0.0), integer, signed: sign.is_some() }
+    }
+
+    fn consume_digits(&mut self, repr: &mut String) {
+        while let Some(c) = self.peek(0).filter(|c| c.is_ascii_digit()) {
+            repr.push(c);
+            self.pos += 1;
+        }
+    }
+
+    fn consume_ident_like(&mut self) -> CssToken {
+        let name = self.consume_name();
+        if self.peek(0) != Some('(') {
+            return CssToken::Ident(name);
+        }
+        self.pos += 1;
+        if !name.eq_ignore_ascii_case("url") {
+            return CssToken::Function(name);
+        }
+
+        //url( followed by a quote is a normal function with a string argument, anything else is a url token
+        while self.peek(0).is_some_and(is_whitespace) && self.peek(1).is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+        let next = if self.peek(0).is_some_and(is_whitespace) { self.peek(1) } else { self.peek(0) };
+        match next {
+            Some('"') | Some('\'') => CssToken::Function(name),
+            _ => self.consume_url(),
+        }
+    }
+
+    fn consume_url(&mut self) -> CssToken {
+        let mut value = String::new();
+        while self.peek(0).is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+        loop {
+            match self.next_char() {
+                Some(')') => return CssToken::Url(value),
+                None => {
+                    self.error(CssTokenizerError::EofInUrl);
+                    return CssToken::Url(value);
+                }
+                Some(c) if is_whitespace(c) => {
+                    while self.peek(0).is_some_and(is_whitespace) {
+                        self.pos += 1;
+                    }
+                    match self.peek(0) {
+                        Some(')') => {
+                            self.pos += 1;
+                            return CssToken::Url(value);
+                        }
+                        None => {
+                            self.error(CssTokenizerError::EofInUrl);
+                            return CssToken::Url(value);
+                        }
+                        Some(_) => {
+                            self.error(CssTokenizerError::InvalidUrl);
+                            self.consume_bad_url_remnants();
+                            return CssToken::BadUrl;
+                        }
+                    }
+                }
+                Some('"') | Some('\'') | Some('(') => {
+                    self.error(CssTokenizerError::InvalidUrl);
+                    self.consume_bad_url_remnants();
+                    return CssToken::BadUrl;
+                }
+                Some(c) if is_non_printable(c) => {
+                    self.error(CssTokenizerError::InvalidUrl);
+                    self.consume_bad_url_remnants();
+                    return CssToken::BadUrl;
+                }
+                Some('\\') => {
+                    if self.is_valid_escape(-1) {
+                        value.push(self.consume_escape());
+                    } else {
+                        self.error(CssTokenizerError::InvalidEscape);
+                        self.consume_bad_url_remnants();
+                        return CssToken::BadUrl;
+                    }
+                }
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    //Skip the rest of a broken url up to its ')', escapes included so "\)" doesn't end it
+    fn consume_bad_url_remnants(&mut self) {
+        loop {
+            match self.next_char() {
+                Some(')') | None => return,
+                Some('\\') if self.is_valid_escape(-1) => {
+                    self.consume_escape();
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    //"Consume an ident sequence", the name of an ident, function, at-keyword, hash or unit
+    fn consume_name(&mut self) -> String {
+        let mut name = String::new();
+        loop {
+            match self.peek(0) {
+                Some(c) if is_name_char(c) => {
+                    name.push(c);
+                    self.pos += 1;
+                }
+                Some('\\') if self.is_valid_escape(0) => {
+                    self.pos += 1;
+                    name.push(self.consume_escape());
+                }
+                _ => return name,
+            }
+        }
+    }
+
+    //"Consume an escaped code point", the '\' is already consumed
+    fn consume_escape(&mut self) -> char {
+        let c = match self.next_char() {
+            Some(c) => c,
+            None => {
+                self.error(CssTokenizerError::EofInEscape);
+                return '\u{FFFD}';
+            }
+        };
+        if !c.is_ascii_hexdigit() {
+            return c;
+        }
+        let mut hex = c.to_string();
+        while hex.len() < 6 && self.peek(0).is_some_and(|c| c.is_ascii_hexdigit()) {
+            hex.push(self.next_char().unwrap());
+        }
+        if self.peek(0).is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+        let code = u32::from_str_radix(&hex, 16).unwrap_or(0);
+        match code {
+            0 => '\u{FFFD}',
+            code => std::char::from_u32(code).unwrap_or('\u{FFFD}'), //surrogates and anything past U+10FFFF
+        }
+    }
+}
+
+// NOTE 3- Helper Methods
+
+impl fmt::Display for CssToken {
+    //The token written back out as CSS, close enough to what was in the stylesheet to show in values and errors
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CssToken::Ident(ref s) => write!(f, "{}", s),
+            CssToken::Function(ref s) => write!(f, "{}(", s),
+            CssToken::AtKeyword(ref s) => write!(f, "@{}", s),
+            CssToken::Hash { ref value, .. } => write!(f, "#{}", value),
+            CssToken::String(ref s) => write!(f, "{:?}", s),
+            CssToken::BadString => write!(f, "\""),
+            CssToken::Url(ref s) => write!(f, "url({})", s),
+            CssToken::BadUrl => write!(f, "url()"),
+            CssToken::Delim(c) => write!(f, "{}", c),
+            CssToken::Number(n) => write!(f, "{}", n),
+            CssToken::Percentage(n) => write!(f, "{}%", n),
+            CssToken::Dimension(n, ref unit) => write!(f, "{}{}", n, unit),
+            CssToken::Whitespace => write!(f, " "),
+            CssToken::Cdo => write!(f, "<!--"),
+            CssToken::Cdc => write!(f, "-->"),
+            CssToken::Colon => write!(f, ":"),
+            CssToken::Semicolon => write!(f, ";"),
+            CssToken::Comma => write!(f, ","),
+            CssToken::OpenSquare => write!(f, "["),
+            CssToken::CloseSquare => write!(f, "]"),
+            CssToken::OpenParen => write!(f, "("),
+            CssToken::CloseParen => write!(f, ")"),
+            CssToken::OpenCurly => write!(f, "{{"),
+            CssToken::CloseCurly => write!(f, "}}"),
+            CssToken::Eof => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+//"Preprocessing the input stream", every kind of newline becomes '\n' and NULL becomes U+FFFD
+fn preprocess(input: &str) -> Vec<char> {
+    let mut chars = Vec::with_capacity(input.len());
+    let mut iter = input.chars().peekable();
+    while let Some(c) = iter.next() {
+        match c {
+            '\r' => {
+                if iter.peek() == Some(&'\n') {
+                    iter.next();
+                }
+                chars.push('\n');
+            }
+            '\u{000C}' => chars.push('\n'),
+            '\0' => chars.push('\u{FFFD}'),
+            c => chars.push(c),
+        }
+    }
+    chars
+}
+
+pub fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n'
+}
+
+fn is_name_start_char(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c >= '\u{0080}'
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c) || c.is_ascii_digit() || c == '-'
+}
+
+fn is_non_printable(c: char) -> bool {
+    c <= '\u{0008}' || c == '\u{000B}' || ('\u{000E}'..='\u{001F}').contains(&c) || c == '\u{007F}'
+}
@@ -0,0 +1,843 @@
+/* Arena version of the Dom
+ * dom::Node owns its children and can't see its parent, fine for building a tree once but useless for anything that
+ * has to walk upward (descendant selectors, closest(), event bubbling)
+ * Here every node lives in one Vec owned by the Document and is named by a NodeId, nodes link to their parent,
+ * first & last child and both siblings, the same links the real DOM has
+ * A NodeId stays valid for as long as the Document lives, nodes are never moved or removed from the arena
+ * The owned dom::Node tree is still the easy way to build a tree by hand (or get one from the parser),
+ * Document::from_node turns it into an arena and to_node turns any part of it back
+ * Nodes can be created, moved around and removed with the same operations the DOM has (appendChild, insertBefore ...),
+ * a removed node is only unlinked, it keeps its id and can be inserted again
+ * Those operations also report what they changed to the observers, see mutation.rs
+ */
+
+use crate::css_parser::CssParser;
+use crate::dom::{AttrMap, ElementData, ElementState, Node, NodeType, QuirksMode};
+use crate::mutation::{MutationRecord, ObserveOptions, ObserverId, Observers};
+use crate::source::Span;
+use crate::style::{collapse_whitespace, selector_matches, WhiteSpace};
+
+// NOTE 1- Data Structures
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+//One node in the arena, the links are private so they can't get out of sync
+#[derive(Debug, Clone)]
+pub struct NodeData {
+    pub node_type: NodeType,
+    pub span: Span, //where the node came from in the HTML source
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+}
+
+pub struct Document {
+    nodes: Vec<NodeData>,
+    observers: Observers,
+}
+
+//Why a mutation was refused, named after the DOMException the browser would throw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomError {
+    HierarchyRequest, //the node can't go there: into a text node, into its own subtree, a document inside something,
+                      //text or a second element or doctype right in the document, a doctype anywhere else
+    NotFound,         //the reference node isn't a child of the parent
+    Syntax,           //querySelector got a selector we can't parse
+}
+
+// NOTE 2- Implement Methods For These DataStructures
+
+impl NodeData {
+    fn new(node_type: NodeType, span: Span) -> NodeData {
+        NodeData {
+            node_type,
+            span,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None,
+        }
+    }
+}
+
+impl Document {
+    //Empty document, just the root #document node
+    pub fn new(quirks_mode: QuirksMode) -> Document {
+        Document { nodes: vec![NodeData::new(NodeType::Document(quirks_mode), Span::default())], observers: Observers::default() }
+    }
+
+    //Convenience builder, copy an owned tree (like the one HtmlParser::parse_document returns) into a new arena
+    //The given node becomes the root, it doesn't have to be a #document
+    pub fn from_node(node: &Node) -> Document {
+        let mut document = Document { nodes: vec![NodeData::new(node.node_type.clone(), node.span)], observers: Observers::default() };
+        let root = document.root();
+        document.append_children(root, &node.children);
+        document
+    }
+
+    fn append_children(&mut self, parent: NodeId, children: &[Node]) {
+        for child in children {
+            let id = self.push(child.node_type.clone(), child.span);
+            self.link(parent, id, None);
+            self.append_children(id, &child.children);
+        }
+    }
+
+    //Copy a node and everything below it back out into an owned tree
+    pub fn to_node(&self, id: NodeId) -> Node {
+        let mut node = Node::new(self[id].node_type.clone(), self.children(id).map(|child| self.to_node(child)).collect());
+        node.span = self[id].span;
+        node
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    //Number of nodes in the arena, detached ones included
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&NodeData> {
+        self.nodes.get(id.0)
+    }
+
+    //ANCHOR Links
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self[id].parent
+    }
+
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self[id].first_child
+    }
+
+    pub fn last_child(&self, id: NodeId) -> Option<NodeId> {
+        self[id].last_child
+    }
+
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self[id].previous_sibling
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self[id].next_sibling
+    }
+
+    //ANCHOR Traversal
+    pub fn children(&self, id: NodeId) -> Children<'_> {
+        Children { document: self, next: self.first_child(id) }
+    }
+
+    //Parent, grandparent ... up to the root, the node itself isn't included
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_> {
+        Ancestors { document: self, next: self.parent(id) }
+    }
+
+    //The node and everything below it in document order (a pre-order walk)
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_> {
+        Descendants { document: self, root: id, next: Some(id) }
+    }
+
+    //ANCHOR Node Data
+    pub fn node_type(&self, id: NodeId) -> &NodeType {
+        &self[id].node_type
+    }
+
+    pub fn element(&self, id: NodeId) -> Option<&ElementData> {
+        match self[id].node_type {
+            NodeType::Element(ref e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn element_mut(&mut self, id: NodeId) -> Option<&mut ElementData> {
+        match self[id].node_type {
+            NodeType::Element(ref mut e) => Some(e),
+            _ => None,
+        }
+    }
+
+    //Nearest ancestor (or the node itself) that is an element with the given tag name
+    pub fn closest(&self, id: NodeId, tag_name: &str) -> Option<NodeId> {
+        Some(id)
+            .into_iter()
+            .chain(self.ancestors(id))
+            .find(|&node| self.element(node).is_some_and(|e| e.tag_name == tag_name))
+    }
+
+    //ANCHOR Queries
+    //Lookups go through the tree under the root in document order, nodes that were removed aren't found anymore
+
+    //First element with that id
+    pub fn get_element_by_id(&self, element_id: &str) -> Option<NodeId> {
+        self.elements(self.root()).find(|&(_, e)| e.get_id().is_some_and(|id| id == element_id)).map(|(id, _)| id)
+    }
+
+    //Elements under `scope` with that tag name, "*" means every element
+    //Lowercase names find HTML elements whatever case they were written in, SVG names keep their case (foreignObject)
+    pub fn get_elements_by_tag_name(&self, scope: NodeId, tag_name: &str) -> Vec<NodeId> {
+        let lowercase = tag_name.to_ascii_lowercase();
+        self.elements(scope)
+            .filter(|&(id, e)| {
+                id != scope && (tag_name == "*" || e.tag_name == tag_name || (e.is_html() && e.tag_name == lowercase))
+            })
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    //Elements under `scope` that have all of the space separated class names
+    //Split on ASCII whitespace like the class attribute itself, a non-breaking space is part of a class name
+    pub fn get_elements_by_class_name(&self, scope: NodeId, class_names: &str) -> Vec<NodeId> {
+        let wanted: Vec<&str> = class_names.split_ascii_whitespace().collect();
+        if wanted.is_empty() {
+            return Vec::new();
+        }
+        self.elements(scope)
+            .filter(|&(id, e)| {
+                let classes = e.get_classes();
+                id != scope && wanted.iter().all(|class| classes.contains(class))
+            })
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    //First element under `scope` matching the selectors, the same selectors a stylesheet takes ("p", "#main", "li.done, .todo", "ul > li a")
+    pub fn query_selector(&self, scope: NodeId, selectors: &str) -> Result<Option<NodeId>, DomError> {
+        Ok(self.query_selector_all(scope, selectors)?.into_iter().next())
+    }
+
+    //Every element under `scope` matching the selectors, in document order
+    pub fn query_selector_all(&self, scope: NodeId, selectors: &str) -> Result<Vec<NodeId>, DomError> {
+        let mut parser = CssParser::new(selectors);
+        let selectors = parser.parse_selector_list();
+        if selectors.is_empty() || !parser.errors().is_empty() {
+            return Err(DomError::Syntax);
+        }
+        Ok(self
+            .elements(scope)
+            .filter(|&(id, _)| id != scope && selectors.iter().any(|selector| selector_matches(ElementRef { document: self, id }, selector)))
+            .map(|(id, _)| id)
+            .collect())
+    }
+
+    //The node and every element below it, with their data
+    fn elements(&self, scope: NodeId) -> impl Iterator<Item = (NodeId, &ElementData)> + '_ {
+        self.descendants(scope).filter_map(move |id| self.element(id).map(|e| (id, e)))
+    }
+
+    //ANCHOR Text
+    //Like node.textContent: the data of a text or comment node, all the text below an element
+    //exactly as the parser kept it, None for the document and a doctype (null in the DOM)
+    pub fn text_content(&self, id: NodeId) -> Option<String> {
+        match self[id].node_type {
+            NodeType::Text(ref data) | NodeType::Comment(ref data) => Some(data.clone()),
+            NodeType::Doctype(_) | NodeType::Document(_) => None,
+            NodeType::Element(_) => Some(
+                self.descendants(id)
+                    .filter_map(|node| match self[node].node_type {
+                        NodeType::Text(ref data) => Some(data.as_str()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    //Something like element.innerText without a layout: the text the way it reads on the page
+    //Whitespace is collapsed except inside <pre> and friends, block elements go on their own lines,
+    //<p> is a paragraph apart, <br> breaks the line and table cells are separated by tabs
+    //script, style and the rest of what isn't shown are left out
+    pub fn inner_text(&self, id: NodeId) -> String {
+        let mut items = Vec::new();
+        let preformatted = self.ancestors(id).chain(Some(id)).any(|node| self.element(node).is_some_and(is_preformatted));
+        self.collect_inner_text(id, preformatted, &mut items);
+        trim_trailing_space(&mut items);
+
+        //Turn the items into text, a row of breaks counts as the biggest of them and breaks at the ends are dropped
+        let mut text = String::new();
+        let mut breaks = 0;
+        for item in items {
+            match item {
+                TextItem::Break(count) => breaks = breaks.max(count),
+                TextItem::Text(t) | TextItem::Pre(t) => {
+                    if t.is_empty() {
+                        continue;
+                    }
+                    if !text.is_empty() {
+                        text.push_str(&"\n".repeat(breaks));
+                    }
+                    breaks = 0;
+                    text.push_str(&t);
+                }
+            }
+        }
+        text
+    }
+
+    fn collect_inner_text(&self, id: NodeId, preformatted: bool, items: &mut Vec<TextItem>) {
+        let element = match self[id].node_type {
+            NodeType::Text(ref data) => {
+                if preformatted {
+                    items.push(TextItem::Pre(data.clone()));
+                } else {
+                    //a space is only kept between words, not at the start of a line or next to another space
+                    let mut text = collapse_whitespace(data, WhiteSpace::Normal);
+                    let after_space = match items.last() {
+                        Some(TextItem::Text(t)) => t.ends_with(' '),
+                        Some(TextItem::Pre(t)) => t.ends_with('\n'),
+                        _ => true,
+                    };
+                    if after_space && text.starts_with(' ') {
+                        text.remove(0);
+                    }
+                    if !text.is_empty() {
+                        items.push(TextItem::Text(text));
+                    }
+                }
+                return;
+            }
+            NodeType::Element(ref e) => e,
+            NodeType::Document(_) => {
+                for child in self.children(id) {
+                    self.collect_inner_text(child, preformatted, items);
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        if element.is_html() && is_one_of(&element.tag_name, HIDDEN_ELEMENTS) {
+            return;
+        }
+        if element.is_html() && element.tag_name == "br" {
+            trim_trailing_space(items);
+            items.push(TextItem::Pre("\n".to_string()));
+            return;
+        }
+
+        let required_breaks = match element.tag_name.as_str() {
+            _ if !element.is_html() => 0,
+            "p" => 2,
+            name if is_one_of(name, BLOCK_ELEMENTS) => 1,
+            _ => 0,
+        };
+        if required_breaks > 0 {
+            trim_trailing_space(items);
+            items.push(TextItem::Break(required_breaks));
+        }
+        for child in self.children(id) {
+            self.collect_inner_text(child, preformatted || is_preformatted(element), items);
+        }
+        if required_breaks > 0 {
+            trim_trailing_space(items);
+            items.push(TextItem::Break(required_breaks));
+        }
+        //cells in a row are separated by a tab, the row's own line break comes after the last one
+        if element.is_html() && (element.tag_name == "td" || element.tag_name == "th") && self.next_sibling(id).is_some() {
+            trim_trailing_space(items);
+            items.push(TextItem::Pre("\t".to_string()));
+        }
+    }
+
+    //ANCHOR Creating Nodes
+    //New nodes aren't in the tree yet, insert them with append_child or insert_before
+    pub fn create_element(&mut self, tag_name: &str) -> NodeId {
+        self.push(NodeType::Element(ElementData::new(tag_name.to_string(), AttrMap::new())), Span::default())
+    }
+
+    pub fn create_text_node(&mut self, text: &str) -> NodeId {
+        self.push(NodeType::Text(text.to_string()), Span::default())
+    }
+
+    pub fn create_comment(&mut self, text: &str) -> NodeId {
+        self.push(NodeType::Comment(text.to_string()), Span::default())
+    }
+
+    //Any other kind of node, an element with attributes, a doctype ...
+    pub fn create_node(&mut self, node_type: NodeType) -> NodeId {
+        self.push(node_type, Span::default())
+    }
+
+    fn push(&mut self, node_type: NodeType, span: Span) -> NodeId {
+        self.nodes.push(NodeData::new(node_type, span));
+        NodeId(self.nodes.len() - 1)
+    }
+
+    //ANCHOR Mutation
+    //Add the child at the end of the parent, if it was somewhere else in the tree it moves
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) -> Result<NodeId, DomError> {
+        self.insert_before(parent, child, None)
+    }
+
+    //Add the child right before `reference`, or at the end when there is no reference
+    pub fn insert_before(&mut self, parent: NodeId, child: NodeId, reference: Option<NodeId>) -> Result<NodeId, DomError> {
+        self.check_insert(parent, child, reference, None)?;
+        if let Some(reference) = reference {
+            if self.parent(reference) != Some(parent) {
+                return Err(DomError::NotFound);
+            }
+            if reference == child {
+                return Ok(child); //already right where it should go
+            }
+        }
+        self.remove_from_parent(child);
+        self.link(parent, child, reference);
+        let record = MutationRecord::child_list(parent, vec![child], Vec::new(), self.previous_sibling(child), reference);
+        self.queue_record(record);
+        Ok(child)
+    }
+
+    //Take the child out of the tree, it keeps its own children and can be inserted again later
+    pub fn remove_child(&mut self, parent: NodeId, child: NodeId) -> Result<NodeId, DomError> {
+        if self.parent(child) != Some(parent) {
+            return Err(DomError::NotFound);
+        }
+        self.remove_from_parent(child);
+        Ok(child)
+    }
+
+    //Put `new_child` where `old_child` is, returns the old child which is now out of the tree
+    pub fn replace_child(&mut self, parent: NodeId, new_child: NodeId, old_child: NodeId) -> Result<NodeId, DomError> {
+        if self.parent(old_child) != Some(parent) {
+            return Err(DomError::NotFound);
+        }
+        self.check_insert(parent, new_child, Some(old_child), Some(old_child))?;
+        if new_child == old_child {
+            return Ok(old_child);
+        }
+        let reference = match self.next_sibling(old_child) {
+            Some(next) if next == new_child => self.next_sibling(new_child), //new_child was right after it
+            next => next,
+        };
+        self.remove_from_parent(new_child);
+        let previous = self.previous_sibling(old_child);
+        self.unlink(old_child);
+        self.link(parent, new_child, reference);
+        //one record for both halves, like the browser does
+        let record = MutationRecord::child_list(parent, vec![new_child], vec![old_child], previous, reference);
+        self.queue_record(record);
+        Ok(old_child)
+    }
+
+    //Like element.setAttribute, does nothing if the node isn't an element
+    pub fn set_attribute(&mut self, id: NodeId, name: &str, value: &str) {
+        let old_value = match self.element_mut(id) {
+            Some(element) => {
+                let old_value = element.get_attribute(name).cloned();
+                element.set_attribute(name, value);
+                old_value
+            }
+            None => return,
+        };
+        self.queue_record(MutationRecord::attributes(id, name, old_value));
+    }
+
+    //Like element.removeAttribute, returns the old value
+    pub fn remove_attribute(&mut self, id: NodeId, name: &str) -> Option<String> {
+        let old_value = self.element_mut(id)?.remove_attribute(name)?;
+        self.queue_record(MutationRecord::attributes(id, name, Some(old_value.clone())));
+        Some(old_value)
+    }
+
+    //element.classList.add / remove / toggle, the class attribute is rewritten through set_attribute
+    //so observers see the change like any other attribute change
+    pub fn add_class(&mut self, id: NodeId, class: &str) {
+        self.update_class(id, |element| element.add_class(class));
+    }
+
+    pub fn remove_class(&mut self, id: NodeId, class: &str) {
+        //no class attribute means nothing to remove, and nothing gets recorded
+        if self.element(id).is_some_and(|e| e.has_attribute("class")) {
+            self.update_class(id, |element| element.remove_class(class));
+        }
+    }
+
+    pub fn toggle_class(&mut self, id: NodeId, class: &str) -> bool {
+        let mut on = false;
+        self.update_class(id, |element| on = element.toggle_class(class));
+        on
+    }
+
+    //The mouse moved over the element, it got focus, a checkbox was clicked ... what :hover, :focus and so on match
+    //It's not a change to the Dom itself so observers don't hear about it, style has to be redone for the element
+    pub fn set_element_state(&mut self, id: NodeId, state: ElementState, on: bool) {
+        if let Some(element) = self.element_mut(id) {
+            element.set_state(state, on);
+        }
+    }
+
+    //Like node.textContent = text: text and comments get their data replaced, elements lose all their children
+    //and get a single text node instead (no text node at all for ""), documents and doctypes are left alone
+    pub fn set_text_content(&mut self, id: NodeId, text: &str) {
+        match self[id].node_type {
+            NodeType::Text(ref mut data) | NodeType::Comment(ref mut data) => {
+                let old_value = std::mem::replace(data, text.to_string());
+                self.queue_record(MutationRecord::character_data(id, old_value));
+                return;
+            }
+            NodeType::Doctype(_) | NodeType::Document(_) => return,
+            NodeType::Element(_) => {}
+        }
+
+        let removed: Vec<NodeId> = self.children(id).collect();
+        for &child in removed.iter() {
+            self.unlink(child);
+        }
+        let mut added = Vec::new();
+        if !text.is_empty() {
+            let text_node = self.create_text_node(text);
+            self.link(id, text_node, None);
+            added.push(text_node);
+        }
+        if !removed.is_empty() || !added.is_empty() {
+            self.queue_record(MutationRecord::child_list(id, added, removed, None, None));
+        }
+    }
+
+    //Like node.normalize(): every text node below `id` swallows the text nodes right after it and empty text nodes
+    //are removed, the swallowed ones are removed from the tree like remove_child would
+    pub fn normalize(&mut self, id: NodeId) {
+        let text_nodes: Vec<NodeId> = self.descendants(id).filter(|&node| node != id && self.is_text(node)).collect();
+        for node in text_nodes {
+            let parent = match self.parent(node) {
+                Some(parent) => parent,
+                None => continue, //already merged into the text node before it
+            };
+            if self.text_content(node).is_none_or(|data| data.is_empty()) {
+                self.remove_from_parent(node);
+                continue;
+            }
+
+            let mut merged = Vec::new();
+            let mut next = self.next_sibling(node);
+            while let Some(sibling) = next.filter(|&sibling| self.is_text(sibling)) {
+                merged.push(sibling);
+                next = self.next_sibling(sibling);
+            }
+            if merged.is_empty() {
+                continue;
+            }
+            let mut data = self.text_content(node).unwrap_or_default();
+            for &sibling in merged.iter() {
+                data.push_str(&self.text_content(sibling).unwrap_or_default());
+            }
+            self.set_text_content(node, &data);
+            for sibling in merged {
+                let _ = self.remove_child(parent, sibling);
+            }
+        }
+    }
+
+    fn is_text(&self, id: NodeId) -> bool {
+        matches!(self[id].node_type, NodeType::Text(_))
+    }
+
+    //ANCHOR Observers
+    //Start watching `target`, the callback gets the records in batches whenever notify_observers runs
+    pub fn observe<F>(&mut self, target: NodeId, options: ObserveOptions, callback: F) -> ObserverId
+    where
+        F: FnMut(&[MutationRecord]) + 'static,
+    {
+        self.observers.add(target, options, Box::new(callback))
+    }
+
+    pub fn disconnect(&mut self, observer: ObserverId) {
+        self.observers.remove(observer);
+    }
+
+    //The records queued for the observer so far, they won't be delivered to its callback anymore
+    pub fn take_records(&mut self, observer: ObserverId) -> Vec<MutationRecord> {
+        self.observers.take_records(observer)
+    }
+
+    //Deliver every queued record, call it once a batch of changes is done (before restyling, for example)
+    pub fn notify_observers(&mut self) {
+        self.observers.notify();
+    }
+
+    fn queue_record(&mut self, record: MutationRecord) {
+        if self.observers.is_empty() {
+            return;
+        }
+        let mut chain = vec![record.target];
+        chain.extend(self.ancestors(record.target));
+        self.observers.queue(&record, &chain);
+    }
+
+    //Take a node out of its parent and tell the observers about it, nodes that aren't in the tree are left alone
+    fn remove_from_parent(&mut self, child: NodeId) {
+        if let Some(parent) = self.parent(child) {
+            let record = MutationRecord::child_list(parent, Vec::new(), vec![child], self.previous_sibling(child), self.next_sibling(child));
+            self.unlink(child);
+            self.queue_record(record);
+        }
+    }
+
+    //Run a classList change on the element and record the class attribute's old value
+    fn update_class<F: FnOnce(&mut ElementData)>(&mut self, id: NodeId, update: F) {
+        let old_value = match self.element_mut(id) {
+            Some(element) => {
+                let old_value = element.get_attribute("class").cloned();
+                update(element);
+                old_value
+            }
+            None => return,
+        };
+        self.queue_record(MutationRecord::attributes(id, "class", old_value));
+    }
+
+    //The checks of "ensure pre-insertion validity" that can fail in our tree
+    //`reference` is the child it goes in front of (None for the end), `replaced` the child replace_child takes out
+    fn check_insert(&self, parent: NodeId, child: NodeId, reference: Option<NodeId>, replaced: Option<NodeId>) -> Result<(), DomError> {
+        let parent_can_have_children = matches!(self[parent].node_type, NodeType::Element(_) | NodeType::Document(_));
+        let child_is_document = matches!(self[child].node_type, NodeType::Document(_));
+        //a node can't go inside itself or one of its own descendants
+        let child_contains_parent = parent == child || self.ancestors(parent).any(|ancestor| ancestor == child);
+
+        if !parent_can_have_children || child_is_document || child_contains_parent {
+            return Err(DomError::HierarchyRequest);
+        }
+
+        //right in the document there's no text, at most one doctype and one element, and the doctype comes first
+        let in_document = matches!(self[parent].node_type, NodeType::Document(_));
+        let children: Vec<NodeId> = self.children(parent).collect();
+        let at = reference.and_then(|reference| children.iter().position(|&c| c == reference)).unwrap_or(children.len());
+        let (before, after) = children.split_at(at);
+        let is_element = |c: &NodeId| Some(*c) != replaced && self.element(*c).is_some();
+        let is_doctype = |c: &NodeId| Some(*c) != replaced && matches!(self[*c].node_type, NodeType::Doctype(_));
+
+        let fits = match self[child].node_type {
+            NodeType::Text(_) => !in_document,
+            NodeType::Doctype(_) => in_document && !children.iter().any(is_doctype) && !before.iter().any(is_element),
+            NodeType::Element(_) if in_document => !children.iter().any(is_element) && !after.iter().any(is_doctype),
+            _ => true,
+        };
+        if !fits {
+            return Err(DomError::HierarchyRequest);
+        }
+        Ok(())
+    }
+
+    //Hook up the links for a child that isn't in the tree, before `reference` or at the end
+    fn link(&mut self, parent: NodeId, child: NodeId, reference: Option<NodeId>) {
+        let previous = match reference {
+            Some(reference) => self[reference].previous_sibling,
+            None => self[parent].last_child,
+        };
+        self[child].parent = Some(parent);
+        self[child].previous_sibling = previous;
+        self[child].next_sibling = reference;
+        match previous {
+            Some(previous) => self[previous].next_sibling = Some(child),
+            None => self[parent].first_child = Some(child),
+        }
+        match reference {
+            Some(reference) => self[reference].previous_sibling = Some(child),
+            None => self[parent].last_child = Some(child),
+        }
+    }
+
+    //Take a node out of its parent's child list, its own subtree stays attached to it
+    fn unlink(&mut self, child: NodeId) {
+        let parent = match self[child].parent.take() {
+            Some(parent) => parent,
+            None => return,
+        };
+        let previous = self[child].previous_sibling.take();
+        let next = self[child].next_sibling.take();
+        match previous {
+            Some(previous) => self[previous].next_sibling = next,
+            None => self[parent].first_child = next,
+        }
+        match next {
+            Some(next) => self[next].previous_sibling = previous,
+            None => self[parent].last_child = previous,
+        }
+    }
+}
+
+//document[id] for the node data, panics on an id from another document like a Vec would
+impl std::ops::Index<NodeId> for Document {
+    type Output = NodeData;
+
+    fn index(&self, id: NodeId) -> &NodeData {
+        &self.nodes[id.0]
+    }
+}
+impl std::ops::IndexMut<NodeId> for Document {
+    fn index_mut(&mut self, id: NodeId) -> &mut NodeData {
+        &mut self.nodes[id.0]
+    }
+}
+
+//An element of the Document for the selector matcher, combinators and pseudo-classes look around it in the tree
+//(up to ancestors, along siblings and down into children for :has and :empty)
+#[derive(Clone, Copy)]
+pub(crate) struct ElementRef<'a> {
+    pub(crate) document: &'a Document,
+    pub(crate) id: NodeId,
+}
+
+impl<'a> ElementRef<'a> {
+    //None if the node isn't an element
+    pub(crate) fn new(document: &'a Document, id: NodeId) -> Option<ElementRef<'a>> {
+        document.element(id).map(|_| ElementRef { document, id })
+    }
+
+    pub(crate) fn data(&self) -> &'a ElementData {
+        self.document.element(self.id).expect("only elements are matched against selectors")
+    }
+
+    pub(crate) fn parent_element(&self) -> Option<ElementRef<'a>> {
+        ElementRef::new(self.document, self.document.parent(self.id)?)
+    }
+
+    //The top element, right below the #document (or the arena's root in a tree without one)
+    //A detached element has no parent either, but it isn't the root of anything
+    pub(crate) fn is_root(&self) -> bool {
+        match self.document.parent(self.id) {
+            Some(parent) => matches!(self.document[parent].node_type, NodeType::Document(_)),
+            None => self.id == self.document.root(),
+        }
+    }
+
+    pub(crate) fn previous_element_sibling(&self) -> Option<ElementRef<'a>> {
+        let mut sibling = self.document.previous_sibling(self.id);
+        while let Some(id) = sibling {
+            if let Some(element) = ElementRef::new(self.document, id) {
+                return Some(element);
+            }
+            sibling = self.document.previous_sibling(id);
+        }
+        None
+    }
+
+    pub(crate) fn next_element_sibling(&self) -> Option<ElementRef<'a>> {
+        let mut sibling = self.document.next_sibling(self.id);
+        while let Some(id) = sibling {
+            if let Some(element) = ElementRef::new(self.document, id) {
+                return Some(element);
+            }
+            sibling = self.document.next_sibling(id);
+        }
+        None
+    }
+
+    //Every element below this one, not the element itself
+    pub(crate) fn descendant_elements(&self) -> impl Iterator<Item = ElementRef<'a>> + 'a {
+        let document = self.document;
+        document.descendants(self.id).skip(1).filter_map(move |id| ElementRef::new(document, id))
+    }
+
+    //Nothing but comments inside, for :empty
+    pub(crate) fn is_empty(&self) -> bool {
+        self.document.children(self.id).all(|child| match self.document[child].node_type {
+            NodeType::Element(_) => false,
+            NodeType::Text(ref t) => t.is_empty(),
+            _ => true,
+        })
+    }
+}
+
+//Pieces of inner_text, collapsed text, text that is shown as it is or a number of line breaks that have to be there
+enum TextItem {
+    Text(String),
+    Pre(String),
+    Break(usize),
+}
+
+//Elements whose text never shows up on the page
+const HIDDEN_ELEMENTS: &[&str] = &["head", "script", "style", "template", "noscript", "title", "meta", "link", "base"];
+
+//Elements that are display: block (or a table part) in the browser's own stylesheet
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "body", "caption", "center", "dd", "details", "dialog", "dir", "div",
+    "dl", "dt", "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header",
+    "hgroup", "hr", "html", "legend", "li", "listing", "main", "menu", "nav", "ol", "plaintext", "pre", "section",
+    "summary", "table", "tr", "ul", "xmp",
+];
+
+fn is_one_of(name: &str, names: &[&str]) -> bool {
+    names.contains(&name)
+}
+
+//Text inside these keeps its whitespace, white-space: pre in the browser's own stylesheet
+fn is_preformatted(element: &ElementData) -> bool {
+    element.is_html() && is_one_of(&element.tag_name, &["pre", "textarea", "listing", "plaintext", "xmp"])
+}
+
+//A space right before a line break isn't shown
+fn trim_trailing_space(items: &mut [TextItem]) {
+    if let Some(TextItem::Text(ref mut t)) = items.last_mut() {
+        if t.ends_with(' ') {
+            t.pop();
+        }
+    }
+}
+
+// NOTE 3- Iterators
+
+pub struct Children<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        self.next = self.document.next_sibling(current);
+        Some(current)
+    }
+}
+
+pub struct Ancestors<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        self.next = self.document.parent(current);
+        Some(current)
+    }
+}
+
+pub struct Descendants<'a> {
+    document: &'a Document,
+    root: NodeId,
+    next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+
+        //go down first, then right, then back up until some ancestor has a next sibling (never above the root)
+        self.next = self.document.first_child(current);
+        let mut node = current;
+        while self.next.is_none() && node != self.root {
+            self.next = self.document.next_sibling(node);
+            node = match self.document.parent(node) {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+        Some(current)
+    }
+}
@@ -3,68 +3,420 @@
  * Dom itself is a tree of nodes
  */
 
-use std::collections::{HashMap, HashSet}; 
-//HashMap: like dict HashMap<String, String>, HashSet: like set
+use std::collections::HashSet; //HashSet: like set
+use std::iter::FromIterator;
 use std::fmt;
 
+use crate::source::Span;
+
 #[derive(PartialEq, Eq, Clone)]
 pub enum NodeType {     //Node types that we gonna deal with
     Text(String),   //Node Text with String inside of it
     Element(ElementData), //... Element with ElementData inside of it
     Comment(String),      //... comment with String inside of it
+    Doctype(DoctypeData), //<!DOCTYPE html> at the top of the page
+    Document(QuirksMode), //Root of a parsed page, remembers which quirks mode the doctype asked for
+}
+
+//Missing identifiers are stored as empty strings, like the DOM does
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct DoctypeData {
+    pub name: String,
+    pub public_id: String,
+    pub system_id: String,
+}
+
+//Old pages without a proper doctype are rendered in quirks mode, to behave like the browsers they were written for
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum QuirksMode {
+    NoQuirks,
+    LimitedQuirks,
+    Quirks,
 }
 
 #[derive(PartialEq, Eq, Clone)]
 pub struct Node {
     pub children: Vec<Node>,    //0 or more connected to this node, like tree pattern
     pub node_type: NodeType,    
+    pub span: Span,             //where the node came from in the HTML source
 }
 
-#[derive(PartialEq, Eq, Clone)]
+//Which language the element belongs to, everything is HTML except what's inside <svg> and <math>
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+}
+
+#[derive(Clone)]
 pub struct ElementData {
-    pub tag_name: String,       //div
+    pub tag_name: String,       //div, SVG names keep their case (foreignObject, linearGradient)
+    pub namespace: Namespace,
     attributes: AttrMap,    //Any number of attributes
+    state: ElementState,    //hovered, focused ... not in the markup, the embedder sets it
 /* If u have a div, the div is the tag name 
  * and then it could have a class or an id and those would be an attributes and we can store them at AttrMap
  */
 } 
 
+//What's going on with an element right now that the markup can't say, the mouse is over it, it has focus ...
+//The embedder turns these on and off as the user interacts, :hover, :focus, :active, :checked and :disabled match on them
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug, Hash)]
+pub struct ElementState(u8);
+
 impl ElementData {
     pub fn new(tag_name: String, attributes: AttrMap) -> ElementData {
+        ElementData::new_in(Namespace::Html, tag_name, attributes)
+    }
+
+    //Same as new but for an SVG or MathML element
+    pub fn new_in(namespace: Namespace, tag_name: String, attributes: AttrMap) -> ElementData {
+        let state = ElementState::initial(namespace, &tag_name, &attributes);
         ElementData {
             tag_name,
+            namespace,
             attributes,
+            state,
         }
     }
 
+    pub fn is_html(&self) -> bool {
+        self.namespace == Namespace::Html
+    }
+
     ///Get Attributes Id
     pub fn get_id(&self) -> Option<&String> {
         self.attributes.get("id")
     }
 
+    ///Get all Attributes
+    pub fn attributes(&self) -> &AttrMap {
+        &self.attributes
+    }
+
+    ///Get one Attribute
+    pub fn get_attribute(&self, name: &str) -> Option<&String> {
+        self.attributes.get(name)
+    }
+
+    ///Set an Attribute, replacing the old value if there was one
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        self.attributes.insert(name.to_string(), value.to_string());
+        self.update_state(name);
+    }
+
+    ///Remove an Attribute, returns its old value
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        let old_value = self.attributes.remove(name);
+        self.update_state(name);
+        old_value
+    }
+
     ///Get Attributes Classes
     pub fn get_classes(&self) -> HashSet<&str> {
-        //Match on the output, if found it return it as HashSet of str
-        match self.attributes.get("class") {
-            Some(s) => s.split(' ').collect(),
-            None => HashSet::new(),
+        //Same tokens as class_list, just as a set for quick lookups
+        self.get_token_list("class").into_iter().collect()
+    }
+
+    //ANCHOR Typed Attributes
+    //Boolean attributes like disabled or hidden are on when they're there at all, whatever their value is
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attributes.contains_key(name)
+    }
+
+    pub fn set_boolean_attribute(&mut self, name: &str, on: bool) {
+        if on {
+            if !self.has_attribute(name) {
+                self.set_attribute(name, "");
+            }
+        } else {
+            self.remove_attribute(name);
+        }
+    }
+
+    //Integer attributes like colspan and tabindex, read with the HTML "rules for parsing integers"
+    //so " 3px" is 3, but "px" or a number that doesn't fit is None
+    pub fn get_integer_attribute(&self, name: &str) -> Option<i32> {
+        parse_integer(self.attributes.get(name)?)
+    }
+
+    //Token list attributes like class and rel, split on ASCII whitespace, duplicates dropped, first one keeps its place
+    pub fn get_token_list(&self, name: &str) -> Vec<&str> {
+        let mut tokens: Vec<&str> = Vec::new();
+        if let Some(value) = self.attributes.get(name) {
+            for token in value.split_ascii_whitespace() {
+                if !tokens.contains(&token) {
+                    tokens.push(token);
+                }
+            }
+        }
+        tokens
+    }
+
+    pub fn contains_token(&self, name: &str, token: &str) -> bool {
+        self.get_token_list(name).contains(&token)
+    }
+
+    //Like DOMTokenList.add, the attribute is written back as the tokens joined by single spaces
+    pub fn add_token(&mut self, name: &str, token: &str) {
+        let mut tokens = self.get_token_list(name);
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+        let value = tokens.join(" ");
+        self.set_attribute(name, &value);
+    }
+
+    //Like DOMTokenList.remove, the attribute stays even when the last token is gone (class="")
+    pub fn remove_token(&mut self, name: &str, token: &str) {
+        if !self.has_attribute(name) {
+            return;
+        }
+        let value = self.get_token_list(name).into_iter().filter(|&t| t != token).collect::<Vec<_>>().join(" ");
+        self.set_attribute(name, &value);
+    }
+
+    //Like DOMTokenList.toggle, returns whether the token is there now
+    pub fn toggle_token(&mut self, name: &str, token: &str) -> bool {
+        if self.contains_token(name, token) {
+            self.remove_token(name, token);
+            false
+        } else {
+            self.add_token(name, token);
+            true
+        }
+    }
+
+    //ANCHOR State
+    pub fn state(&self) -> ElementState {
+        self.state
+    }
+
+    pub fn has_state(&self, state: ElementState) -> bool {
+        self.state.contains(state)
+    }
+
+    //Turn one or more states on or off, like when the mouse moves over the element or it gets focus
+    pub fn set_state(&mut self, state: ElementState, on: bool) {
+        self.state.set(state, on);
+    }
+
+    //The elements that can be disabled, :enabled is the ones of these that aren't
+    pub fn is_form_control(&self) -> bool {
+        self.is_html() && is_form_control(&self.tag_name)
+    }
+
+    //checked, selected and disabled were just set or removed, :checked and :disabled follow them again
+    fn update_state(&mut self, name: &str) {
+        if !matches!(name, "checked" | "selected" | "disabled") {
+            return;
+        }
+        let from_attributes = ElementState::initial(self.namespace, &self.tag_name, &self.attributes);
+        for &state in [ElementState::CHECKED, ElementState::DISABLED].iter() {
+            self.state.set(state, from_attributes.contains(state));
+        }
+    }
+
+    //ANCHOR Class List
+    //element.classList, the token list API for the class attribute
+    pub fn class_list(&self) -> Vec<&str> {
+        self.get_token_list("class")
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.contains_token("class", class)
+    }
+
+    pub fn add_class(&mut self, class: &str) {
+        self.add_token("class", class);
+    }
+
+    pub fn remove_class(&mut self, class: &str) {
+        self.remove_token("class", class);
+    }
+
+    pub fn toggle_class(&mut self, class: &str) -> bool {
+        self.toggle_token("class", class)
+    }
+}
+
+
+impl ElementState {
+    pub const HOVER: ElementState = ElementState(1);
+    pub const FOCUS: ElementState = ElementState(1 << 1);
+    pub const ACTIVE: ElementState = ElementState(1 << 2); //being clicked
+    pub const CHECKED: ElementState = ElementState(1 << 3);
+    pub const DISABLED: ElementState = ElementState(1 << 4);
+
+    pub fn empty() -> ElementState {
+        ElementState(0)
+    }
+
+    //All of the states in `other` are on
+    pub fn contains(self, other: ElementState) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn set(&mut self, other: ElementState, on: bool) {
+        if on {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+
+    //What a new element starts with, checkboxes with checked, selected options and disabled form controls
+    //Setting or removing one of those attributes later puts the state back in line with them, in between the
+    //embedder can change it itself (a click on a checkbox)
+    fn initial(namespace: Namespace, tag_name: &str, attributes: &AttrMap) -> ElementState {
+        let mut state = ElementState::empty();
+        if namespace != Namespace::Html {
+            return state;
+        }
+        let checked = match tag_name {
+            "input" => attributes.contains_key("checked"),
+            "option" => attributes.contains_key("selected"),
+            _ => false,
+        };
+        state.set(ElementState::CHECKED, checked);
+        state.set(ElementState::DISABLED, is_form_control(tag_name) && attributes.contains_key("disabled"));
+        state
+    }
+}
+
+//Elements are the same when their markup is, the state (hover, focus ...) isn't part of it
+impl PartialEq for ElementData {
+    fn eq(&self, other: &ElementData) -> bool {
+        self.tag_name == other.tag_name && self.namespace == other.namespace && self.attributes == other.attributes
+    }
+}
+
+impl Eq for ElementData {}
+
+//HOVER | FOCUS, both states at once
+impl std::ops::BitOr for ElementState {
+    type Output = ElementState;
+
+    fn bitor(self, other: ElementState) -> ElementState {
+        ElementState(self.0 | other.0)
+    }
+}
+
+impl Namespace {
+    //The namespace URL, like element.namespaceURI
+    pub fn url(&self) -> &'static str {
+        match *self {
+            Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
         }
     }
 }
 
+impl DoctypeData {
+    pub fn new(name: String, public_id: String, system_id: String) -> DoctypeData {
+        DoctypeData {
+            name,
+            public_id,
+            system_id,
+        }
+    }
+}
 
-pub type AttrMap = HashMap<String, String>; //typealias HashMap, its like Dict
-//type Int = i32; //Other example
+//Attributes in the order they were written, a Vec instead of a HashMap so debug and serializer output
+//is the same on every run, elements only have a handful of attributes so looking them up one by one is fine
+#[derive(PartialEq, Eq, Clone, Default)]
+pub struct AttrMap {
+    attributes: Vec<(String, String)>,
+}
+
+impl AttrMap {
+    pub fn new() -> AttrMap {
+        AttrMap { attributes: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.attributes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.attributes.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    //Set a value, an attribute that's already there keeps its place and gives back its old value
+    pub fn insert(&mut self, name: String, value: String) -> Option<String> {
+        match self.attributes.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, old)) => Some(std::mem::replace(old, value)),
+            None => {
+                self.attributes.push((name, value));
+                None
+            }
+        }
+    }
+
+    //Only add it if it isn't there yet, a duplicate attribute in the markup is ignored and the first one wins
+    pub fn insert_first(&mut self, name: String, value: String) -> bool {
+        if self.contains_key(&name) {
+            return false;
+        }
+        self.attributes.push((name, value));
+        true
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let index = self.attributes.iter().position(|(n, _)| n == name)?;
+        Some(self.attributes.remove(index).1)
+    }
+
+    //In source order
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.attributes.iter().map(|(n, v)| (n, v))
+    }
+}
+
+//Collecting name/value pairs works like the parser, first one wins
+impl FromIterator<(String, String)> for AttrMap {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> AttrMap {
+        let mut attributes = AttrMap::new();
+        for (name, value) in iter {
+            attributes.insert_first(name, value);
+        }
+        attributes
+    }
+}
 
 impl Node {
     pub fn new(node_type: NodeType, children: Vec<Node>) -> Node {
         Node {
             node_type,
             children,
+            span: Span::default(),
         }
     }
 }
 
+fn is_form_control(tag_name: &str) -> bool {
+    matches!(tag_name, "button" | "input" | "select" | "textarea" | "optgroup" | "option" | "fieldset")
+}
+
+//Void elements never have children or an end tag, like <br>, <img> and <input>
+pub fn is_void_element(tag_name: &str) -> bool {
+    matches!(
+        tag_name,
+        "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" | "link" | "meta" | "param"
+            | "source" | "track" | "wbr" | "basefont" | "bgsound" | "frame" | "keygen"
+    )
+}
+
 //Implement Debug for Node so we can debug & easily see it inside browser or terminal
 impl fmt::Debug for Node {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -81,26 +433,41 @@ impl fmt::Debug for NodeType {
 
             NodeType::Element(ref e) => write!(f, "{:?}", e),
             //With Element writing with Debug flag
+
+            NodeType::Doctype(ref d) => write!(f, "<!DOCTYPE {}>", d.name),
+            NodeType::Document(mode) => write!(f, "#document ({:?})", mode),
         }
     }
 }
 
+impl fmt::Debug for AttrMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
 // ALSO Implement Debug for ElementData 
 impl fmt::Debug for ElementData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         //Empty String
         let mut attributes_string = String::new();
 
-        //Iterate in HashMap & add every attributes with its value
+        //Iterate in AttrMap & add every attributes with its value, in source order
         for (attr, value) in self.attributes.iter() {
             attributes_string.push_str(&format!(" {}=\"{}\"", attr, value));
         }
-        write!(f, "<{},{}>", self.tag_name, attributes_string)
+        //foreign elements get a prefix so <svg title> and <title> can be told apart
+        let prefix = match self.namespace {
+            Namespace::Html => "",
+            Namespace::Svg => "svg ",
+            Namespace::MathMl => "math ",
+        };
+        write!(f, "<{}{},{}>", prefix, self.tag_name, attributes_string)
     }
 }
 
 //Print Node and its descendants with indentation, so it be more neater for us
-fn pretty_print(n: &Node, indent_size: usize) {
+pub fn pretty_print(n: &Node, indent_size: usize) {
     //Iterate from 0 to our indent size, map closure & return space, into vector of strings
     //Will give us approprite indent size for everything we need
     let indent = (0..indent_size).map(|_| " ").collect::<String>();
@@ -110,6 +477,7 @@ fn pretty_print(n: &Node, indent_size: usize) {
         NodeType::Element(ref e) => println!("{}{:?}", indent, e),
         NodeType::Text(ref t) => println!("{}{}", indent, t),
         NodeType::Comment(ref c) => println!("{}<!--{}-->", indent, c),
+        NodeType::Doctype(_) | NodeType::Document(_) => println!("{}{:?}", indent, n.node_type),
     }
 
     //Iterate through all of child nodes in our node
@@ -125,4 +493,31 @@ fn pretty_print(n: &Node, indent_size: usize) {
         _ => {},
     }
 
-}
\ No newline at end of file
+}
+
+//"Rules for parsing integers": skip leading whitespace, an optional sign, then as many digits as there are
+//anything after the digits is ignored, no digits at all or a number too big for an i32 gives None
+fn parse_integer(value: &str) -> Option<i32> {
+    let value = value.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let (negative, digits) = match value.as_bytes().first() {
+        Some(b'-') => (true, &value[1..]),
+        Some(b'+') => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let digits = &digits[..digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len())];
+    if digits.is_empty() {
+        return None;
+    }
+    let mut number: i64 = 0;
+    for d in digits.bytes() {
+        number = number * 10 + (d - b'0') as i64;
+        if number > i32::MAX as i64 + 1 {
+            return None;
+        }
+    }
+    let number = if negative { -number } else { number };
+    if number < i32::MIN as i64 || number > i32::MAX as i64 {
+        return None;
+    }
+    Some(number as i32)
+}
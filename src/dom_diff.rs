@@ -0,0 +1,304 @@
+/* Structural diff of two Dom trees
+ * Give it the tree a page generator made last time and the one it makes now, and it says what changed between them:
+ * nodes that were inserted, removed or moved around, attributes that were set or removed, text that changed and a
+ * doctype or quirks mode that's different
+ * Children are lined up with a longest common subsequence, nodes only pair up when they are the same kind of node
+ * (same tag and id for elements), and identical nodes are preferred so the edit list stays as short as possible
+ * A node that shows up somewhere else among the same parent's children is reported as a move, not a remove + insert
+ * Nodes are named by their path, the child indexes from the root ([1, 0, 2] is the 3rd child of the 1st child of the
+ * root's 2nd child), removed nodes are named by their path in the old tree and everything else by the path in the new one
+ */
+
+use std::fmt;
+
+use crate::dom::{DoctypeData, Node, NodeType, QuirksMode};
+
+// NOTE 1- Data Structures
+
+pub type NodePath = Vec<usize>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Insert { path: NodePath, node: Node },
+    Remove { path: NodePath, node: Node },
+    Move { from: NodePath, to: NodePath, node: Node },
+    SetAttribute { path: NodePath, name: String, old_value: Option<String>, value: String },
+    RemoveAttribute { path: NodePath, name: String, old_value: String },
+    SetText { path: NodePath, old_text: String, text: String }, //text and comment nodes
+    SetDoctype { path: NodePath, old_doctype: DoctypeData, doctype: DoctypeData },
+    SetQuirksMode { path: NodePath, old_mode: QuirksMode, mode: QuirksMode }, //of the #document
+}
+
+//What not to count as a difference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiffOptions {
+    pub ignore_whitespace_text: bool, //text nodes with only whitespace in them, the indentation between tags
+    pub ignore_comments: bool,
+}
+
+// NOTE 2- Diffing
+
+//Every edit that turns `old` into `new`
+pub fn diff(old: &Node, new: &Node) -> Vec<Edit> {
+    diff_with(old, new, DiffOptions::default())
+}
+
+pub fn diff_with(old: &Node, new: &Node, options: DiffOptions) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    if same_kind(old, new) {
+        diff_nodes(old, new, &Vec::new(), &Vec::new(), options, &mut edits);
+    } else {
+        //not even the roots line up, the whole tree was replaced
+        edits.push(Edit::Remove { path: Vec::new(), node: old.clone() });
+        edits.push(Edit::Insert { path: Vec::new(), node: new.clone() });
+    }
+    edits
+}
+
+//Two nodes of the same kind, compare what they hold and then their children
+fn diff_nodes(old: &Node, new: &Node, old_path: &NodePath, new_path: &NodePath, options: DiffOptions, edits: &mut Vec<Edit>) {
+    match (&old.node_type, &new.node_type) {
+        (NodeType::Element(ref a), NodeType::Element(ref b)) => {
+            for (name, value) in b.attributes().iter() {
+                let old_value = a.get_attribute(name);
+                if old_value != Some(value) {
+                    edits.push(Edit::SetAttribute {
+                        path: new_path.clone(),
+                        name: name.clone(),
+                        old_value: old_value.cloned(),
+                        value: value.clone(),
+                    });
+                }
+            }
+            for (name, value) in a.attributes().iter() {
+                if !b.has_attribute(name) {
+                    edits.push(Edit::RemoveAttribute { path: new_path.clone(), name: name.clone(), old_value: value.clone() });
+                }
+            }
+        }
+        (NodeType::Text(ref a), NodeType::Text(ref b)) | (NodeType::Comment(ref a), NodeType::Comment(ref b)) if a != b => {
+            edits.push(Edit::SetText { path: new_path.clone(), old_text: a.clone(), text: b.clone() });
+        }
+        (NodeType::Doctype(ref a), NodeType::Doctype(ref b)) if a != b => {
+            edits.push(Edit::SetDoctype { path: new_path.clone(), old_doctype: a.clone(), doctype: b.clone() });
+        }
+        (NodeType::Document(ref a), NodeType::Document(ref b)) if a != b => {
+            edits.push(Edit::SetQuirksMode { path: new_path.clone(), old_mode: *a, mode: *b });
+        }
+        _ => {}
+    }
+    diff_children(old, new, old_path, new_path, options, edits);
+}
+
+fn diff_children(old: &Node, new: &Node, old_path: &NodePath, new_path: &NodePath, options: DiffOptions, edits: &mut Vec<Edit>) {
+    //children are still named by their index among all of the parent's children, ignored ones included
+    let old_children: Vec<(usize, &Node)> = old.children.iter().enumerate().filter(|&(_, n)| !ignored(n, options)).collect();
+    let new_children: Vec<(usize, &Node)> = new.children.iter().enumerate().filter(|&(_, n)| !ignored(n, options)).collect();
+
+    let pairs = line_up(&old_children, &new_children);
+    let mut old_match: Vec<Option<usize>> = vec![None; old_children.len()];
+    let mut new_match: Vec<Option<usize>> = vec![None; new_children.len()];
+    for &(i, j) in pairs.iter() {
+        old_match[i] = Some(j);
+        new_match[j] = Some(i);
+    }
+
+    //What's left over on both sides is a move when the same node (or the element with the same id) is on both
+    let mut moved_from: Vec<Option<usize>> = vec![None; new_children.len()];
+    for j in 0..new_children.len() {
+        if new_match[j].is_some() {
+            continue;
+        }
+        let moved = (0..old_children.len()).find(|&i| old_match[i].is_none() && is_same_node(old_children[i].1, new_children[j].1));
+        if let Some(i) = moved {
+            old_match[i] = Some(j);
+            moved_from[j] = Some(i);
+        }
+    }
+
+    for (i, &(index, node)) in old_children.iter().enumerate() {
+        if old_match[i].is_none() {
+            edits.push(Edit::Remove { path: child_path(old_path, index), node: node.clone() });
+        }
+    }
+    for (j, &(index, node)) in new_children.iter().enumerate() {
+        let child_new_path = child_path(new_path, index);
+        let i = match (new_match[j], moved_from[j]) {
+            (Some(i), _) => i,
+            (None, Some(i)) => {
+                let from = child_path(old_path, old_children[i].0);
+                edits.push(Edit::Move { from, to: child_new_path.clone(), node: node.clone() });
+                i
+            }
+            (None, None) => {
+                edits.push(Edit::Insert { path: child_new_path, node: node.clone() });
+                continue;
+            }
+        };
+        let (old_index, old_node) = old_children[i];
+        diff_nodes(old_node, node, &child_path(old_path, old_index), &child_new_path, options, edits);
+    }
+}
+
+//Longest common subsequence of the two child lists, weighted so identical nodes pair up before nodes that just
+//have the same tag, gives back the (old, new) index pairs in order
+fn line_up(old: &[(usize, &Node)], new: &[(usize, &Node)]) -> Vec<(usize, usize)> {
+    let weight = |i: usize, j: usize| -> usize {
+        let (a, b) = (old[i].1, new[j].1);
+        if !same_kind(a, b) {
+            0
+        } else if same_content(a, b) {
+            3
+        } else {
+            1
+        }
+    };
+
+    //best[i][j] is the best score for old[i..] and new[j..]
+    let mut best = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            let w = weight(i, j);
+            let paired = if w > 0 { best[i + 1][j + 1] + w } else { 0 };
+            best[i][j] = paired.max(best[i + 1][j]).max(best[i][j + 1]);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        let w = weight(i, j);
+        if w > 0 && best[i][j] == best[i + 1][j + 1] + w {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if best[i + 1][j] >= best[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+// NOTE 3- Helper Methods
+
+//Nodes that can be compared with each other, elements need the same name and the same id (or neither has one)
+fn same_kind(a: &Node, b: &Node) -> bool {
+    match (&a.node_type, &b.node_type) {
+        (NodeType::Element(ref x), NodeType::Element(ref y)) => {
+            x.namespace == y.namespace && x.tag_name == y.tag_name && x.get_id() == y.get_id()
+        }
+        (NodeType::Text(_), NodeType::Text(_))
+        | (NodeType::Comment(_), NodeType::Comment(_))
+        | (NodeType::Doctype(_), NodeType::Doctype(_))
+        | (NodeType::Document(_), NodeType::Document(_)) => true,
+        _ => false,
+    }
+}
+
+//The same node in a different place, it's identical or it's the element with that id
+fn is_same_node(a: &Node, b: &Node) -> bool {
+    match (&a.node_type, &b.node_type) {
+        (NodeType::Element(ref x), NodeType::Element(ref y)) if x.get_id().is_some() => same_kind(a, b) && x.get_id() == y.get_id(),
+        _ => same_content(a, b),
+    }
+}
+
+//Equal nodes and children, spans aren't compared since any edit earlier in the page shifts everything after it
+fn same_content(a: &Node, b: &Node) -> bool {
+    a.node_type == b.node_type
+        && a.children.len() == b.children.len()
+        && a.children.iter().zip(b.children.iter()).all(|(x, y)| same_content(x, y))
+}
+
+fn ignored(node: &Node, options: DiffOptions) -> bool {
+    match node.node_type {
+        NodeType::Text(ref t) => options.ignore_whitespace_text && t.chars().all(|c| c.is_ascii_whitespace()),
+        NodeType::Comment(_) => options.ignore_comments,
+        _ => false,
+    }
+}
+
+fn child_path(parent: &NodePath, index: usize) -> NodePath {
+    let mut path = parent.clone();
+    path.push(index);
+    path
+}
+
+// NOTE 4- Report
+
+//One line per edit, like
+//  + /1/0/2 <li, class="new">
+//  - /1/0/0 <li,>
+//  > /1/0/3 -> /1/0/1 <li, id="top">
+//  @ /1/0/1 class: "a" -> "b"
+//  @ /1/0/1 id removed (was "x")
+//  ~ /1/0/1/0 "old text" -> "new text"
+//  ! /0 <!DOCTYPE html> -> <!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01//EN">
+//  ! / quirks mode NoQuirks -> Quirks
+pub fn report(edits: &[Edit]) -> String {
+    let mut result = String::new();
+    for edit in edits {
+        result.push_str(&format!("{}\n", edit));
+    }
+    result
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Edit::Insert { ref path, ref node } => write!(f, "+ {} {}", format_path(path), describe(node)),
+            Edit::Remove { ref path, ref node } => write!(f, "- {} {}", format_path(path), describe(node)),
+            Edit::Move { ref from, ref to, ref node } => {
+                write!(f, "> {} -> {} {}", format_path(from), format_path(to), describe(node))
+            }
+            Edit::SetAttribute { ref path, ref name, ref old_value, ref value } => match *old_value {
+                Some(ref old_value) => write!(f, "@ {} {}: {:?} -> {:?}", format_path(path), name, old_value, value),
+                None => write!(f, "@ {} {} added: {:?}", format_path(path), name, value),
+            },
+            Edit::RemoveAttribute { ref path, ref name, ref old_value } => {
+                write!(f, "@ {} {} removed (was {:?})", format_path(path), name, old_value)
+            }
+            Edit::SetText { ref path, ref old_text, ref text } => {
+                write!(f, "~ {} {:?} -> {:?}", format_path(path), old_text, text)
+            }
+            Edit::SetDoctype { ref path, ref old_doctype, ref doctype } => {
+                write!(f, "! {} {} -> {}", format_path(path), describe_doctype(old_doctype), describe_doctype(doctype))
+            }
+            Edit::SetQuirksMode { ref path, old_mode, mode } => {
+                write!(f, "! {} quirks mode {:?} -> {:?}", format_path(path), old_mode, mode)
+            }
+        }
+    }
+}
+
+fn format_path(path: &NodePath) -> String {
+    if path.is_empty() {
+        return String::from("/");
+    }
+    path.iter().map(|index| format!("/{}", index)).collect()
+}
+
+//Short description of a node, elements with their attributes but not their children
+fn describe(node: &Node) -> String {
+    match node.node_type {
+        NodeType::Text(ref t) => format!("{:?}", t),
+        NodeType::Comment(ref c) => format!("<!--{}-->", c),
+        _ => format!("{:?}", node.node_type),
+    }
+}
+
+//The whole doctype, the public and system ids are what the quirks mode is decided on
+fn describe_doctype(doctype: &DoctypeData) -> String {
+    let mut result = format!("<!DOCTYPE {}", doctype.name);
+    if !doctype.public_id.is_empty() {
+        result.push_str(&format!(" PUBLIC {:?}", doctype.public_id));
+    } else if !doctype.system_id.is_empty() {
+        result.push_str(" SYSTEM");
+    }
+    if !doctype.system_id.is_empty() {
+        result.push_str(&format!(" {:?}", doctype.system_id));
+    }
+    result + ">"
+}
@@ -0,0 +1,247 @@
+/* Pages come in as bytes and not every page is UTF-8, old pages especially are Latin-1 / windows-1252
+ * Before the tokenizer sees a single character we have to work out which encoding the bytes are in, the spec calls it
+ * "encoding sniffing": https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding
+ * 1- a byte order mark at the start always wins
+ * 2- otherwise we prescan the first 1024 bytes for <meta charset="..."> or <meta http-equiv="content-type" content="...">
+ * 3- otherwise we fall back to windows-1252, like browsers do for pages without any hint
+ * The actual decoding (windows-1252, ISO-8859-x, UTF-16, Shift_JIS ...) is done by encoding_rs
+ */
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+
+//The prescan only looks this far into the page, the <meta> is supposed to be near the top
+const PRESCAN_LENGTH: usize = 1024;
+
+// NOTE 1- Main Entry Points
+
+//Decode a whole page, returns the text and the encoding it was decoded with
+//A byte order mark is removed, broken byte sequences turn into U+FFFD
+pub fn decode_html(bytes: &[u8]) -> (String, &'static Encoding) {
+    let encoding = sniff_encoding(bytes);
+    let (text, _) = encoding.decode_with_bom_removal(bytes);
+    (text.into_owned(), encoding)
+}
+
+//Work out which encoding the page is in without decoding it
+pub fn sniff_encoding(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    let end = bytes.len().min(PRESCAN_LENGTH);
+    match prescan(&bytes[..end]) {
+        Some(encoding) => encoding,
+        None => WINDOWS_1252,
+    }
+}
+
+// NOTE 2- Prescan
+//https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+
+        if rest.starts_with(b"<!--") {
+            //skip the comment, "<!-->" already closes it
+            pos = match find(bytes, pos + 2, b"-->") {
+                Some(end) => end + 3,
+                None => return None,
+            };
+        } else if starts_with_ignore_case(rest, b"<meta") && rest.get(5).is_some_and(|&b| is_space(b) || b == b'/') {
+            pos += 5;
+            if let Some(encoding) = meta_charset(bytes, &mut pos) {
+                return Some(encoding);
+            }
+        } else if rest.len() > 1 && rest[0] == b'<' && (rest[1].is_ascii_alphabetic() || (rest[1] == b'/' && rest.get(2).is_some_and(|b| b.is_ascii_alphabetic()))) {
+            //any other tag, skip its name and its attributes (a '>' inside a quoted value doesn't end it)
+            while pos < bytes.len() && !is_space(bytes[pos]) && bytes[pos] != b'>' {
+                pos += 1;
+            }
+            while get_attribute(bytes, &mut pos).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            //doctype, processing instruction or a broken end tag, skip up to the next '>'
+            pos = match find(bytes, pos, b">") {
+                Some(end) => end + 1,
+                None => return None,
+            };
+        } else {
+            pos += 1;
+        }
+    }
+
+    None
+}
+
+//Go through the attributes of a <meta> and see if together they name an encoding
+fn meta_charset(bytes: &[u8], pos: &mut usize) -> Option<&'static Encoding> {
+    let mut seen = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None; //None: no charset found yet, Some(true): it came from "content", Some(false): from "charset"
+    let mut charset = None;
+
+    while let Some((name, value)) = get_attribute(bytes, pos) {
+        if seen.contains(&name) {
+            continue; //only the first attribute with a given name counts
+        }
+
+        match &name[..] {
+            b"http-equiv" => got_pragma = value.eq_ignore_ascii_case(b"content-type"),
+            b"content" if charset.is_none() => {
+                if let Some(label) = charset_from_content(&value) {
+                    charset = Some(Encoding::for_label(&label));
+                    need_pragma = Some(true);
+                }
+            }
+            b"charset" => {
+                charset = Some(Encoding::for_label(&value));
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        seen.push(name);
+    }
+
+    match need_pragma {
+        None => return None,
+        Some(true) if !got_pragma => return None,
+        _ => {}
+    }
+
+    //a label we don't know is the same as no label at all
+    let encoding = charset??;
+    //a page that made it through the prescan as ASCII can't really be UTF-16
+    if encoding == UTF_16BE || encoding == UTF_16LE {
+        Some(UTF_8)
+    } else if encoding == X_USER_DEFINED {
+        Some(WINDOWS_1252)
+    } else {
+        Some(encoding)
+    }
+}
+
+//Read one attribute starting at pos, names come back lowercased
+//Returns None at the '>' that ends the tag (or at the end of the bytes)
+fn get_attribute(bytes: &[u8], pos: &mut usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    while *pos < bytes.len() && (is_space(bytes[*pos]) || bytes[*pos] == b'/') {
+        *pos += 1;
+    }
+    if *pos >= bytes.len() || bytes[*pos] == b'>' {
+        return None;
+    }
+
+    //ANCHOR Name
+    let mut name = Vec::new();
+    loop {
+        let b = *bytes.get(*pos)?;
+        match b {
+            b'=' if !name.is_empty() => {
+                *pos += 1;
+                break;
+            }
+            b if is_space(b) => {
+                //whitespace is allowed around the '='
+                while *pos < bytes.len() && is_space(bytes[*pos]) {
+                    *pos += 1;
+                }
+                if bytes.get(*pos) != Some(&b'=') {
+                    return Some((name, Vec::new()));
+                }
+                *pos += 1;
+                break;
+            }
+            b'/' | b'>' => return Some((name, Vec::new())),
+            b => {
+                name.push(b.to_ascii_lowercase());
+                *pos += 1;
+            }
+        }
+    }
+
+    //ANCHOR Value
+    while *pos < bytes.len() && is_space(bytes[*pos]) {
+        *pos += 1;
+    }
+    let mut value = Vec::new();
+    match *bytes.get(*pos)? {
+        quote @ b'"' | quote @ b'\'' => {
+            *pos += 1;
+            loop {
+                let b = *bytes.get(*pos)?;
+                *pos += 1;
+                if b == quote {
+                    return Some((name, value));
+                }
+                value.push(b.to_ascii_lowercase());
+            }
+        }
+        b'>' => Some((name, value)),
+        _ => {
+            while let Some(&b) = bytes.get(*pos) {
+                if is_space(b) || b == b'>' {
+                    break;
+                }
+                value.push(b.to_ascii_lowercase());
+                *pos += 1;
+            }
+            Some((name, value))
+        }
+    }
+}
+
+//Pull the label out of content="text/html; charset=iso-8859-1"
+//https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element
+fn charset_from_content(content: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    loop {
+        pos = find_ignore_case(content, pos, b"charset")? + 7;
+        while pos < content.len() && is_space(content[pos]) {
+            pos += 1;
+        }
+        if content.get(pos) == Some(&b'=') {
+            pos += 1;
+            break;
+        }
+        //"charset" without a '=' after it, look for the next one
+    }
+    while pos < content.len() && is_space(content[pos]) {
+        pos += 1;
+    }
+
+    match *content.get(pos)? {
+        quote @ b'"' | quote @ b'\'' => {
+            let start = pos + 1;
+            let end = start + content[start..].iter().position(|&b| b == quote)?; //an unclosed quote is no charset at all
+            Some(content[start..end].to_vec())
+        }
+        _ => {
+            let end = content[pos..]
+                .iter()
+                .position(|&b| is_space(b) || b == b';')
+                .map_or(content.len(), |end| pos + end);
+            Some(content[pos..end].to_vec())
+        }
+    }
+}
+
+// NOTE 3- Helper Methods
+
+//Whitespace as far as the prescan is concerned: tab, LF, FF, CR and space
+fn is_space(b: u8) -> bool {
+    b == 0x09 || b == 0x0A || b == 0x0C || b == 0x0D || b == 0x20
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+//Index of the first `needle` at or after `from`
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    (from..bytes.len()).find(|&i| bytes[i..].starts_with(needle))
+}
+
+fn find_ignore_case(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    (from..bytes.len()).find(|&i| starts_with_ignore_case(&bytes[i..], needle))
+}
@@ -3,314 +3,2491 @@
  * The HTML parsing algorithm does not reject invalid input, instead it uses very specific error handling instructions
  * This way different web browsers will be able to agree how website would look like
  * Also bec. non conforming HTML has been supported since early days of web its now used in large amount of web pages that exist
+ * The tree builder below follows the spec's "tree construction" stage: https://html.spec.whatwg.org/multipage/parsing.html#tree-construction
+ * It keeps a stack of open elements, a list of active formatting elements and an insertion mode that says how the next token is handled
  */
-use crate::dom::{AttrMap, ElementData, Node, NodeType};
+use crate::dom::{is_void_element, AttrMap, DoctypeData, ElementData, Namespace, Node, NodeType, QuirksMode};
+use crate::encoding;
+use crate::html_tokenizer::{is_whitespace, Attribute, Doctype, State, Tag, Token, Tokenizer, TokenizerError};
+use crate::source::{Position, Span};
 
-use std::iter::Peekable;
-use std::str::Chars;
+use encoding_rs::{Encoding, UTF_8};
+use std::fmt;
 
 // NOTE 1- Data Structure
-//Has lifetime of 'a, 
-pub struct HtmlParser<'a> {
-    chars: Peekable<Chars<'a>>, // A peekable list of charachters
-    node_q: Vec<String>, 
-    //Peakable data type is an iterator type it iterates over option types and tells whether the option has some or none 
-    //In this case bec. we are iterating over characters, Its like a character stream 
-    //Our Node Q is like adding our characters into strings and then passing them into a vector
+
+//While building, nodes live in a flat list and point at each other by index, because the algorithm
+//needs to move nodes around (adoption agency, foster parenting) after they were inserted
+type Handle = usize;
+
+const DOCUMENT: Handle = 0;
+
+enum BuildData {
+    Document,
+    Doctype(DoctypeData),
+    Element(Tag, Namespace),
+    Text(String),
+    Comment(String),
+}
+
+//Nodes point at the token that created them, elements the parser made up on its own (an implied <tbody>)
+//point at the token that made it create them
+struct BuildNode {
+    data: BuildData,
+    parent: Option<Handle>,
+    children: Vec<Handle>,
+    span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InsertionMode {
+    Initial,
+    BeforeHtml,
+    BeforeHead,
+    InHead,
+    InHeadNoscript,
+    AfterHead,
+    InBody,
+    Text,
+    InTable,
+    InTableText,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
+    InSelect,
+    InSelectInTable,
+    InTemplate,
+    AfterBody,
+    InFrameset,
+    AfterFrameset,
+    AfterAfterBody,
+    AfterAfterFrameset,
+}
+
+//Formatting elements (b, i, a ...) are remembered so they can be reopened after being closed by misnested markup
+enum FormattingEntry {
+    Marker,
+    Element(Handle, Tag),
+}
+
+//Parse errors never stop the parser, they are collected so authors can see why their markup
+//was fixed up the way it was
+#[derive(Debug, Clone, PartialEq)]
+pub enum HtmlErrorKind {
+    Tokenizer(TokenizerError),     //broken syntax, like a missing attribute value or an unterminated comment
+    MissingDoctype,
+    UnexpectedDoctype,
+    UnexpectedStartTag(String),    //a start tag that isn't allowed where it is, it was ignored or moved
+    UnexpectedEndTag(String),      //an end tag that doesn't close anything, or closes more than itself
+    UnexpectedCharacter(char),     //text where no text can go, like directly inside a <table>
+    UnexpectedEof,
+    NonVoidSelfClosingTag(String), //"<div/>" is just "<div>", only void elements can close themselves
+    MisnestedTag(String),          //"<b><i></b></i>", fixed by the adoption agency
+    UnclosedElement(String),       //the file ended while the element was still open
 }
 
-// NOTE 2- Implement Methods For The DataStructure
-impl<'a> HtmlParser<'a> {
-    pub fn new(full_html: &str) -> HtmlParser {
-        HtmlParser {
-            chars: full_html.chars().peekable(), //take string, convert it to peekable iterator of characthers
-            node_q: Vec::new(), //empty vector
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlParseError {
+    pub kind: HtmlErrorKind,
+    pub span: Span,
+}
+
+impl HtmlParseError {
+    pub fn new(kind: HtmlErrorKind, span: Span) -> HtmlParseError {
+        HtmlParseError { kind, span }
+    }
+}
+impl fmt::Display for HtmlParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}", self.span.start, self.kind)
+    }
+}
+
+pub struct HtmlParser {
+    tokenizer: Tokenizer,
+    nodes: Vec<BuildNode>,
+    mode: InsertionMode,
+    quirks_mode: QuirksMode,
+    original_mode: InsertionMode,
+    template_modes: Vec<InsertionMode>,
+    open_elements: Vec<Handle>,
+    active_formatting: Vec<FormattingEntry>,
+    head_element: Option<Handle>,
+    form_element: Option<Handle>,
+    frameset_ok: bool,
+    foster_parenting: bool,
+    pending_table_text: Vec<(char, Span)>,
+    skip_newline: bool, //a newline right after <pre>, <listing> or <textarea> is dropped
+    self_closing_acknowledged: bool, //the "/>" of the current start tag meant something (void or foreign element)
+    token_span: Span,   //span of the token being processed, new nodes and errors point at it
+    errors: Vec<HtmlParseError>,
+    encoding: &'static Encoding, //what the page was decoded from, UTF-8 when we were given a string
+    context_element: Option<Handle>, //fragment parsing only, the element the fragment is parsed into (never in the tree)
+}
+
+// NOTE 2- Implement Methods For The DataStructure
+impl HtmlParser {
+    pub fn new(full_html: &str) -> HtmlParser {
+        HtmlParser::with_tokenizer(Tokenizer::new(full_html))
+    }
+
+    //Push style parser, the document arrives in chunks through feed() and ends with finish()
+    //In between document() shows what has been built so far, so we can render progressively
+    pub fn streaming() -> HtmlParser {
+        HtmlParser::with_tokenizer(Tokenizer::streaming())
+    }
+
+    fn with_tokenizer(tokenizer: Tokenizer) -> HtmlParser {
+        HtmlParser {
+            tokenizer,
+            nodes: vec![BuildNode { data: BuildData::Document, parent: None, children: Vec::new(), span: Span::default() }],
+            mode: InsertionMode::Initial,
+            quirks_mode: QuirksMode::NoQuirks,
+            original_mode: InsertionMode::Initial,
+            template_modes: Vec::new(),
+            open_elements: Vec::new(),
+            active_formatting: Vec::new(),
+            head_element: None,
+            form_element: None,
+            frameset_ok: true,
+            foster_parenting: false,
+            pending_table_text: Vec::new(),
+            skip_newline: false,
+            self_closing_acknowledged: false,
+            token_span: Span::default(),
+            errors: Vec::new(),
+            encoding: UTF_8,
+            context_element: None,
+        }
+    }
+
+    //Fragment parsing (innerHTML), the markup is parsed as if it was the content of a `context` element,
+    //so "<li>a<li>b" in a "ul" or "<tr><td>x" in a "tbody" come out the way they would inside that element
+    //https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments
+    pub fn new_fragment(context: &str, fragment_html: &str) -> HtmlParser {
+        HtmlParser::new_fragment_in(context, Namespace::Html, fragment_html)
+    }
+
+    //Same as new_fragment for a context element in any namespace, like "svg" or "foreignObject" in Namespace::Svg
+    //or "math" in Namespace::MathMl, the content then gets the foreign content rules like it would inside that element
+    pub fn new_fragment_in(context: &str, namespace: Namespace, fragment_html: &str) -> HtmlParser {
+        let mut parser = HtmlParser::new(fragment_html);
+        //HTML names don't care about case, SVG names keep theirs (foreignObject) and MathML ones are lowercase
+        let context_name = match namespace {
+            Namespace::Html | Namespace::MathMl => context.to_ascii_lowercase(),
+            Namespace::Svg => adjust_svg_tag_name(&context.to_ascii_lowercase()).map_or_else(|| context.to_string(), String::from),
+        };
+
+        //the context decides if the content is markup at all
+        let state = match (namespace, context_name.as_str()) {
+            (Namespace::Html, "title" | "textarea") => State::RcData,
+            (Namespace::Html, "style" | "xmp" | "iframe" | "noembed" | "noframes") => State::RawText,
+            (Namespace::Html, "script") => State::ScriptData,
+            (Namespace::Html, "plaintext") => State::PlainText,
+            _ => State::Data,
+        };
+        parser.tokenizer.set_state(state);
+        parser.tokenizer.set_last_start_tag(&context_name);
+        parser.tokenizer.set_in_foreign_content(namespace != Namespace::Html); //<![CDATA[ works right away in svg and math
+
+        //everything gets parsed into a bare <html> root
+        let root = parser.create_element(tag_named("html"));
+        parser.append(DOCUMENT, root);
+        parser.open_elements.push(root);
+
+        let context = parser.create_element_in(tag_named(&context_name), namespace);
+        if parser.is_html(context, "template") {
+            parser.template_modes.push(InsertionMode::InTemplate);
+        }
+        if parser.is_html(context, "form") {
+            parser.form_element = Some(context);
+        }
+        parser.context_element = Some(context);
+        parser.reset_insertion_mode();
+        parser
+    }
+
+    //Run a parser made by new_fragment, returns the nodes the context element would get as children
+    pub fn parse_fragment(&mut self) -> Vec<Node> {
+        self.finish();
+
+        //in the fragment case the <html> root is the only child of the document
+        let root = self.nodes[DOCUMENT].children[0];
+        let children = self.nodes[root].children.clone();
+        children.iter().map(|&child| self.to_node(child)).collect()
+    }
+
+    //Entry point for raw bytes (a file, a network response), sniffs the encoding and decodes them first
+    pub fn from_bytes(bytes: &[u8]) -> HtmlParser {
+        let (html, encoding) = encoding::decode_html(bytes);
+        let mut parser = HtmlParser::new(&html);
+        parser.encoding = encoding;
+        parser
+    }
+
+    //Main entry point for our HTML parser
+    //HTML can accept inavalid structure and invalid syntax
+    //Rather than throw error on invalid syntax, we want them to correct that invalid syntax
+    //Returns the Document node, its children are the doctype, comments and the <html> element (always there)
+    pub fn parse_document(&mut self) -> Node {
+        self.finish()
+    }
+
+    //Give the next chunk to a streaming parser, everything it completes goes into the tree right away
+    pub fn feed(&mut self, chunk: &str) {
+        self.tokenizer.feed(chunk);
+        self.run();
+    }
+
+    //The last chunk was fed, close whatever is still open and return the finished document
+    pub fn finish(&mut self) -> Node {
+        self.tokenizer.finish();
+        self.run();
+        self.document()
+    }
+
+    //Snapshot of the tree built so far, elements that are still open just don't have all their children yet
+    pub fn document(&self) -> Node {
+        self.to_node(DOCUMENT)
+    }
+
+    //Build the tree from every token the tokenizer can give us with the input it has
+    fn run(&mut self) {
+        while let Some(token) = self.tokenizer.next_token() {
+            self.token_span = self.tokenizer.span();
+            for (error, span) in self.tokenizer.take_errors() {
+                self.errors.push(HtmlParseError::new(HtmlErrorKind::Tokenizer(error), span));
+            }
+            self.process_token(token);
+            let foreign = self.in_foreign_element();
+            self.tokenizer.set_in_foreign_content(foreign);
+        }
+        //the tokenizer finds its errors while reading ahead, put them back in page order like the CSS parser does
+        self.errors.sort_by_key(|error| error.span.start);
+        self.nodes[DOCUMENT].span = Span::new(Position::default(), self.token_span.end);
+    }
+
+    //Same as parse_document but only the children of the document
+    pub fn parse_nodes(&mut self) -> Vec<Node> {
+        self.parse_document().children
+    }
+
+    //The encoding the page was decoded with, its name() gives the label ("windows-1252", "UTF-8" ...)
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    //Everything the parser had to recover from, in the order it appears in the page
+    pub fn errors(&self) -> &[HtmlParseError] {
+        &self.errors
+    }
+
+    //ANCHOR Dispatch
+    //Every token from the tokenizer goes through here exactly once, reprocessing a token skips it
+    fn process_token(&mut self, token: Token) {
+        let self_closing = match token {
+            Token::StartTag(ref tag) if tag.self_closing && !is_void_element(&tag.name) => Some(tag.name.clone()),
+            _ => None,
+        };
+        let end_tag = match token {
+            Token::EndTag(ref tag) => Some((tag.name.clone(), self.open_elements.clone())),
+            _ => None,
+        };
+
+        self.self_closing_acknowledged = false;
+        self.process(token);
+
+        //"<div/>" is just "<div>", only void elements and SVG/MathML elements can close themselves
+        if let Some(name) = self_closing {
+            if !self.self_closing_acknowledged {
+                self.parse_error(HtmlErrorKind::NonVoidSelfClosingTag(name));
+            }
+        }
+
+        //Elements closed by their own end tag stretch up to the end of that tag
+        if let Some((name, open_before)) = end_tag {
+            for handle in open_before {
+                if !self.open_elements.contains(&handle) && self.local_name(handle).eq_ignore_ascii_case(&name) {
+                    self.nodes[handle].span.end = self.token_span.end;
+                }
+            }
+        }
+    }
+
+    fn process(&mut self, token: Token) {
+        if self.skip_newline {
+            self.skip_newline = false;
+            if token == Token::Character('\n') {
+                return;
+            }
+        }
+        if self.uses_foreign_rules(&token) {
+            self.in_foreign_content(token);
+        } else {
+            let mode = self.mode;
+            self.process_using(mode, token);
+        }
+    }
+
+    //The "tree construction dispatcher", inside <svg> and <math> tokens skip the insertion mode and get
+    //the foreign content rules, except where the foreign element lets HTML back in (the integration points)
+    fn uses_foreign_rules(&self, token: &Token) -> bool {
+        if !self.in_foreign_element() {
+            return false;
+        }
+        let node = self.adjusted_current_node();
+        match *token {
+            Token::Eof => false,
+            Token::StartTag(ref tag) if self.is_mathml_text_integration_point(node) => {
+                tag.name == "mglyph" || tag.name == "malignmark"
+            }
+            Token::Character(_) if self.is_mathml_text_integration_point(node) => false,
+            Token::StartTag(ref tag)
+                if tag.name == "svg" && self.namespace(node) == Some(Namespace::MathMl) && self.local_name(node) == "annotation-xml" =>
+            {
+                false
+            }
+            Token::StartTag(_) | Token::Character(_) => !self.is_html_integration_point(node),
+            _ => true,
+        }
+    }
+
+    //"Process the token using the rules for" another insertion mode, without switching to it
+    fn process_using(&mut self, mode: InsertionMode, token: Token) {
+        match mode {
+            InsertionMode::Initial => self.initial(token),
+            InsertionMode::BeforeHtml => self.before_html(token),
+            InsertionMode::BeforeHead => self.before_head(token),
+            InsertionMode::InHead => self.in_head(token),
+            InsertionMode::InHeadNoscript => self.in_head_noscript(token),
+            InsertionMode::AfterHead => self.after_head(token),
+            InsertionMode::InBody => self.in_body(token),
+            InsertionMode::Text => self.text(token),
+            InsertionMode::InTable => self.in_table(token),
+            InsertionMode::InTableText => self.in_table_text(token),
+            InsertionMode::InCaption => self.in_caption(token),
+            InsertionMode::InColumnGroup => self.in_column_group(token),
+            InsertionMode::InTableBody => self.in_table_body(token),
+            InsertionMode::InRow => self.in_row(token),
+            InsertionMode::InCell => self.in_cell(token),
+            InsertionMode::InSelect => self.in_select(token),
+            InsertionMode::InSelectInTable => self.in_select_in_table(token),
+            InsertionMode::InTemplate => self.in_template(token),
+            InsertionMode::AfterBody => self.after_body(token),
+            InsertionMode::InFrameset => self.in_frameset(token),
+            InsertionMode::AfterFrameset => self.after_frameset(token),
+            InsertionMode::AfterAfterBody => self.after_after_body(token),
+            InsertionMode::AfterAfterFrameset => self.after_after_frameset(token),
+        }
+    }
+
+    //Switch mode and hand the same token to the new mode
+    fn reprocess(&mut self, mode: InsertionMode, token: Token) {
+        self.mode = mode;
+        self.process(token);
+    }
+
+    //ANCHOR Insertion Modes
+    fn initial(&mut self, token: Token) {
+        match token {
+            Token::Character(c) if is_whitespace(c) => {}
+            Token::Comment(text) => self.insert_comment_in(text, DOCUMENT),
+            Token::Doctype(doctype) => {
+                self.quirks_mode = quirks_mode_for(&doctype);
+                let data = DoctypeData::new(
+                    doctype.name.unwrap_or_default(),
+                    doctype.public_id.unwrap_or_default(),
+                    doctype.system_id.unwrap_or_default(),
+                );
+                let node = self.new_node(BuildData::Doctype(data));
+                self.append(DOCUMENT, node);
+                self.mode = InsertionMode::BeforeHtml;
+            }
+            token => {
+                //no doctype at all means quirks mode
+                self.parse_error(HtmlErrorKind::MissingDoctype);
+                self.quirks_mode = QuirksMode::Quirks;
+                self.reprocess(InsertionMode::BeforeHtml, token);
+            }
+        }
+    }
+
+    fn before_html(&mut self, token: Token) {
+        match token {
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::Comment(text) => self.insert_comment_in(text, DOCUMENT),
+            Token::Character(c) if is_whitespace(c) => {}
+            Token::StartTag(ref tag) if tag.name == "html" => {
+                let html = self.create_element(tag.clone());
+                self.append(DOCUMENT, html);
+                self.open_elements.push(html);
+                self.mode = InsertionMode::BeforeHead;
+            }
+            Token::EndTag(ref tag) if !is_one_of(&tag.name, &["head", "body", "html", "br"]) => self.unexpected(&token), //ignored
+            token => {
+                let html = self.create_element(tag_named("html"));
+                self.append(DOCUMENT, html);
+                self.open_elements.push(html);
+                self.reprocess(InsertionMode::BeforeHead, token);
+            }
+        }
+    }
+
+    fn before_head(&mut self, token: Token) {
+        match token {
+            Token::Character(c) if is_whitespace(c) => {}
+            Token::Comment(text) => self.insert_comment(text),
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(ref tag) if tag.name == "html" => self.process_using(InsertionMode::InBody, token.clone()),
+            Token::StartTag(ref tag) if tag.name == "head" => {
+                let head = self.insert_html_element(tag.clone());
+                self.head_element = Some(head);
+                self.mode = InsertionMode::InHead;
+            }
+            Token::EndTag(ref tag) if !is_one_of(&tag.name, &["head", "body", "html", "br"]) => self.unexpected(&token), //ignored
+            token => {
+                let head = self.insert_html_element(tag_named("head"));
+                self.head_element = Some(head);
+                self.reprocess(InsertionMode::InHead, token);
+            }
+        }
+    }
+
+    fn in_head(&mut self, token: Token) {
+        match token {
+            Token::Character(c) if is_whitespace(c) => self.insert_character(c),
+            Token::Comment(text) => self.insert_comment(text),
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(tag) => match tag.name.as_str() {
+                "html" => self.in_body(Token::StartTag(tag)),
+                "base" | "basefont" | "bgsound" | "link" | "meta" => self.insert_void_element(tag),
+                "title" => self.parse_text_element(tag, State::RcData),
+                "noframes" | "style" => self.parse_text_element(tag, State::RawText),
+                "script" => self.parse_text_element(tag, State::ScriptData),
+                "noscript" => {
+                    //We don't run scripts, so noscript content is parsed as markup
+                    self.insert_html_element(tag);
+                    self.mode = InsertionMode::InHeadNoscript;
+                }
+                "template" => {
+                    self.insert_html_element(tag);
+                    self.active_formatting.push(FormattingEntry::Marker);
+                    self.frameset_ok = false;
+                    self.mode = InsertionMode::InTemplate;
+                    self.template_modes.push(InsertionMode::InTemplate);
+                }
+                "head" => self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone())), //ignored
+                _ => self.pop_head_and_reprocess(Token::StartTag(tag)),
+            },
+            Token::EndTag(tag) => match tag.name.as_str() {
+                "head" => {
+                    self.open_elements.pop();
+                    self.mode = InsertionMode::AfterHead;
+                }
+                "body" | "html" | "br" => self.pop_head_and_reprocess(Token::EndTag(tag)),
+                "template" => self.close_template(),
+                _ => self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())), //ignored
+            },
+            token => self.pop_head_and_reprocess(token),
+        }
+    }
+
+    fn pop_head_and_reprocess(&mut self, token: Token) {
+        self.open_elements.pop();
+        self.reprocess(InsertionMode::AfterHead, token);
+    }
+
+    fn close_template(&mut self) {
+        if !self.stack_contains("template") {
+            self.parse_error(HtmlErrorKind::UnexpectedEndTag(String::from("template")));
+            return; //ignore
+        }
+        self.generate_all_implied_end_tags();
+        self.pop_until(&["template"]);
+        self.clear_formatting_to_marker();
+        self.template_modes.pop();
+        self.reset_insertion_mode();
+    }
+
+    fn in_head_noscript(&mut self, token: Token) {
+        match token {
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(ref tag) if tag.name == "html" => self.in_body(token.clone()),
+            Token::EndTag(ref tag) if tag.name == "noscript" => {
+                self.open_elements.pop();
+                self.mode = InsertionMode::InHead;
+            }
+            Token::Character(c) if is_whitespace(c) => self.in_head(token),
+            Token::Comment(_) => self.in_head(token),
+            Token::StartTag(ref tag)
+                if is_one_of(&tag.name, &["basefont", "bgsound", "link", "meta", "noframes", "style"]) =>
+            {
+                self.in_head(token.clone())
+            }
+            Token::StartTag(ref tag) if is_one_of(&tag.name, &["head", "noscript"]) => self.unexpected(&token), //ignored
+            Token::EndTag(ref tag) if tag.name != "br" => self.unexpected(&token), //ignored
+            token => {
+                self.unexpected(&token);
+                self.open_elements.pop();
+                self.reprocess(InsertionMode::InHead, token);
+            }
+        }
+    }
+
+    fn after_head(&mut self, token: Token) {
+        match token {
+            Token::Character(c) if is_whitespace(c) => self.insert_character(c),
+            Token::Comment(text) => self.insert_comment(text),
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(tag) => match tag.name.as_str() {
+                "html" => self.in_body(Token::StartTag(tag)),
+                "body" => {
+                    self.insert_html_element(tag);
+                    self.frameset_ok = false;
+                    self.mode = InsertionMode::InBody;
+                }
+                "frameset" => {
+                    self.insert_html_element(tag);
+                    self.mode = InsertionMode::InFrameset;
+                }
+                "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes" | "script" | "style" | "template"
+                | "title" => {
+                    //the element still belongs in the head
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    if let Some(head) = self.head_element {
+                        self.open_elements.push(head);
+                        self.in_head(Token::StartTag(tag));
+                        self.remove_from_stack(head);
+                    }
+                }
+                "head" => self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone())), //ignored
+                _ => self.insert_body_and_reprocess(Token::StartTag(tag)),
+            },
+            Token::EndTag(tag) => match tag.name.as_str() {
+                "template" => self.in_head(Token::EndTag(tag)),
+                "body" | "html" | "br" => self.insert_body_and_reprocess(Token::EndTag(tag)),
+                _ => self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())), //ignored
+            },
+            token => self.insert_body_and_reprocess(token),
+        }
+    }
+
+    fn insert_body_and_reprocess(&mut self, token: Token) {
+        self.insert_html_element(tag_named("body"));
+        self.reprocess(InsertionMode::InBody, token);
+    }
+
+    fn in_body(&mut self, token: Token) {
+        match token {
+            Token::Character('\0') => {} //already reported by the tokenizer, ignore
+            Token::Character(c) => {
+                self.reconstruct_active_formatting();
+                self.insert_character(c);
+                if !is_whitespace(c) {
+                    self.frameset_ok = false;
+                }
+            }
+            Token::Comment(text) => self.insert_comment(text),
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(tag) => self.in_body_start_tag(tag),
+            Token::EndTag(tag) => self.in_body_end_tag(tag),
+            Token::Eof => {
+                if !self.template_modes.is_empty() {
+                    self.in_template(Token::Eof);
+                } else {
+                    //stop parsing, whatever is still open stays where it is
+                    self.report_unclosed_element();
+                }
+            }
+        }
+    }
+
+    fn in_body_start_tag(&mut self, mut tag: Tag) {
+        match tag.name.as_str() {
+            "html" => {
+                //copy over any attributes the root doesn't have yet
+                self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                if !self.stack_contains("template") {
+                    let html = self.open_elements[0];
+                    self.merge_attributes(html, tag.attributes);
+                }
+            }
+            "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes" | "script" | "style" | "template"
+            | "title" => self.in_head(Token::StartTag(tag)),
+            "body" => {
+                //a second body only adds its attributes to the first one
+                self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                if self.open_elements.len() > 1 && self.is_html(self.open_elements[1], "body") && !self.stack_contains("template") {
+                    self.frameset_ok = false;
+                    let body = self.open_elements[1];
+                    self.merge_attributes(body, tag.attributes);
+                }
+            }
+            "frameset" => {
+                if self.open_elements.len() > 1 && self.is_html(self.open_elements[1], "body") && self.frameset_ok {
+                    let body = self.open_elements[1];
+                    self.detach(body);
+                    self.open_elements.truncate(1);
+                    self.insert_html_element(tag);
+                    self.mode = InsertionMode::InFrameset;
+                }
+            }
+            "address" | "article" | "aside" | "blockquote" | "center" | "details" | "dialog" | "dir" | "div"
+            | "dl" | "fieldset" | "figcaption" | "figure" | "footer" | "header" | "hgroup" | "main" | "menu"
+            | "nav" | "ol" | "p" | "search" | "section" | "summary" | "ul" => {
+                self.close_p_if_in_button_scope();
+                self.insert_html_element(tag);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.close_p_if_in_button_scope();
+                if is_heading(self.tag_name(self.current_node())) {
+                    //headings don't nest
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    self.open_elements.pop();
+                }
+                self.insert_html_element(tag);
+            }
+            "pre" | "listing" => {
+                self.close_p_if_in_button_scope();
+                self.insert_html_element(tag);
+                self.skip_newline = true;
+                self.frameset_ok = false;
+            }
+            "form" => {
+                if self.form_element.is_some() && !self.stack_contains("template") {
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    return; //ignore
+                }
+                self.close_p_if_in_button_scope();
+                let form = self.insert_html_element(tag);
+                if !self.stack_contains("template") {
+                    self.form_element = Some(form);
+                }
+            }
+            "li" => {
+                self.frameset_ok = false;
+                self.close_list_item(&["li"]);
+                self.close_p_if_in_button_scope();
+                self.insert_html_element(tag);
+            }
+            "dd" | "dt" => {
+                self.frameset_ok = false;
+                self.close_list_item(&["dd", "dt"]);
+                self.close_p_if_in_button_scope();
+                self.insert_html_element(tag);
+            }
+            "plaintext" => {
+                //nothing after <plaintext> is markup anymore, not even its own end tag
+                self.close_p_if_in_button_scope();
+                self.insert_html_element(tag);
+                self.tokenizer.set_state(State::PlainText);
+            }
+            "button" => {
+                if self.has_in_scope("button", Scope::Default) {
+                    //close the open button first
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&["button"]);
+                }
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+                self.frameset_ok = false;
+            }
+            "a" => {
+                if let Some(a) = self.formatting_element_after_marker("a") {
+                    //an <a> can't contain another one
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    self.adoption_agency("a");
+                    self.remove_from_formatting(a);
+                    self.remove_from_stack(a);
+                }
+                self.reconstruct_active_formatting();
+                let element = self.insert_html_element(tag.clone());
+                self.push_formatting(element, tag);
+            }
+            "b" | "big" | "code" | "em" | "font" | "i" | "s" | "small" | "strike" | "strong" | "tt" | "u" => {
+                self.reconstruct_active_formatting();
+                let element = self.insert_html_element(tag.clone());
+                self.push_formatting(element, tag);
+            }
+            "nobr" => {
+                self.reconstruct_active_formatting();
+                if self.has_in_scope("nobr", Scope::Default) {
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    self.adoption_agency("nobr");
+                    self.reconstruct_active_formatting();
+                }
+                let element = self.insert_html_element(tag.clone());
+                self.push_formatting(element, tag);
+            }
+            "applet" | "marquee" | "object" => {
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+                self.active_formatting.push(FormattingEntry::Marker);
+                self.frameset_ok = false;
+            }
+            "table" => {
+                //in quirks mode a table can sit inside a paragraph
+                if self.quirks_mode != QuirksMode::Quirks {
+                    self.close_p_if_in_button_scope();
+                }
+                self.insert_html_element(tag);
+                self.frameset_ok = false;
+                self.mode = InsertionMode::InTable;
+            }
+            "area" | "br" | "embed" | "img" | "keygen" | "wbr" => {
+                self.reconstruct_active_formatting();
+                self.insert_void_element(tag);
+                self.frameset_ok = false;
+            }
+            "input" => {
+                self.reconstruct_active_formatting();
+                let hidden = is_hidden_input(&tag);
+                self.insert_void_element(tag);
+                if !hidden {
+                    self.frameset_ok = false;
+                }
+            }
+            "param" | "source" | "track" => self.insert_void_element(tag),
+            "hr" => {
+                self.close_p_if_in_button_scope();
+                self.insert_void_element(tag);
+                self.frameset_ok = false;
+            }
+            "image" => {
+                //<image> is an old alias for <img>
+                self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                tag.name = String::from("img");
+                self.in_body_start_tag(tag);
+            }
+            "textarea" => {
+                self.parse_text_element(tag, State::RcData);
+                self.skip_newline = true;
+                self.frameset_ok = false;
+            }
+            "xmp" => {
+                self.close_p_if_in_button_scope();
+                self.reconstruct_active_formatting();
+                self.frameset_ok = false;
+                self.parse_text_element(tag, State::RawText);
+            }
+            "iframe" => {
+                self.frameset_ok = false;
+                self.parse_text_element(tag, State::RawText);
+            }
+            "noembed" => self.parse_text_element(tag, State::RawText),
+            "select" => {
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+                self.frameset_ok = false;
+                self.mode = match self.mode {
+                    InsertionMode::InTable
+                    | InsertionMode::InCaption
+                    | InsertionMode::InTableBody
+                    | InsertionMode::InRow
+                    | InsertionMode::InCell => InsertionMode::InSelectInTable,
+                    _ => InsertionMode::InSelect,
+                };
+            }
+            "optgroup" | "option" => {
+                if self.is_html(self.current_node(), "option") {
+                    self.open_elements.pop();
+                }
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+            }
+            "rb" | "rtc" => {
+                if self.has_in_scope("ruby", Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                }
+                self.insert_html_element(tag);
+            }
+            "rp" | "rt" => {
+                if self.has_in_scope("ruby", Scope::Default) {
+                    self.generate_implied_end_tags(Some("rtc"));
+                }
+                self.insert_html_element(tag);
+            }
+            "math" => {
+                self.reconstruct_active_formatting();
+                self.insert_foreign_element(tag, Namespace::MathMl);
+            }
+            "svg" => {
+                self.reconstruct_active_formatting();
+                self.insert_foreign_element(tag, Namespace::Svg);
+            }
+            "caption" | "col" | "colgroup" | "frame" | "head" | "tbody" | "td" | "tfoot" | "th" | "thead" | "tr" => {
+                //these only mean something inside their own context
+                self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+            }
+            _ => {
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+            }
+        }
+    }
+
+    fn in_body_end_tag(&mut self, tag: Tag) {
+        match tag.name.as_str() {
+            "template" => self.in_head(Token::EndTag(tag)),
+            "body" => {
+                if self.has_in_scope("body", Scope::Default) {
+                    self.mode = InsertionMode::AfterBody;
+                } else {
+                    self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())); //nothing to close, ignored
+                }
+            }
+            "html" => {
+                if self.has_in_scope("body", Scope::Default) {
+                    self.reprocess(InsertionMode::AfterBody, Token::EndTag(tag));
+                } else {
+                    self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())); //nothing to close, ignored
+                }
+            }
+            "address" | "article" | "aside" | "blockquote" | "button" | "center" | "details" | "dialog" | "dir"
+            | "div" | "dl" | "fieldset" | "figcaption" | "figure" | "footer" | "header" | "hgroup" | "listing"
+            | "main" | "menu" | "nav" | "ol" | "pre" | "search" | "section" | "summary" | "ul" => {
+                if self.has_in_scope(&tag.name, Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&[tag.name.as_str()]);
+                } else {
+                    self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())); //nothing to close, ignored
+                }
+            }
+            "form" => {
+                if !self.stack_contains("template") {
+                    let form = self.form_element.take();
+                    match form {
+                        Some(form) if self.element_in_scope(form) => {
+                            self.generate_implied_end_tags(None);
+                            self.remove_from_stack(form);
+                        }
+                        _ => self.parse_error(HtmlErrorKind::UnexpectedEndTag(String::from("form"))), //ignored
+                    }
+                } else if self.has_in_scope("form", Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&["form"]);
+                }
+            }
+            "p" => {
+                if !self.has_in_scope("p", Scope::Button) {
+                    //a stray </p> creates an empty paragraph
+                    self.parse_error(HtmlErrorKind::UnexpectedEndTag(String::from("p")));
+                    self.insert_html_element(tag_named("p"));
+                }
+                self.close_p_element();
+            }
+            "li" => {
+                if self.has_in_scope("li", Scope::ListItem) {
+                    self.generate_implied_end_tags(Some("li"));
+                    self.pop_until(&["li"]);
+                } else {
+                    self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())); //nothing to close, ignored
+                }
+            }
+            "dd" | "dt" => {
+                if self.has_in_scope(&tag.name, Scope::Default) {
+                    self.generate_implied_end_tags(Some(&tag.name));
+                    self.pop_until(&[tag.name.as_str()]);
+                } else {
+                    self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())); //nothing to close, ignored
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let headings = ["h1", "h2", "h3", "h4", "h5", "h6"];
+                if headings.iter().any(|h| self.has_in_scope(h, Scope::Default)) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&headings);
+                } else {
+                    self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())); //nothing to close, ignored
+                }
+            }
+            "a" | "b" | "big" | "code" | "em" | "font" | "i" | "nobr" | "s" | "small" | "strike" | "strong"
+            | "tt" | "u" => {
+                if !self.adoption_agency(&tag.name) {
+                    self.any_other_end_tag(&tag.name);
+                }
+            }
+            "applet" | "marquee" | "object" => {
+                if self.has_in_scope(&tag.name, Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&[tag.name.as_str()]);
+                    self.clear_formatting_to_marker();
+                } else {
+                    self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())); //nothing to close, ignored
+                }
+            }
+            "br" => {
+                //</br> is treated like <br>
+                self.parse_error(HtmlErrorKind::UnexpectedEndTag(String::from("br")));
+                self.in_body_start_tag(tag_named("br"));
+            }
+            _ => self.any_other_end_tag(&tag.name),
+        }
+    }
+
+    //Walk down the stack looking for the element to close, giving up if a special element is in the way
+    fn any_other_end_tag(&mut self, name: &str) {
+        for index in (0..self.open_elements.len()).rev() {
+            let node = self.open_elements[index];
+            if self.is_html(node, name) {
+                self.generate_implied_end_tags(Some(name));
+                self.open_elements.truncate(index);
+                return;
+            }
+            if self.is_special_node(node) {
+                self.parse_error(HtmlErrorKind::UnexpectedEndTag(name.to_string()));
+                return; //ignore
+            }
+        }
+    }
+
+    //A new <li> (or <dd>/<dt>) implicitly closes the previous one
+    fn close_list_item(&mut self, names: &[&str]) {
+        for index in (0..self.open_elements.len()).rev() {
+            let node = self.open_elements[index];
+            let name = self.tag_name(node).to_string();
+            if is_one_of(&name, names) {
+                self.generate_implied_end_tags(Some(&name));
+                self.pop_until(&[name.as_str()]);
+                return;
+            }
+            if self.is_special_node(node) && !is_one_of(&name, &["address", "div", "p"]) {
+                return;
+            }
+        }
+    }
+
+    //Inside title, textarea, style, script ... the tokenizer only produces characters until the matching end tag
+    fn text(&mut self, token: Token) {
+        match token {
+            Token::Character(c) => self.insert_character(c),
+            Token::Eof => {
+                //the file ended inside the element
+                let name = self.tag_name(self.current_node()).to_string();
+                self.parse_error(HtmlErrorKind::UnclosedElement(name));
+                self.open_elements.pop();
+                let original = self.original_mode;
+                self.reprocess(original, Token::Eof);
+            }
+            _ => {
+                self.open_elements.pop();
+                self.mode = self.original_mode;
+            }
+        }
+    }
+
+    fn in_table(&mut self, token: Token) {
+        match token {
+            Token::Character(_)
+                if is_one_of(self.tag_name(self.current_node()), &["table", "tbody", "template", "tfoot", "thead", "tr"]) =>
+            {
+                self.pending_table_text.clear();
+                self.original_mode = self.mode;
+                self.reprocess(InsertionMode::InTableText, token);
+            }
+            Token::Comment(text) => self.insert_comment(text),
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(tag) => match tag.name.as_str() {
+                "caption" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.active_formatting.push(FormattingEntry::Marker);
+                    self.insert_html_element(tag);
+                    self.mode = InsertionMode::InCaption;
+                }
+                "colgroup" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_html_element(tag);
+                    self.mode = InsertionMode::InColumnGroup;
+                }
+                "col" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_html_element(tag_named("colgroup"));
+                    self.reprocess(InsertionMode::InColumnGroup, Token::StartTag(tag));
+                }
+                "tbody" | "tfoot" | "thead" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_html_element(tag);
+                    self.mode = InsertionMode::InTableBody;
+                }
+                "td" | "th" | "tr" => {
+                    self.clear_stack_back_to(&["table", "template", "html"]);
+                    self.insert_html_element(tag_named("tbody"));
+                    self.reprocess(InsertionMode::InTableBody, Token::StartTag(tag));
+                }
+                "table" => {
+                    //a table start tag inside a table closes the first table
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    if self.has_in_scope("table", Scope::Table) {
+                        self.pop_until(&["table"]);
+                        self.reset_insertion_mode();
+                        self.process(Token::StartTag(tag));
+                    }
+                }
+                "style" | "script" | "template" => self.in_head(Token::StartTag(tag)),
+                "input" if is_hidden_input(&tag) => {
+                    //hidden inputs may stay inside the table
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    self.insert_void_element(tag);
+                }
+                "form" => {
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    if self.form_element.is_none() && !self.stack_contains("template") {
+                        let form = self.insert_html_element(tag);
+                        self.form_element = Some(form);
+                        self.open_elements.pop();
+                    }
+                }
+                _ => self.foster_parent(Token::StartTag(tag)),
+            },
+            Token::EndTag(tag) => match tag.name.as_str() {
+                "table" => {
+                    if self.has_in_scope("table", Scope::Table) {
+                        self.pop_until(&["table"]);
+                        self.reset_insertion_mode();
+                    }
+                }
+                "body" | "caption" | "col" | "colgroup" | "html" | "tbody" | "td" | "tfoot" | "th" | "thead"
+                | "tr" => self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())), //ignored
+                "template" => self.in_head(Token::EndTag(tag)),
+                _ => self.foster_parent(Token::EndTag(tag)),
+            },
+            Token::Eof => self.in_body(Token::Eof),
+            token => self.foster_parent(token),
+        }
+    }
+
+    //Content that isn't allowed directly in a table gets moved in front of the table
+    fn foster_parent(&mut self, token: Token) {
+        //pending table text is reported once by in_table_text, not for every character
+        if let Token::Character(_) = token {
+        } else {
+            self.unexpected(&token);
+        }
+        self.foster_parenting = true;
+        self.in_body(token);
+        self.foster_parenting = false;
+    }
+
+    fn in_table_text(&mut self, token: Token) {
+        match token {
+            Token::Character('\0') => {} //already reported by the tokenizer, ignore
+            Token::Character(c) => self.pending_table_text.push((c, self.token_span)),
+            token => {
+                //the pending characters keep their own spans, put back the current one once they're in
+                let token_span = self.token_span;
+                let pending = std::mem::take(&mut self.pending_table_text);
+                if let Some(&(c, span)) = pending.iter().find(|&&(c, _)| !is_whitespace(c)) {
+                    //non whitespace text in a table is foster parented
+                    self.token_span = span;
+                    self.parse_error(HtmlErrorKind::UnexpectedCharacter(c));
+                    for (c, span) in pending {
+                        self.token_span = span;
+                        self.foster_parent(Token::Character(c));
+                    }
+                } else {
+                    for (c, span) in pending {
+                        self.token_span = span;
+                        self.insert_character(c);
+                    }
+                }
+                self.token_span = token_span;
+                let original = self.original_mode;
+                self.reprocess(original, token);
+            }
+        }
+    }
+
+    fn in_caption(&mut self, token: Token) {
+        match token {
+            Token::EndTag(ref tag) if tag.name == "caption" => {
+                self.close_caption();
+            }
+            Token::StartTag(ref tag)
+                if is_one_of(&tag.name, &["caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr"]) =>
+            {
+                if self.close_caption() {
+                    self.process(token);
+                }
+            }
+            Token::EndTag(ref tag) if tag.name == "table" => {
+                if self.close_caption() {
+                    self.process(token);
+                }
+            }
+            Token::EndTag(ref tag)
+                if is_one_of(&tag.name, &["body", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead", "tr"]) => self.unexpected(&token), //ignored
+            token => self.in_body(token),
+        }
+    }
+
+    fn close_caption(&mut self) -> bool {
+        if !self.has_in_scope("caption", Scope::Table) {
+            self.parse_error(HtmlErrorKind::UnexpectedEndTag(String::from("caption")));
+            return false; //ignore
+        }
+        self.generate_implied_end_tags(None);
+        self.pop_until(&["caption"]);
+        self.clear_formatting_to_marker();
+        self.mode = InsertionMode::InTable;
+        true
+    }
+
+    fn in_column_group(&mut self, token: Token) {
+        match token {
+            Token::Character(c) if is_whitespace(c) => self.insert_character(c),
+            Token::Comment(text) => self.insert_comment(text),
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(ref tag) if tag.name == "html" => self.in_body(token.clone()),
+            Token::StartTag(ref tag) if tag.name == "col" => self.insert_void_element(tag.clone()),
+            Token::EndTag(ref tag) if tag.name == "colgroup" => {
+                if self.is_html(self.current_node(), "colgroup") {
+                    self.open_elements.pop();
+                    self.mode = InsertionMode::InTable;
+                }
+            }
+            Token::EndTag(ref tag) if tag.name == "col" => self.unexpected(&token), //ignored
+            Token::StartTag(ref tag) if tag.name == "template" => self.in_head(token.clone()),
+            Token::EndTag(ref tag) if tag.name == "template" => self.in_head(token.clone()),
+            Token::Eof => self.in_body(token),
+            token => {
+                if self.is_html(self.current_node(), "colgroup") {
+                    self.open_elements.pop();
+                    self.reprocess(InsertionMode::InTable, token);
+                }
+            }
+        }
+    }
+
+    fn in_table_body(&mut self, token: Token) {
+        match token {
+            Token::StartTag(ref tag) if tag.name == "tr" => {
+                self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.insert_html_element(tag.clone());
+                self.mode = InsertionMode::InRow;
+            }
+            Token::StartTag(ref tag) if is_one_of(&tag.name, &["th", "td"]) => {
+                //the row is implied
+                self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.insert_html_element(tag_named("tr"));
+                self.reprocess(InsertionMode::InRow, token);
+            }
+            Token::EndTag(ref tag) if is_one_of(&tag.name, &["tbody", "tfoot", "thead"]) => {
+                if self.has_in_scope(&tag.name, Scope::Table) {
+                    self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                    self.open_elements.pop();
+                    self.mode = InsertionMode::InTable;
+                }
+            }
+            Token::StartTag(ref tag) if is_one_of(&tag.name, &["caption", "col", "colgroup", "tbody", "tfoot", "thead"]) => {
+                self.close_table_body_and_reprocess(token);
+            }
+            Token::EndTag(ref tag) if tag.name == "table" => self.close_table_body_and_reprocess(token),
+            Token::EndTag(ref tag)
+                if is_one_of(&tag.name, &["body", "caption", "col", "colgroup", "html", "td", "th", "tr"]) => self.unexpected(&token), //ignored
+            token => self.in_table(token),
+        }
+    }
+
+    fn close_table_body_and_reprocess(&mut self, token: Token) {
+        if ["tbody", "thead", "tfoot"].iter().any(|name| self.has_in_scope(name, Scope::Table)) {
+            self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+            self.open_elements.pop();
+            self.reprocess(InsertionMode::InTable, token);
+        }
+    }
+
+    fn in_row(&mut self, token: Token) {
+        match token {
+            Token::StartTag(ref tag) if is_one_of(&tag.name, &["th", "td"]) => {
+                self.clear_stack_back_to(&["tr", "template", "html"]);
+                self.insert_html_element(tag.clone());
+                self.mode = InsertionMode::InCell;
+                self.active_formatting.push(FormattingEntry::Marker);
+            }
+            Token::EndTag(ref tag) if tag.name == "tr" => {
+                self.close_row();
+            }
+            Token::StartTag(ref tag)
+                if is_one_of(&tag.name, &["caption", "col", "colgroup", "tbody", "tfoot", "thead", "tr"]) =>
+            {
+                if self.close_row() {
+                    self.process(token);
+                }
+            }
+            Token::EndTag(ref tag) if tag.name == "table" => {
+                if self.close_row() {
+                    self.process(token);
+                }
+            }
+            Token::EndTag(ref tag) if is_one_of(&tag.name, &["tbody", "tfoot", "thead"]) => {
+                if self.has_in_scope(&tag.name, Scope::Table) && self.close_row() {
+                    self.process(token);
+                }
+            }
+            Token::EndTag(ref tag) if is_one_of(&tag.name, &["body", "caption", "col", "colgroup", "html", "td", "th"]) => self.unexpected(&token), //ignored
+            token => self.in_table(token),
+        }
+    }
+
+    fn close_row(&mut self) -> bool {
+        if !self.has_in_scope("tr", Scope::Table) {
+            self.parse_error(HtmlErrorKind::UnexpectedEndTag(String::from("tr")));
+            return false; //ignore
+        }
+        self.clear_stack_back_to(&["tr", "template", "html"]);
+        self.open_elements.pop();
+        self.mode = InsertionMode::InTableBody;
+        true
+    }
+
+    fn in_cell(&mut self, token: Token) {
+        match token {
+            Token::EndTag(ref tag) if is_one_of(&tag.name, &["td", "th"]) => {
+                if self.has_in_scope(&tag.name, Scope::Table) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&[tag.name.as_str()]);
+                    self.clear_formatting_to_marker();
+                    self.mode = InsertionMode::InRow;
+                }
+            }
+            Token::StartTag(ref tag)
+                if is_one_of(&tag.name, &["caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr"]) =>
+            {
+                if self.has_in_scope("td", Scope::Table) || self.has_in_scope("th", Scope::Table) {
+                    self.close_cell();
+                    self.process(token);
+                }
+            }
+            Token::EndTag(ref tag) if is_one_of(&tag.name, &["body", "caption", "col", "colgroup", "html"]) => self.unexpected(&token), //ignored
+            Token::EndTag(ref tag) if is_one_of(&tag.name, &["table", "tbody", "tfoot", "thead", "tr"]) => {
+                if self.has_in_scope(&tag.name, Scope::Table) {
+                    self.close_cell();
+                    self.process(token);
+                }
+            }
+            token => self.in_body(token),
+        }
+    }
+
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags(None);
+        self.pop_until(&["td", "th"]);
+        self.clear_formatting_to_marker();
+        self.mode = InsertionMode::InRow;
+    }
+
+    fn in_select(&mut self, token: Token) {
+        match token {
+            Token::Character('\0') => {} //already reported by the tokenizer, ignore
+            Token::Character(c) => self.insert_character(c),
+            Token::Comment(text) => self.insert_comment(text),
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(tag) => match tag.name.as_str() {
+                "html" => self.in_body(Token::StartTag(tag)),
+                "option" => {
+                    if self.is_html(self.current_node(), "option") {
+                        self.open_elements.pop();
+                    }
+                    self.insert_html_element(tag);
+                }
+                "optgroup" | "hr" => {
+                    if self.is_html(self.current_node(), "option") {
+                        self.open_elements.pop();
+                    }
+                    if self.is_html(self.current_node(), "optgroup") {
+                        self.open_elements.pop();
+                    }
+                    if tag.name == "hr" {
+                        self.insert_void_element(tag);
+                    } else {
+                        self.insert_html_element(tag);
+                    }
+                }
+                "select" => {
+                    //a nested select closes the open one
+                    self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                    if self.has_in_scope("select", Scope::Select) {
+                        self.pop_until(&["select"]);
+                        self.reset_insertion_mode();
+                    }
+                }
+                "input" | "keygen" | "textarea" => {
+                    if self.has_in_scope("select", Scope::Select) {
+                        self.pop_until(&["select"]);
+                        self.reset_insertion_mode();
+                        self.process(Token::StartTag(tag));
+                    }
+                }
+                "script" | "template" => self.in_head(Token::StartTag(tag)),
+                _ => self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone())), //ignored
+            },
+            Token::EndTag(tag) => match tag.name.as_str() {
+                "optgroup" => {
+                    let len = self.open_elements.len();
+                    if self.is_html(self.current_node(), "option")
+                        && len > 1
+                        && self.is_html(self.open_elements[len - 2], "optgroup")
+                    {
+                        self.open_elements.pop();
+                    }
+                    if self.is_html(self.current_node(), "optgroup") {
+                        self.open_elements.pop();
+                    }
+                }
+                "option" => {
+                    if self.is_html(self.current_node(), "option") {
+                        self.open_elements.pop();
+                    }
+                }
+                "select" => {
+                    if self.has_in_scope("select", Scope::Select) {
+                        self.pop_until(&["select"]);
+                        self.reset_insertion_mode();
+                    }
+                }
+                "template" => self.in_head(Token::EndTag(tag)),
+                _ => self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone())), //ignored
+            },
+            Token::Eof => self.in_body(Token::Eof),
+        }
+    }
+
+    fn in_select_in_table(&mut self, token: Token) {
+        let table_tags = ["caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th"];
+        match token {
+            Token::StartTag(ref tag) if is_one_of(&tag.name, &table_tags) => {
+                //table markup closes the select
+                self.parse_error(HtmlErrorKind::UnexpectedStartTag(tag.name.clone()));
+                self.pop_until(&["select"]);
+                self.reset_insertion_mode();
+                self.process(token);
+            }
+            Token::EndTag(ref tag) if is_one_of(&tag.name, &table_tags) => {
+                if self.has_in_scope(&tag.name, Scope::Table) {
+                    self.pop_until(&["select"]);
+                    self.reset_insertion_mode();
+                    self.process(token);
+                }
+            }
+            token => self.in_select(token),
         }
     }
 
-    //Main entry point for our HTML parser
-    //HTML can accept inavalid structure and invalid syntax 
-    //Rather than throw error on invalid syntax, we want them to correct that invalid syntax
-    pub fn parse_nodes(&mut self) -> Vec<Node> {
-        let mut nodes = Vec::new();
+    fn in_template(&mut self, token: Token) {
+        match token {
+            Token::Character(_) | Token::Comment(_) | Token::Doctype(_) => self.in_body(token),
+            Token::StartTag(ref tag)
+                if is_one_of(&tag.name, &["base", "basefont", "bgsound", "link", "meta", "noframes", "script", "style", "template", "title"]) =>
+            {
+                self.in_head(token.clone())
+            }
+            Token::EndTag(ref tag) if tag.name == "template" => self.in_head(token.clone()),
+            Token::StartTag(ref tag) => {
+                //The first real element decides what kind of content the template holds
+                let mode = match tag.name.as_str() {
+                    "caption" | "colgroup" | "tbody" | "tfoot" | "thead" => InsertionMode::InTable,
+                    "col" => InsertionMode::InColumnGroup,
+                    "tr" => InsertionMode::InTableBody,
+                    "td" | "th" => InsertionMode::InRow,
+                    _ => InsertionMode::InBody,
+                };
+                self.template_modes.pop();
+                self.template_modes.push(mode);
+                self.reprocess(mode, token);
+            }
+            Token::EndTag(tag) => self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name)), //ignored
+            Token::Eof => {
+                if self.stack_contains("template") {
+                    self.parse_error(HtmlErrorKind::UnclosedElement(String::from("template")));
+                    self.pop_until(&["template"]);
+                    self.clear_formatting_to_marker();
+                    self.template_modes.pop();
+                    self.reset_insertion_mode();
+                    self.process(Token::Eof);
+                }
+            }
+        }
+    }
+
+    fn after_body(&mut self, token: Token) {
+        match token {
+            Token::Character(c) if is_whitespace(c) => self.in_body(token),
+            Token::Comment(text) => {
+                let html = self.open_elements[0];
+                self.insert_comment_in(text, html);
+            }
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(ref tag) if tag.name == "html" => self.in_body(token.clone()),
+            Token::EndTag(ref tag) if tag.name == "html" => {
+                if self.context_element.is_some() {
+                    self.unexpected(&token); //a fragment can't close its root, ignored
+                } else {
+                    self.mode = InsertionMode::AfterAfterBody;
+                }
+            }
+            Token::Eof => {}
+            token => {
+                self.unexpected(&token);
+                self.reprocess(InsertionMode::InBody, token);
+            }
+        }
+    }
+
+    fn in_frameset(&mut self, token: Token) {
+        match token {
+            Token::Character(c) if is_whitespace(c) => self.insert_character(c),
+            Token::Comment(text) => self.insert_comment(text),
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(ref tag) if tag.name == "html" => self.in_body(token.clone()),
+            Token::StartTag(ref tag) if tag.name == "frameset" => {
+                self.insert_html_element(tag.clone());
+            }
+            Token::StartTag(ref tag) if tag.name == "frame" => self.insert_void_element(tag.clone()),
+            Token::EndTag(ref tag) if tag.name == "frameset" => {
+                if self.open_elements.len() > 1 {
+                    self.open_elements.pop();
+                    if !self.is_html(self.current_node(), "frameset") {
+                        self.mode = InsertionMode::AfterFrameset;
+                    }
+                }
+            }
+            Token::StartTag(ref tag) if tag.name == "noframes" => self.in_head(token.clone()),
+            Token::Eof => {}
+            token => self.unexpected(&token), //ignored
+        }
+    }
 
-        while self.chars.peek().is_some() {  //Check if we have some data, will return true if Option is some and false if its not 
-            self.consume_while(char::is_whitespace);    //Check if char is whitespace, if its consume/remove it
-            if self.chars.peek().map_or(false, |c| *c == '<' ) { //if c is opening tag
-                self.chars.next();          //move forward
-                
-                if self.chars.peek().map_or(false, |c| *c == '/') { //If its backslash
-                    self.chars.next();      //move forward
-                    self.consume_while(char::is_whitespace);  //Check if char is whitespace, if its consume/remove it
+    fn after_frameset(&mut self, token: Token) {
+        match token {
+            Token::Character(c) if is_whitespace(c) => self.insert_character(c),
+            Token::Comment(text) => self.insert_comment(text),
+            Token::StartTag(ref tag) if tag.name == "html" => self.in_body(token.clone()),
+            Token::EndTag(ref tag) if tag.name == "html" => self.mode = InsertionMode::AfterAfterFrameset,
+            Token::StartTag(ref tag) if tag.name == "noframes" => self.in_head(token.clone()),
+            Token::Eof => {}
+            token => self.unexpected(&token), //ignored
+        }
+    }
 
-                    //Check if we have valid tagname inside HTML as long its bacis word its valid tag name
-                    let close_tag_name = self.consume_while(is_valid_tag_name);
+    fn after_after_body(&mut self, token: Token) {
+        match token {
+            Token::Comment(text) => self.insert_comment_in(text, DOCUMENT),
+            Token::Doctype(_) => self.in_body(token),
+            Token::Character(c) if is_whitespace(c) => self.in_body(token),
+            Token::StartTag(ref tag) if tag.name == "html" => self.in_body(token.clone()),
+            Token::Eof => {}
+            token => {
+                self.unexpected(&token);
+                self.reprocess(InsertionMode::InBody, token);
+            }
+        }
+    }
 
-                    self.consume_while(|x| x != '>');
-                    self.chars.next();
+    fn after_after_frameset(&mut self, token: Token) {
+        match token {
+            Token::Comment(text) => self.insert_comment_in(text, DOCUMENT),
+            Token::Doctype(_) => self.in_body(token),
+            Token::Character(c) if is_whitespace(c) => self.in_body(token),
+            Token::StartTag(ref tag) if tag.name == "html" => self.in_body(token.clone()),
+            Token::StartTag(ref tag) if tag.name == "noframes" => self.in_head(token.clone()),
+            Token::Eof => {}
+            token => self.unexpected(&token), //ignored
+        }
+    }
 
-                    self.node_q.push(close_tag_name);
-                    break;
-                
-                } else if self.chars.peek().map_or(false, |c| *c == '!') { //means we looking at comment
-                    self.chars.next();
-                    nodes.push(self.parse_comment_node());
-                
-                } else {    //if we dont have appropriate looking HTML for a comment or for a normal element
-                    let mut node = self.parse_node();
-                    //Check what length of our node are
-                    let insert_index = nodes.len();
-
-                    //Match on node type
-                    match node.node_type {
-                        //Element type
-                        NodeType::Element(ref e) => if self.node_q.len() > 0 {
-                            let assumed_tag = self.node_q.remove(0); //remove front of our node q
-
-                            if e.tag_name != assumed_tag {      //check if they aren't the same
-                                nodes.append(&mut node.children); //append node children to our nodes
-                                self.node_q.insert(0, assumed_tag); //insert our assumed tag into our node q
-                            }
-                        },
-                        _ => {},
+    //ANCHOR Foreign Content
+    //Inside <svg> and <math> any tag can go anywhere, names keep their case and "/>" closes the element
+    //https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+    fn in_foreign_content(&mut self, token: Token) {
+        match token {
+            Token::Character('\0') => self.insert_character('\u{FFFD}'), //already reported by the tokenizer
+            Token::Character(c) => {
+                self.insert_character(c);
+                if !is_whitespace(c) {
+                    self.frameset_ok = false;
+                }
+            }
+            Token::Comment(text) => self.insert_comment(text),
+            Token::Doctype(_) => self.parse_error(HtmlErrorKind::UnexpectedDoctype), //ignored
+            Token::StartTag(ref tag) if is_breakout_tag(tag) => self.break_out_of_foreign_content(token.clone()),
+            Token::EndTag(ref tag) if tag.name == "br" || tag.name == "p" => self.break_out_of_foreign_content(token.clone()),
+            Token::StartTag(mut tag) => {
+                //the new element goes into the same namespace as the element it's in
+                let namespace = self.namespace(self.adjusted_current_node()).unwrap_or(Namespace::Html);
+                if namespace == Namespace::Svg {
+                    if let Some(name) = adjust_svg_tag_name(&tag.name) {
+                        tag.name = name.to_string();
                     }
+                }
+                self.insert_foreign_element(tag, namespace);
+            }
+            Token::EndTag(tag) => self.foreign_end_tag(tag),
+            Token::Eof => {
+                //never sent here by the dispatcher, the insertion mode deals with the end of the file
+                let mode = self.mode;
+                self.process_using(mode, Token::Eof);
+            }
+        }
+    }
 
-                    nodes.insert(insert_index, node);
-                } 
-            } else {    //if dont have '>', we're not running into an HTML element instead we're running into block of text
-                nodes.push(self.parse_text_node());
+    //An HTML tag like <p> or <table> inside <svg> closes the foreign elements and is handled as HTML
+    fn break_out_of_foreign_content(&mut self, token: Token) {
+        self.unexpected(&token);
+        while let Some(&node) = self.open_elements.last() {
+            if self.is_html_element(node) || self.is_mathml_text_integration_point(node) || self.is_html_integration_point(node) {
+                break;
             }
+            self.open_elements.pop();
         }
+        let mode = self.mode;
+        self.process_using(mode, token);
+    }
+
+    //Close the nearest open element with that name, case doesn't matter, an HTML element in the way hands
+    //the end tag over to the insertion mode
+    fn foreign_end_tag(&mut self, tag: Tag) {
+        let mut index = self.open_elements.len() - 1;
+        if !self.local_name(self.open_elements[index]).eq_ignore_ascii_case(&tag.name) {
+            self.parse_error(HtmlErrorKind::UnexpectedEndTag(tag.name.clone()));
+        }
+        while index > 0 {
+            if self.local_name(self.open_elements[index]).eq_ignore_ascii_case(&tag.name) {
+                self.open_elements.truncate(index);
+                return;
+            }
+            index -= 1;
+            if self.is_html_element(self.open_elements[index]) {
+                let mode = self.mode;
+                self.process_using(mode, Token::EndTag(tag));
+                return;
+            }
+        }
+    }
+
+    //ANCHOR Errors
+    fn parse_error(&mut self, kind: HtmlErrorKind) {
+        self.errors.push(HtmlParseError::new(kind, self.token_span));
+    }
+
+    //Only elements whose end tag can be left out may still be open at the end of the file
+    fn report_unclosed_element(&mut self) {
+        let optional = [
+            "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc", "tbody", "td", "tfoot", "th", "thead",
+            "tr", "body", "html",
+        ];
+        let unclosed = self
+            .open_elements
+            .iter()
+            .find(|&&h| !(self.is_html_element(h) && is_one_of(self.tag_name(h), &optional)))
+            .map(|&h| self.local_name(h));
+        if let Some(name) = unclosed.map(|name| name.to_string()) {
+            self.parse_error(HtmlErrorKind::UnclosedElement(name));
+        }
+    }
+
+    //Report a token that isn't allowed where it showed up
+    fn unexpected(&mut self, token: &Token) {
+        let kind = match *token {
+            Token::Doctype(_) => HtmlErrorKind::UnexpectedDoctype,
+            Token::StartTag(ref tag) => HtmlErrorKind::UnexpectedStartTag(tag.name.clone()),
+            Token::EndTag(ref tag) => HtmlErrorKind::UnexpectedEndTag(tag.name.clone()),
+            Token::Character(c) => HtmlErrorKind::UnexpectedCharacter(c),
+            Token::Comment(_) => return, //comments are allowed everywhere
+            Token::Eof => HtmlErrorKind::UnexpectedEof,
+        };
+        self.parse_error(kind);
+    }
+
+    //ANCHOR Tree Operations
+    fn new_node(&mut self, data: BuildData) -> Handle {
+        let span = self.token_span;
+        self.nodes.push(BuildNode { data, parent: None, children: Vec::new(), span });
+        self.nodes.len() - 1
+    }
+
+    fn create_element(&mut self, tag: Tag) -> Handle {
+        self.create_element_in(tag, Namespace::Html)
+    }
+
+    fn create_element_in(&mut self, tag: Tag, namespace: Namespace) -> Handle {
+        self.new_node(BuildData::Element(Tag { name: tag.name, self_closing: false, attributes: tag.attributes }, namespace))
+    }
+
+    fn append(&mut self, parent: Handle, child: Handle) {
+        self.insert_before(parent, child, None);
+    }
 
-        nodes
+    fn insert_before(&mut self, parent: Handle, child: Handle, before: Option<Handle>) {
+        self.detach(child);
+        let index = match before {
+            Some(sibling) => self.child_index(parent, sibling),
+            None => self.nodes[parent].children.len(),
+        };
+        self.nodes[parent].children.insert(index, child);
+        self.nodes[child].parent = Some(parent);
     }
 
-    fn parse_node(&mut self) -> Node {
-        let tagname = self.consume_while(is_valid_tag_name); //check tag name valid
-        let attributes = self.parse_attributes();
+    fn detach(&mut self, child: Handle) {
+        if let Some(parent) = self.nodes[child].parent.take() {
+            self.nodes[parent].children.retain(|&c| c != child);
+        }
+    }
 
-        //Create new element data
-        let elem = ElementData::new(tagname, attributes);   //Main
-        let children = self.parse_nodes();                  //Children
-        Node::new(NodeType::Element(elem), children)        //Return it
+    fn child_index(&self, parent: Handle, child: Handle) -> usize {
+        self.nodes[parent].children.iter().position(|&c| c == child).unwrap_or(self.nodes[parent].children.len())
     }
 
-    fn parse_text_node(&mut self) -> Node {
-        let mut text_content = String::new();   //Empty String
+    //Where a new node goes: normally the end of the current node, but with foster parenting
+    //content that ended up in a table is moved right before that table
+    fn appropriate_place(&self, target: Handle) -> (Handle, Option<Handle>) {
+        if self.foster_parenting && is_one_of(self.tag_name(target), &["table", "tbody", "tfoot", "thead", "tr"]) {
+            let last_template = self.open_elements.iter().rposition(|&h| self.is_html(h, "template"));
+            let last_table = self.open_elements.iter().rposition(|&h| self.is_html(h, "table"));
 
-        //Iterator thorugh all characters & peek inside checking if character is NOT EQUAL to '<' Open HTML tag
-        while self.chars.peek().map_or(false, |c| *c != '<' ) {
-            let whitespace = self.consume_while(char::is_whitespace);   //Consume all whitespace
-            if whitespace.len() > 0 {       //if white space more than 0
-                text_content.push(' ');     //take & push 1 single space
-                //"I   Love   You   Man!" -> "I Love You Man!" | will delete spaces & make it just 1
+            match (last_template, last_table) {
+                (Some(template), Some(table)) if template > table => (self.open_elements[template], None),
+                (Some(template), None) => (self.open_elements[template], None),
+                (_, None) => (self.open_elements[0], None),
+                (_, Some(table)) => {
+                    let table_node = self.open_elements[table];
+                    match self.nodes[table_node].parent {
+                        Some(parent) => (parent, Some(table_node)),
+                        None => (self.open_elements[table - 1], None),
+                    }
+                }
             }
-            //Check where x != whitespace & != to opening HTML tag, oush that content into our string 
-            let text_part = self.consume_while(|x| !x.is_whitespace() && x != '<');
-            text_content.push_str(&text_part);
+        } else {
+            (target, None)
         }
-        //Create/Return Text type node
-        Node::new(NodeType::Text(text_content), Vec::new())
     }
 
-    fn parse_comment_node(&mut self) -> Node {
-        let mut comment_content = String::new();
+    fn insert_html_element(&mut self, tag: Tag) -> Handle {
+        self.insert_element(tag, Namespace::Html)
+    }
 
-        //Checking if we have acutal HTML comment that looks correct
-        //like so <!-- This is a comment -->
+    fn insert_element(&mut self, tag: Tag, namespace: Namespace) -> Handle {
+        let (parent, before) = self.appropriate_place(self.current_node());
+        let element = self.create_element_in(tag, namespace);
+        self.insert_before(parent, element, before);
+        self.open_elements.push(element);
+        element
+    }
 
-        if self.chars.peek().map_or(false, |c| *c == '-') { //if followed by 1st dash
-            self.chars.next();
-            if self.chars.peek().map_or(false, |c| *c == '-') {//if followed by 2nd dash
-                self.chars.next();
-            } else {
-                self.consume_while(|c| c != '>'); //consume all string until hit closing tag
-                return Node::new(NodeType::Comment(comment_content), Vec::new());
+    //SVG and MathML elements, unlike HTML ones they are closed right away by "/>"
+    fn insert_foreign_element(&mut self, mut tag: Tag, namespace: Namespace) {
+        for attr in tag.attributes.iter_mut() {
+            //the tokenizer lowercased every attribute name, put the capitals back
+            //xlink:href, xml:lang & co. keep their prefix as part of the name, we don't store attribute namespaces
+            let adjusted = match namespace {
+                Namespace::Svg => adjust_svg_attribute_name(&attr.name),
+                Namespace::MathMl if attr.name == "definitionurl" => Some("definitionURL"),
+                _ => None,
+            };
+            if let Some(name) = adjusted {
+                attr.name = name.to_string();
             }
-        } else {    //missing dashes, exit immediatley
-            self.consume_while(|c| c != '>');
-            return Node::new(NodeType::Comment(comment_content), Vec::new());
         }
 
-        //If we have an error, like we have opening tag then immediatley have closing tag
-        if self.chars.peek().map_or(false, |c| *c == '>') {
-            self.chars.next();
-            return Node::new(NodeType::Comment(comment_content), Vec::new());
+        let self_closing = tag.self_closing;
+        self.insert_element(tag, namespace);
+        if self_closing {
+            self.open_elements.pop();
+            self.self_closing_acknowledged = true;
+        }
+    }
+
+    //Void elements can't have children, they are popped right away
+    //They are also the only HTML elements where "/>" means anything, on any other element the slash is ignored
+    fn insert_void_element(&mut self, tag: Tag) {
+        debug_assert!(is_void_element(&tag.name));
+        self.insert_html_element(tag);
+        self.open_elements.pop();
+    }
+
+    //The "generic raw text" and "generic RCDATA" element algorithms, content goes in as plain text
+    fn parse_text_element(&mut self, tag: Tag, state: State) {
+        self.insert_html_element(tag);
+        self.tokenizer.set_state(state);
+        self.original_mode = self.mode;
+        self.mode = InsertionMode::Text;
+    }
+
+    fn insert_character(&mut self, c: char) {
+        let (parent, before) = self.appropriate_place(self.current_node());
+        if parent == DOCUMENT {
+            return; //text can't be a child of the document
         }
 
-        //If after opening tag we have 1 dash & then have closing tag
-        //Take everything after that and slam it into our string as well
-        if self.chars.peek().map_or(false, |c| *c == '-') {
-            self.chars.next();
-            if self.chars.peek().map_or(false, |c| *c == '>' ) {
-                self.chars.next();
-                return Node::new(NodeType::Comment(comment_content), Vec::new());
-            } else {    //if not
-                //Put dash 
-                comment_content.push('-');
+        //Append to the text node right before the insertion point if there is one
+        let children = &self.nodes[parent].children;
+        let previous = match before {
+            Some(sibling) => {
+                let index = self.child_index(parent, sibling);
+                if index > 0 { Some(children[index - 1]) } else { None }
+            }
+            None => children.last().cloned(),
+        };
+        if let Some(previous) = previous {
+            if let BuildData::Text(ref mut text) = self.nodes[previous].data {
+                text.push(c);
+                self.nodes[previous].span = self.nodes[previous].span.to(self.token_span);
+                return;
             }
         }
 
-        //Check different error cases for our comments, all else statements create proper error tag
-        //with the elements that are missing from the if statements
-        while self.chars.peek().is_some() {
-            comment_content.push_str(&self.consume_while(|c| c != '<' && c != '-'));
-            if self.chars.peek().map_or(false, |c| *c == '<') {
-                self.chars.next();
-                if self.chars.peek().map_or(false, |c| *c == '!') {
-                    self.chars.next();
-                    if self.chars.peek().map_or(false, |c| *c == '-') {
-                        self.chars.next();
-                        if self.chars.peek().map_or(false, |c| *c == '-') {
-                            self.consume_while(|c| c != '>');
+        let text = self.new_node(BuildData::Text(c.to_string()));
+        self.insert_before(parent, text, before);
+    }
 
-                            return Node::new(NodeType::Comment(String::from("")), Vec::new());
-                        } else {
-                            comment_content.push_str("<!-");
-                        }
-                    } else if self.chars.peek().map_or(false, |c| *c == ' ') {
-                        self.chars.next();
-                        if self.chars.peek().map_or(false, |c| *c == '-') {
-                            self.chars.next();
-                            if self.chars.peek().map_or(false, |c| *c == '-') {
-                                self.chars.next();
-                                if self.chars.peek().map_or(false, |c| *c == '-') {
-                                    self.chars.next();
-                                    if self.chars.peek().map_or(false, |c| *c == '>') {
-                                        self.chars.next();
-                                        return Node::new(
-                                            NodeType::Comment(String::from("")),
-                                            Vec::new(),
-                                        );
-                                    } else {
-                                        comment_content.push_str("<! --");
-                                    }
-                                } else {
-                                    comment_content.push_str("<! -");
-                                }
-                            } else {
-                                comment_content.push_str("<! ");
-                            }
-                        }
-                    } else {
-                        comment_content.push_str("<!");
-                    }
-                } else {
-                    comment_content.push('<');
-                }
-            } else if self.chars.peek().map_or(false, |c| *c == '-') {
-                self.chars.next();
-                if self.chars.peek().map_or(false, |c| *c == '-') {
-                    self.chars.next();
-                    if self.chars.peek().map_or(false, |c| *c == '>') {
-                        self.chars.next();
-                        break;
-                    } else {
-                        comment_content.push_str("--");
-                    }
-                } else {
-                    comment_content.push('-');
+    fn insert_comment(&mut self, text: String) {
+        let (parent, before) = self.appropriate_place(self.current_node());
+        let comment = self.new_node(BuildData::Comment(text));
+        self.insert_before(parent, comment, before);
+    }
+
+    fn insert_comment_in(&mut self, text: String, parent: Handle) {
+        let comment = self.new_node(BuildData::Comment(text));
+        self.append(parent, comment);
+    }
+
+    fn merge_attributes(&mut self, element: Handle, attributes: Vec<Attribute>) {
+        if let BuildData::Element(ref mut tag, _) = self.nodes[element].data {
+            for attr in attributes {
+                if !tag.attributes.iter().any(|a| a.name == attr.name) {
+                    tag.attributes.push(attr);
                 }
             }
         }
+    }
+
+    //ANCHOR Stack Of Open Elements
+    fn current_node(&self) -> Handle {
+        *self.open_elements.last().unwrap_or(&DOCUMENT)
+    }
+
+    //Tag name of an HTML element, "" for anything else
+    //The tree construction rules only ever look for HTML elements, so an SVG <title> must not count as a <title>
+    fn tag_name(&self, node: Handle) -> &str {
+        match self.nodes[node].data {
+            BuildData::Element(ref tag, Namespace::Html) => &tag.name,
+            _ => "",
+        }
+    }
+
+    //Tag name of an element in any namespace
+    fn local_name(&self, node: Handle) -> &str {
+        match self.nodes[node].data {
+            BuildData::Element(ref tag, _) => &tag.name,
+            _ => "",
+        }
+    }
+
+    fn namespace(&self, node: Handle) -> Option<Namespace> {
+        match self.nodes[node].data {
+            BuildData::Element(_, namespace) => Some(namespace),
+            _ => None,
+        }
+    }
+
+    fn is_html_element(&self, node: Handle) -> bool {
+        self.namespace(node) == Some(Namespace::Html)
+    }
+
+    //Same as the current node, except that when parsing a fragment the context element stands in for the <html> root
+    fn adjusted_current_node(&self) -> Handle {
+        match self.context_element {
+            Some(context) if self.open_elements.len() == 1 => context,
+            _ => self.current_node(),
+        }
+    }
+
+    fn in_foreign_element(&self) -> bool {
+        !self.open_elements.is_empty() && !self.is_html_element(self.adjusted_current_node())
+    }
+
+    fn is_special_node(&self, node: Handle) -> bool {
+        match self.nodes[node].data {
+            BuildData::Element(ref tag, Namespace::Html) => is_special(&tag.name),
+            BuildData::Element(ref tag, namespace) => is_foreign_special(namespace, &tag.name),
+            _ => false,
+        }
+    }
+
+    //Text inside <mi>, <mo>, <mn>, <ms> and <mtext> is parsed as HTML
+    fn is_mathml_text_integration_point(&self, node: Handle) -> bool {
+        self.namespace(node) == Some(Namespace::MathMl) && is_one_of(self.local_name(node), &["mi", "mo", "mn", "ms", "mtext"])
+    }
+
+    //Elements whose content is HTML again, like <foreignObject> in SVG
+    fn is_html_integration_point(&self, node: Handle) -> bool {
+        match self.nodes[node].data {
+            BuildData::Element(ref tag, Namespace::MathMl) => {
+                tag.name == "annotation-xml"
+                    && tag.attributes.iter().any(|a| {
+                        a.name == "encoding"
+                            && (a.value.eq_ignore_ascii_case("text/html") || a.value.eq_ignore_ascii_case("application/xhtml+xml"))
+                    })
+            }
+            BuildData::Element(ref tag, Namespace::Svg) => is_one_of(&tag.name, &["foreignObject", "desc", "title"]),
+            _ => false,
+        }
+    }
+
+    fn is_scope_boundary(&self, node: Handle, scope: Scope) -> bool {
+        match self.nodes[node].data {
+            BuildData::Element(ref tag, Namespace::Html) => scope.is_boundary(&tag.name),
+            BuildData::Element(ref tag, namespace) => scope.is_foreign_boundary(namespace, &tag.name),
+            _ => false,
+        }
+    }
+
+    fn is_html(&self, node: Handle, name: &str) -> bool {
+        self.tag_name(node) == name
+    }
+
+    fn stack_contains(&self, name: &str) -> bool {
+        self.open_elements.iter().any(|&h| self.is_html(h, name))
+    }
+
+    fn remove_from_stack(&mut self, node: Handle) {
+        self.open_elements.retain(|&h| h != node);
+    }
+
+    //Pop elements until one of the given names has been popped
+    fn pop_until(&mut self, names: &[&str]) {
+        while let Some(node) = self.open_elements.pop() {
+            if is_one_of(self.tag_name(node), names) {
+                break;
+            }
+        }
+    }
+
+    //Pop elements until one of the given names is the current node
+    fn clear_stack_back_to(&mut self, names: &[&str]) {
+        while !is_one_of(self.tag_name(self.current_node()), names) {
+            self.open_elements.pop();
+        }
+    }
+
+    //"Has an element in scope": look down the stack for the element, the scope's boundary elements stop the search
+    fn has_in_scope(&self, name: &str, scope: Scope) -> bool {
+        for &node in self.open_elements.iter().rev() {
+            if self.tag_name(node) == name {
+                return true;
+            }
+            if self.is_scope_boundary(node, scope) {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn element_in_scope(&self, element: Handle) -> bool {
+        for &node in self.open_elements.iter().rev() {
+            if node == element {
+                return true;
+            }
+            if self.is_scope_boundary(node, Scope::Default) {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn generate_implied_end_tags(&mut self, except: Option<&str>) {
+        loop {
+            let name = self.tag_name(self.current_node());
+            if except == Some(name) || !is_one_of(name, &["dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc"]) {
+                break;
+            }
+            self.open_elements.pop();
+        }
+    }
+
+    fn generate_all_implied_end_tags(&mut self) {
+        while is_one_of(
+            self.tag_name(self.current_node()),
+            &["caption", "colgroup", "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc", "tbody", "td", "tfoot", "th", "thead", "tr"],
+        ) {
+            self.open_elements.pop();
+        }
+    }
 
-        Node::new(NodeType::Comment(comment_content), Vec::new())
+    fn close_p_element(&mut self) {
+        self.generate_implied_end_tags(Some("p"));
+        self.pop_until(&["p"]);
     }
 
-    fn parse_attributes(&mut self) -> AttrMap {
-        let mut attributes = AttrMap::new();
+    fn close_p_if_in_button_scope(&mut self) {
+        if self.has_in_scope("p", Scope::Button) {
+            self.close_p_element();
+        }
+    }
 
-        //make sure our charachter is not a closing tag ">"
-        while self.chars.peek().map_or(false, |c| *c != '>') {
-            self.consume_while(char::is_whitespace);
-            //When our attribute name is valid, lowercase it  
-            let name = self.consume_while(|c| is_valid_attr_name(c)).to_lowercase();
-            self.consume_while(char::is_whitespace);
+    //Work out the insertion mode again from what's on the stack, used after closing tables, selects and templates
+    fn reset_insertion_mode(&mut self) {
+        for index in (0..self.open_elements.len()).rev() {
+            let last = index == 0;
+            //when parsing a fragment the bottom of the stack stands in for the context element
+            let node = match self.context_element {
+                Some(context) if last => context,
+                _ => self.open_elements[index],
+            };
 
-            //Like class = Header,
-            let value = if self.chars.peek().map_or(false, |c| *c == '=') {
-                self.chars.next();
-                self.consume_while(char::is_whitespace);
-                let s = self.parse_attr_value(); //parse that header word
-                self.consume_while(|c| !c.is_whitespace() && c != '>'); //consume until closing tag
-                self.consume_while(char::is_whitespace);
-                s
-            } else {
-                "".to_string()
+            self.mode = match self.tag_name(node) {
+                "select" => {
+                    let in_table = self.open_elements[..index]
+                        .iter()
+                        .rev()
+                        .take_while(|&&h| !self.is_html(h, "template"))
+                        .any(|&h| self.is_html(h, "table"));
+                    if in_table { InsertionMode::InSelectInTable } else { InsertionMode::InSelect }
+                }
+                "td" | "th" if !last => InsertionMode::InCell,
+                "tr" => InsertionMode::InRow,
+                "tbody" | "thead" | "tfoot" => InsertionMode::InTableBody,
+                "caption" => InsertionMode::InCaption,
+                "colgroup" => InsertionMode::InColumnGroup,
+                "table" => InsertionMode::InTable,
+                "template" => *self.template_modes.last().unwrap_or(&InsertionMode::InTemplate),
+                "head" if !last => InsertionMode::InHead,
+                "body" => InsertionMode::InBody,
+                "frameset" => InsertionMode::InFrameset,
+                "html" => {
+                    if self.head_element.is_none() { InsertionMode::BeforeHead } else { InsertionMode::AfterHead }
+                }
+                _ if last => InsertionMode::InBody,
+                _ => continue,
             };
-            attributes.insert(name, value); 
-            //insert name & value into our AttrMap which was our custom HashMap<String, String>
+            return;
+        }
+        self.mode = InsertionMode::InBody;
+    }
+
+    //ANCHOR Active Formatting Elements
+    fn push_formatting(&mut self, element: Handle, tag: Tag) {
+        //"Noah's Ark" clause, at most 3 identical entries since the last marker
+        let mut identical = Vec::new();
+        for (index, entry) in self.active_formatting.iter().enumerate().rev() {
+            match *entry {
+                FormattingEntry::Marker => break,
+                FormattingEntry::Element(_, ref existing) => {
+                    if existing.name == tag.name && same_attributes(&existing.attributes, &tag.attributes) {
+                        identical.push(index);
+                    }
+                }
+            }
+        }
+        if identical.len() >= 3 {
+            self.active_formatting.remove(*identical.last().unwrap());
         }
-        //Keep iterating through our characters
-        self.chars.next();
+        self.active_formatting.push(FormattingEntry::Element(element, tag));
+    }
+
+    fn formatting_index(&self, element: Handle) -> Option<usize> {
+        self.active_formatting.iter().position(|entry| match *entry {
+            FormattingEntry::Element(h, _) => h == element,
+            FormattingEntry::Marker => false,
+        })
+    }
 
-        attributes
+    fn formatting_element_after_marker(&self, name: &str) -> Option<Handle> {
+        for entry in self.active_formatting.iter().rev() {
+            match *entry {
+                FormattingEntry::Marker => return None,
+                FormattingEntry::Element(h, ref tag) if tag.name == name => return Some(h),
+                _ => {}
+            }
+        }
+        None
     }
 
-    fn parse_attr_value(&mut self) -> String {
-        self.consume_while(char::is_whitespace);
+    fn remove_from_formatting(&mut self, element: Handle) {
+        if let Some(index) = self.formatting_index(element) {
+            self.active_formatting.remove(index);
+        }
+    }
 
-        let result = match self.chars.peek() {
-            Some(&c) if c == '"' || c == '\'' => {
-                self.chars.next();  //iterate by 1 
-                let ret = self.consume_while(|x| x != c); //Consume all of the text until we get another " or '
-                self.chars.next();
-                ret //for result
+    fn clear_formatting_to_marker(&mut self) {
+        while let Some(entry) = self.active_formatting.pop() {
+            if let FormattingEntry::Marker = entry {
+                break;
             }
-            _ => self.consume_while(is_valid_attr_value),
+        }
+    }
+
+    //Reopen formatting elements that were closed implicitly, so "<b>1<p>2" makes the 2 bold too
+    fn reconstruct_active_formatting(&mut self) {
+        let is_open = |parser: &HtmlParser, index: usize| match parser.active_formatting[index] {
+            FormattingEntry::Marker => true,
+            FormattingEntry::Element(h, _) => parser.open_elements.contains(&h),
         };
-        result
+
+        if self.active_formatting.is_empty() || is_open(self, self.active_formatting.len() - 1) {
+            return;
+        }
+
+        //Rewind to the first entry after the last marker or open element
+        let mut index = self.active_formatting.len() - 1;
+        while index > 0 && !is_open(self, index - 1) {
+            index -= 1;
+        }
+
+        //Advance, creating a fresh element for every entry from there on
+        for i in index..self.active_formatting.len() {
+            let (old, tag) = match self.active_formatting[i] {
+                FormattingEntry::Element(old, ref tag) => (old, tag.clone()),
+                FormattingEntry::Marker => continue,
+            };
+            let element = self.insert_html_element(tag.clone());
+            self.nodes[element].span = self.nodes[old].span; //the copy points at the original start tag
+            self.active_formatting[i] = FormattingEntry::Element(element, tag);
+        }
     }
 
-    //Takes condition which is a function 
-    fn consume_while<F>(&mut self, condition: F) -> String 
-    where 
-        F: Fn(char) -> bool, //Must take character and output bool
-    {
-        let mut result = String::new();
+    //The adoption agency algorithm fixes misnested formatting like "<b>1<p>2</b>3</p>"
+    //Returns false when the end tag should be handled as "any other end tag" instead
+    fn adoption_agency(&mut self, subject: &str) -> bool {
+        let current = self.current_node();
+        if self.is_html(current, subject) && self.formatting_index(current).is_none() {
+            self.open_elements.pop();
+            return true;
+        }
+
+        for _ in 0..8 {
+            let formatting_element = match self.formatting_element_after_marker(subject) {
+                Some(h) => h,
+                None => return false,
+            };
+            let formatting_tag = match self.nodes[formatting_element].data {
+                BuildData::Element(ref tag, _) => tag.clone(),
+                _ => return true,
+            };
+
+            let fe_stack_index = match self.open_elements.iter().position(|&h| h == formatting_element) {
+                Some(index) => index,
+                None => {
+                    //the element was already closed
+                    self.parse_error(HtmlErrorKind::UnexpectedEndTag(subject.to_string()));
+                    self.remove_from_formatting(formatting_element);
+                    return true;
+                }
+            };
+            if !self.element_in_scope(formatting_element) {
+                self.parse_error(HtmlErrorKind::UnexpectedEndTag(subject.to_string()));
+                return true; //ignore
+            }
+            if formatting_element != self.current_node() {
+                self.parse_error(HtmlErrorKind::MisnestedTag(subject.to_string()));
+            }
+
+            //The furthest block is the first special element below the formatting element on the stack
+            let furthest_block_index = (fe_stack_index + 1..self.open_elements.len())
+                .find(|&i| self.is_special_node(self.open_elements[i]));
+            let furthest_block_index = match furthest_block_index {
+                Some(index) => index,
+                None => {
+                    self.open_elements.truncate(fe_stack_index);
+                    self.remove_from_formatting(formatting_element);
+                    return true;
+                }
+            };
+            let furthest_block = self.open_elements[furthest_block_index];
+            let common_ancestor = self.open_elements[fe_stack_index - 1];
+            let mut bookmark = self.formatting_index(formatting_element).unwrap();
+
+            let mut node_index = furthest_block_index;
+            let mut last_node = furthest_block;
+            let mut inner_counter = 0;
+            loop {
+                inner_counter += 1;
+                node_index -= 1;
+                let node = self.open_elements[node_index];
+                if node == formatting_element {
+                    break;
+                }
+
+                if inner_counter > 3 {
+                    if let Some(index) = self.formatting_index(node) {
+                        self.active_formatting.remove(index);
+                        if index < bookmark {
+                            bookmark -= 1;
+                        }
+                    }
+                }
+                let formatting_index = match self.formatting_index(node) {
+                    Some(index) => index,
+                    None => {
+                        self.open_elements.remove(node_index);
+                        continue;
+                    }
+                };
+
+                //Replace the node with a fresh copy, both in the formatting list and on the stack
+                let tag = match self.active_formatting[formatting_index] {
+                    FormattingEntry::Element(_, ref tag) => tag.clone(),
+                    FormattingEntry::Marker => unreachable!(),
+                };
+                let new_element = self.create_element(tag.clone());
+                self.nodes[new_element].span = self.nodes[node].span;
+                self.active_formatting[formatting_index] = FormattingEntry::Element(new_element, tag);
+                self.open_elements[node_index] = new_element;
+
+                if last_node == furthest_block {
+                    bookmark = formatting_index + 1;
+                }
+                self.append(new_element, last_node);
+                last_node = new_element;
+            }
 
-        //Loop through characters & 'map or', we want to check if this condition was false
-        //& if its not then return default values, in this case its white-space character
-        while self.chars.peek().map_or(false, |c| condition(*c)) {
-            //Take result & push it on to our characters. then we go to next character and unwrap it
-            result.push(self.chars.next().unwrap());
+            let (parent, before) = self.appropriate_place(common_ancestor);
+            self.insert_before(parent, last_node, before);
+
+            //Everything in the furthest block moves into a copy of the formatting element
+            let new_element = self.create_element(formatting_tag.clone());
+            self.nodes[new_element].span = self.nodes[formatting_element].span;
+            let children = self.nodes[furthest_block].children.clone();
+            for child in children {
+                self.append(new_element, child);
+            }
+            self.append(furthest_block, new_element);
+
+            let old_index = self.formatting_index(formatting_element).unwrap();
+            self.active_formatting.remove(old_index);
+            if old_index < bookmark {
+                bookmark -= 1;
+            }
+            let bookmark = bookmark.min(self.active_formatting.len());
+            self.active_formatting.insert(bookmark, FormattingEntry::Element(new_element, formatting_tag));
+
+            self.remove_from_stack(formatting_element);
+            let fb_index = self.open_elements.iter().position(|&h| h == furthest_block).unwrap();
+            self.open_elements.insert(fb_index + 1, new_element);
         }
+        true
+    }
+
+    //ANCHOR Output
+    //Turn the flat list back into the owned dom::Node tree
+    //Elements that weren't closed by their own end tag end wherever their last child ends
+    //Text is kept exactly as it was written, whitespace is left for the style tree to deal with (white-space)
+    fn to_node(&self, handle: Handle) -> Node {
+        let node = &self.nodes[handle];
+        let children: Vec<Node> = node.children.iter().map(|&child| self.to_node(child)).collect();
+        let span = children.iter().fold(node.span, |span, child| span.to(child.span));
+
+        let node_type = match node.data {
+            BuildData::Element(ref tag, namespace) => NodeType::Element(create_element(tag, namespace)),
+            BuildData::Comment(ref text) => NodeType::Comment(text.clone()),
+            BuildData::Text(ref text) => NodeType::Text(text.clone()),
+            BuildData::Doctype(ref data) => NodeType::Doctype(data.clone()),
+            BuildData::Document => NodeType::Document(self.quirks_mode),
+        };
 
-        result
+        let mut dom_node = Node::new(node_type, children);
+        dom_node.span = span;
+        dom_node
     }
 }
 
 // NOTE 3- Helper Methods
-//Helper functions to parse HTML characters
 
-fn is_valid_tag_name(ch: char) -> bool {
-    ch.is_digit(36)
+//The different kinds of "in scope" checks, each stops at a different set of elements
+#[derive(Clone, Copy)]
+enum Scope {
+    Default,
+    ListItem,
+    Button,
+    Table,
+    Select,
 }
 
-fn is_valid_attr_name(c: char) -> bool {
-    !is_excluded_name(c) && !is_control(c)
+impl Scope {
+    fn is_boundary(&self, name: &str) -> bool {
+        let default = ["applet", "caption", "html", "table", "td", "th", "marquee", "object", "template"];
+        match *self {
+            Scope::Default => is_one_of(name, &default),
+            Scope::ListItem => is_one_of(name, &default) || name == "ol" || name == "ul",
+            Scope::Button => is_one_of(name, &default) || name == "button",
+            Scope::Table => is_one_of(name, &["html", "table", "template"]),
+            Scope::Select => name != "optgroup" && name != "option",
+        }
+    }
+
+    //The SVG and MathML elements that stop a scope search, all of them are integration points
+    fn is_foreign_boundary(&self, namespace: Namespace, name: &str) -> bool {
+        match *self {
+            Scope::Default | Scope::ListItem | Scope::Button => is_foreign_special(namespace, name),
+            Scope::Table => false,
+            Scope::Select => true,
+        }
+    }
 }
 
-//looking at hexadecimal characters to see if we have non ASCII characters
-fn is_control(ch: char) -> bool {
-    match ch {
-        '\u{007F}' => true,
-        c if c >= '\u{0000}' && c <= '\u{001F}' => true,
-        c if c >= '\u{0080}' && c <= '\u{009F}' => true,
-        _ => false,
+//Work out the quirks mode from the doctype, the lists come straight from the spec
+//https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+fn quirks_mode_for(doctype: &Doctype) -> QuirksMode {
+    let name = doctype.name.as_deref();
+    let public_id = doctype.public_id.as_ref().map(|id| id.to_ascii_lowercase());
+    let system_id = doctype.system_id.as_ref().map(|id| id.to_ascii_lowercase());
+    let public = public_id.as_deref().unwrap_or("");
+    let starts_with_any = |prefixes: &[&str]| prefixes.iter().any(|p| public.starts_with(&p.to_ascii_lowercase()));
+
+    if doctype.force_quirks
+        || name != Some("html")
+        || public_id.as_ref().is_some_and(|id| {
+            is_one_of(id, &["-//w3o//dtd w3 html strict 3.0//en//", "-/w3c/dtd html 4.0 transitional/en", "html"])
+        })
+        || system_id.as_ref().is_some_and(|id| id == "http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+        || starts_with_any(QUIRKS_PUBLIC_PREFIXES)
+        || (system_id.is_none()
+            && starts_with_any(&["-//W3C//DTD HTML 4.01 Frameset//", "-//W3C//DTD HTML 4.01 Transitional//"]))
+    {
+        QuirksMode::Quirks
+    } else if starts_with_any(&["-//W3C//DTD XHTML 1.0 Frameset//", "-//W3C//DTD XHTML 1.0 Transitional//"])
+        || (system_id.is_some()
+            && starts_with_any(&["-//W3C//DTD HTML 4.01 Frameset//", "-//W3C//DTD HTML 4.01 Transitional//"]))
+    {
+        QuirksMode::LimitedQuirks
+    } else {
+        QuirksMode::NoQuirks
     }
 }
 
-fn is_excluded_name(c: char) -> bool {
-    match c {
-        ' ' | '"' | '\'' | '>' | '/' | '=' => true,
-        _ => false,
+const QUIRKS_PUBLIC_PREFIXES: &[&str] = &[
+    "+//Silmaril//dtd html Pro v0r11 19970101//",
+    "-//AS//DTD HTML 3.0 asWedit + extensions//",
+    "-//AdvaSoft Ltd//DTD HTML 3.0 asWedit + extensions//",
+    "-//IETF//DTD HTML 2.0 Level 1//",
+    "-//IETF//DTD HTML 2.0 Level 2//",
+    "-//IETF//DTD HTML 2.0 Strict Level 1//",
+    "-//IETF//DTD HTML 2.0 Strict Level 2//",
+    "-//IETF//DTD HTML 2.0 Strict//",
+    "-//IETF//DTD HTML 2.0//",
+    "-//IETF//DTD HTML 2.1E//",
+    "-//IETF//DTD HTML 3.0//",
+    "-//IETF//DTD HTML 3.2 Final//",
+    "-//IETF//DTD HTML 3.2//",
+    "-//IETF//DTD HTML 3//",
+    "-//IETF//DTD HTML Level 0//",
+    "-//IETF//DTD HTML Level 1//",
+    "-//IETF//DTD HTML Level 2//",
+    "-//IETF//DTD HTML Level 3//",
+    "-//IETF//DTD HTML Strict Level 0//",
+    "-//IETF//DTD HTML Strict Level 1//",
+    "-//IETF//DTD HTML Strict Level 2//",
+    "-//IETF//DTD HTML Strict Level 3//",
+    "-//IETF//DTD HTML Strict//",
+    "-//IETF//DTD HTML//",
+    "-//Metrius//DTD Metrius Presentational//",
+    "-//Microsoft//DTD Internet Explorer 2.0 HTML Strict//",
+    "-//Microsoft//DTD Internet Explorer 2.0 HTML//",
+    "-//Microsoft//DTD Internet Explorer 2.0 Tables//",
+    "-//Microsoft//DTD Internet Explorer 3.0 HTML Strict//",
+    "-//Microsoft//DTD Internet Explorer 3.0 HTML//",
+    "-//Microsoft//DTD Internet Explorer 3.0 Tables//",
+    "-//Netscape Comm. Corp.//DTD HTML//",
+    "-//Netscape Comm. Corp.//DTD Strict HTML//",
+    "-//O'Reilly and Associates//DTD HTML 2.0//",
+    "-//O'Reilly and Associates//DTD HTML Extended 1.0//",
+    "-//O'Reilly and Associates//DTD HTML Extended Relaxed 1.0//",
+    "-//SQ//DTD HTML 2.0 HoTMetaL + extensions//",
+    "-//SoftQuad Software//DTD HoTMetaL PRO 6.0::19990601::extensions to HTML 4.0//",
+    "-//SoftQuad//DTD HoTMetaL PRO 4.0::19970916::extensions to HTML 4.0//",
+    "-//Spyglass//DTD HTML 2.0 Extended//",
+    "-//Sun Microsystems Corp.//DTD HotJava HTML//",
+    "-//Sun Microsystems Corp.//DTD HotJava Strict HTML//",
+    "-//W3C//DTD HTML 3 1995-03-24//",
+    "-//W3C//DTD HTML 3.2 Draft//",
+    "-//W3C//DTD HTML 3.2 Final//",
+    "-//W3C//DTD HTML 3.2//",
+    "-//W3C//DTD HTML 3.2S Draft//",
+    "-//W3C//DTD HTML 4.0 Frameset//",
+    "-//W3C//DTD HTML 4.0 Transitional//",
+    "-//W3C//DTD HTML Experimental 19960712//",
+    "-//W3C//DTD HTML Experimental 970421//",
+    "-//W3C//DTD W3 HTML//",
+    "-//W3O//DTD W3 HTML 3.0//",
+    "-//WebTechs//DTD Mozilla HTML 2.0//",
+    "-//WebTechs//DTD Mozilla HTML//",
+];
+
+fn tag_named(name: &str) -> Tag {
+    Tag { name: name.to_string(), self_closing: false, attributes: Vec::new() }
+}
+
+fn create_element(tag: &Tag, namespace: Namespace) -> ElementData {
+    //the tokenizer already drops duplicate attributes, collecting keeps the first one anyway
+    let attributes: AttrMap = tag.attributes.iter().map(|attr| (attr.name.clone(), attr.value.clone())).collect();
+    ElementData::new_in(namespace, tag.name.clone(), attributes)
+}
+
+fn is_one_of(name: &str, names: &[&str]) -> bool {
+    names.contains(&name)
+}
+
+fn is_heading(name: &str) -> bool {
+    is_one_of(name, &["h1", "h2", "h3", "h4", "h5", "h6"])
+}
+
+fn is_hidden_input(tag: &Tag) -> bool {
+    tag.attributes.iter().any(|a| a.name == "type" && a.value.eq_ignore_ascii_case("hidden"))
+}
+
+fn same_attributes(a: &[Attribute], b: &[Attribute]) -> bool {
+    a.len() == b.len() && a.iter().all(|x| b.iter().any(|y| x.name == y.name && x.value == y.value))
+}
+
+//Elements with special parsing rules, they stop "any other end tag" and mark the furthest block
+fn is_special(name: &str) -> bool {
+    is_one_of(
+        name,
+        &[
+            "address", "applet", "area", "article", "aside", "base", "basefont", "bgsound", "blockquote", "body",
+            "br", "button", "caption", "center", "col", "colgroup", "dd", "details", "dir", "div", "dl", "dt",
+            "embed", "fieldset", "figcaption", "figure", "footer", "form", "frame", "frameset", "h1", "h2", "h3",
+            "h4", "h5", "h6", "head", "header", "hgroup", "hr", "html", "iframe", "img", "input", "keygen", "li",
+            "link", "listing", "main", "marquee", "menu", "meta", "nav", "noembed", "noframes", "noscript",
+            "object", "ol", "p", "param", "plaintext", "pre", "script", "search", "section", "select", "source",
+            "style", "summary", "table", "tbody", "td", "template", "textarea", "tfoot", "th", "thead", "title",
+            "tr", "track", "ul", "wbr", "xmp",
+        ],
+    )
+}
+
+//The special SVG and MathML elements, the integration points plus <annotation-xml>
+fn is_foreign_special(namespace: Namespace, name: &str) -> bool {
+    match namespace {
+        Namespace::MathMl => is_one_of(name, &["mi", "mo", "mn", "ms", "mtext", "annotation-xml"]),
+        Namespace::Svg => is_one_of(name, &["foreignObject", "desc", "title"]),
+        Namespace::Html => false,
     }
 }
 
-fn is_valid_attr_value(c: char) -> bool {
-    match c {
-        ' ' | '"' | '\'' | '=' | '<' | '>' | '`' => false,
-        _ => true,
+//HTML start tags that can't be part of an SVG or MathML image, they end the foreign content
+fn is_breakout_tag(tag: &Tag) -> bool {
+    match tag.name.as_str() {
+        "b" | "big" | "blockquote" | "body" | "br" | "center" | "code" | "dd" | "div" | "dl" | "dt" | "em" | "embed"
+        | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "head" | "hr" | "i" | "img" | "li" | "listing" | "menu" | "meta"
+        | "nobr" | "ol" | "p" | "pre" | "ruby" | "s" | "small" | "span" | "strong" | "strike" | "sub" | "sup"
+        | "table" | "tt" | "u" | "ul" | "var" => true,
+        //<font> only counts when it looks like the HTML one
+        "font" => tag.attributes.iter().any(|a| is_one_of(&a.name, &["color", "face", "size"])),
+        _ => false,
     }
-}
\ No newline at end of file
+}
+
+//The tokenizer lowercases every tag name, SVG has mixed case ones
+fn adjust_svg_tag_name(name: &str) -> Option<&'static str> {
+    let adjusted = match name {
+        "altglyph" => "altGlyph",
+        "altglyphdef" => "altGlyphDef",
+        "altglyphitem" => "altGlyphItem",
+        "animatecolor" => "animateColor",
+        "animatemotion" => "animateMotion",
+        "animatetransform" => "animateTransform",
+        "clippath" => "clipPath",
+        "feblend" => "feBlend",
+        "fecolormatrix" => "feColorMatrix",
+        "fecomponenttransfer" => "feComponentTransfer",
+        "fecomposite" => "feComposite",
+        "feconvolvematrix" => "feConvolveMatrix",
+        "fediffuselighting" => "feDiffuseLighting",
+        "fedisplacementmap" => "feDisplacementMap",
+        "fedistantlight" => "feDistantLight",
+        "fedropshadow" => "feDropShadow",
+        "feflood" => "feFlood",
+        "fefunca" => "feFuncA",
+        "fefuncb" => "feFuncB",
+        "fefuncg" => "feFuncG",
+        "fefuncr" => "feFuncR",
+        "fegaussianblur" => "feGaussianBlur",
+        "feimage" => "feImage",
+        "femerge" => "feMerge",
+        "femergenode" => "feMergeNode",
+        "femorphology" => "feMorphology",
+        "feoffset" => "feOffset",
+        "fepointlight" => "fePointLight",
+        "fespecularlighting" => "feSpecularLighting",
+        "fespotlight" => "feSpotLight",
+        "fetile" => "feTile",
+        "feturbulence" => "feTurbulence",
+        "foreignobject" => "foreignObject",
+        "glyphref" => "glyphRef",
+        "lineargradient" => "linearGradient",
+        "radialgradient" => "radialGradient",
+        "textpath" => "textPath",
+        _ => return None,
+    };
+    Some(adjusted)
+}
+
+//Same for the attribute names, viewBox is the one everybody runs into
+fn adjust_svg_attribute_name(name: &str) -> Option<&'static str> {
+    let adjusted = match name {
+        "attributename" => "attributeName",
+        "attributetype" => "attributeType",
+        "basefrequency" => "baseFrequency",
+        "baseprofile" => "baseProfile",
+        "calcmode" => "calcMode",
+        "clippathunits" => "clipPathUnits",
+        "diffuseconstant" => "diffuseConstant",
+        "edgemode" => "edgeMode",
+        "filterunits" => "filterUnits",
+        "glyphref" => "glyphRef",
+        "gradienttransform" => "gradientTransform",
+        "gradientunits" => "gradientUnits",
+        "kernelmatrix" => "kernelMatrix",
+        "kernelunitlength" => "kernelUnitLength",
+        "keypoints" => "keyPoints",
+        "keysplines" => "keySplines",
+        "keytimes" => "keyTimes",
+        "lengthadjust" => "lengthAdjust",
+        "limitingconeangle" => "limitingConeAngle",
+        "markerheight" => "markerHeight",
+        "markerunits" => "markerUnits",
+        "markerwidth" => "markerWidth",
+        "maskcontentunits" => "maskContentUnits",
+        "maskunits" => "maskUnits",
+        "numoctaves" => "numOctaves",
+        "pathlength" => "pathLength",
+        "patterncontentunits" => "patternContentUnits",
+        "patterntransform" => "patternTransform",
+        "patternunits" => "patternUnits",
+        "pointsatx" => "pointsAtX",
+        "pointsaty" => "pointsAtY",
+        "pointsatz" => "pointsAtZ",
+        "preservealpha" => "preserveAlpha",
+        "preserveaspectratio" => "preserveAspectRatio",
+        "primitiveunits" => "primitiveUnits",
+        "refx" => "refX",
+        "refy" => "refY",
+        "repeatcount" => "repeatCount",
+        "repeatdur" => "repeatDur",
+        "requiredextensions" => "requiredExtensions",
+        "requiredfeatures" => "requiredFeatures",
+        "specularconstant" => "specularConstant",
+        "specularexponent" => "specularExponent",
+        "spreadmethod" => "spreadMethod",
+        "startoffset" => "startOffset",
+        "stddeviation" => "stdDeviation",
+        "stitchtiles" => "stitchTiles",
+        "surfacescale" => "surfaceScale",
+        "systemlanguage" => "systemLanguage",
+        "tablevalues" => "tableValues",
+        "targetx" => "targetX",
+        "targety" => "targetY",
+        "textlength" => "textLength",
+        "viewbox" => "viewBox",
+        "viewtarget" => "viewTarget",
+        "xchannelselector" => "xChannelSelector",
+        "ychannelselector" => "yChannelSelector",
+        "zoomandpan" => "zoomAndPan",
+        _ => return None,
+    };
+    Some(adjusted)
+}
@@ -0,0 +1,118 @@
+/* The other direction of html_parse, turns a Dom node back into HTML text
+ * It follows the spec's "serializing HTML fragments" algorithm: https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+ * so the output parses back into the same tree, text and attribute values are escaped, void elements get no end tag
+ * and the contents of <script>, <style> & co. are written out as they are because the parser never decodes them
+ */
+
+use crate::dom::{is_void_element, ElementData, Node, NodeType};
+
+// NOTE 1- Main Entry Points
+
+//The node itself and everything inside of it, like element.outerHTML
+pub fn outer_html(node: &Node) -> String {
+    let mut output = String::new();
+    serialize_node(node, None, &mut output);
+    output
+}
+
+//Only the children of the node, like element.innerHTML
+pub fn inner_html(node: &Node) -> String {
+    let mut output = String::new();
+    serialize_children(node, &mut output);
+    output
+}
+
+// NOTE 2- Serializing
+
+fn serialize_children(node: &Node, output: &mut String) {
+    let parent = match node.node_type {
+        NodeType::Element(ref e) if e.is_html() => Some(e.tag_name.as_str()),
+        _ => None,
+    };
+    for child in node.children.iter() {
+        serialize_node(child, parent, output);
+    }
+}
+
+//`parent` is the tag name of the HTML element the node sits in, it decides if text gets escaped
+//(an SVG <style> is a normal element, its text was decoded and is escaped again)
+fn serialize_node(node: &Node, parent: Option<&str>, output: &mut String) {
+    match node.node_type {
+        NodeType::Element(ref e) => {
+            serialize_start_tag(e, output);
+            if e.is_html() && is_void_element(&e.tag_name) {
+                return; //no children and no end tag
+            }
+            //the parser drops a newline right after <pre>, so one the text starts with needs another in front of it
+            if e.is_html() && matches!(e.tag_name.as_str(), "pre" | "textarea" | "listing") && starts_with_newline(node) {
+                output.push('\n');
+            }
+            serialize_children(node, output);
+            output.push_str("</");
+            output.push_str(&e.tag_name);
+            output.push('>');
+        }
+        NodeType::Text(ref t) => {
+            if parent.is_some_and(is_raw_text_parent) {
+                output.push_str(t);
+            } else {
+                escape(t, false, output);
+            }
+        }
+        NodeType::Comment(ref c) => {
+            output.push_str("<!--");
+            output.push_str(c);
+            output.push_str("-->");
+        }
+        NodeType::Doctype(ref d) => {
+            output.push_str("<!DOCTYPE ");
+            output.push_str(&d.name);
+            output.push('>');
+        }
+        NodeType::Document(_) => serialize_children(node, output),
+    }
+}
+
+fn starts_with_newline(node: &Node) -> bool {
+    match node.children.first() {
+        Some(Node { node_type: NodeType::Text(ref t), .. }) => t.starts_with('\n'),
+        _ => false,
+    }
+}
+
+fn serialize_start_tag(element: &ElementData, output: &mut String) {
+    output.push('<');
+    output.push_str(&element.tag_name);
+
+    //AttrMap keeps the source order, so attributes come out the way they were written
+    for (name, value) in element.attributes().iter() {
+        output.push(' ');
+        output.push_str(name);
+        output.push_str("=\"");
+        escape(value, true, output);
+        output.push('"');
+    }
+
+    output.push('>');
+}
+
+// NOTE 3- Helper Methods
+
+//Text inside these elements is never decoded by the parser, so it must not be escaped either
+fn is_raw_text_parent(tag_name: &str) -> bool {
+    matches!(tag_name, "style" | "script" | "xmp" | "iframe" | "noembed" | "noframes" | "plaintext")
+}
+
+//"Escaping a string", attribute values are always written in double quotes so '"' needs escaping there too
+fn escape(text: &str, attribute_mode: bool, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '\u{00A0}' => output.push_str("&nbsp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' if attribute_mode => output.push_str("&quot;"),
+            c => output.push(c),
+        }
+    }
+}
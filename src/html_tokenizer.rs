@@ -0,0 +1,1765 @@
+/* The tokenizer is the first half of the HTML parsing algorithm, it walks the input one character at a time
+ * and moves between a set of named states (data, tag open, tag name, attribute name, comment ...) exactly like
+ * the state machine in the spec: https://html.spec.whatwg.org/multipage/parsing.html#tokenization
+ * Every state knows what to do with every character, including the broken ones, so there is no such thing as
+ * invalid input here, malformed markup just takes one of the error recovery paths and we keep going
+ * The output is a stream of tokens (doctype, start tag, end tag, character, comment, end of file)
+ * that the tree builder in html_parse consumes instead of reading characters itself
+ */
+
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::entities;
+use crate::source::{LineIndex, Span};
+
+// NOTE 1- Tokens
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Doctype(Doctype),
+    StartTag(Tag),
+    EndTag(Tag),
+    Character(char),
+    Comment(String),
+    Eof,
+}
+
+//Start and end tags share the same shape, end tags just never have attributes that matter
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Tag {
+    pub name: String,
+    pub self_closing: bool,
+    pub attributes: Vec<Attribute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Attribute {
+    pub name: String,
+    pub value: String,
+}
+
+//A missing identifier (None) is different from an empty one (Some("")) when we work out the quirks mode
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Doctype {
+    pub name: Option<String>,
+    pub public_id: Option<String>,
+    pub system_id: Option<String>,
+    pub force_quirks: bool,
+}
+
+//The states of the tokenizer, names follow the spec so they are easy to look up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Data,
+    RcData,
+    RawText,
+    ScriptData,
+    PlainText,
+    TagOpen,
+    EndTagOpen,
+    TagName,
+    RcDataLessThanSign,
+    RcDataEndTagOpen,
+    RcDataEndTagName,
+    RawTextLessThanSign,
+    RawTextEndTagOpen,
+    RawTextEndTagName,
+    ScriptDataLessThanSign,
+    ScriptDataEndTagOpen,
+    ScriptDataEndTagName,
+    ScriptDataEscapeStart,
+    ScriptDataEscapeStartDash,
+    ScriptDataEscaped,
+    ScriptDataEscapedDash,
+    ScriptDataEscapedDashDash,
+    ScriptDataEscapedLessThanSign,
+    ScriptDataEscapedEndTagOpen,
+    ScriptDataEscapedEndTagName,
+    ScriptDataDoubleEscapeStart,
+    ScriptDataDoubleEscaped,
+    ScriptDataDoubleEscapedDash,
+    ScriptDataDoubleEscapedDashDash,
+    ScriptDataDoubleEscapedLessThanSign,
+    ScriptDataDoubleEscapeEnd,
+    BeforeAttributeName,
+    AttributeName,
+    AfterAttributeName,
+    BeforeAttributeValue,
+    AttributeValueDoubleQuoted,
+    AttributeValueSingleQuoted,
+    AttributeValueUnquoted,
+    AfterAttributeValueQuoted,
+    SelfClosingStartTag,
+    BogusComment,
+    MarkupDeclarationOpen,
+    CommentStart,
+    CommentStartDash,
+    Comment,
+    CommentLessThanSign,
+    CommentLessThanSignBang,
+    CommentLessThanSignBangDash,
+    CommentLessThanSignBangDashDash,
+    CommentEndDash,
+    CommentEnd,
+    CommentEndBang,
+    Doctype,
+    BeforeDoctypeName,
+    DoctypeName,
+    AfterDoctypeName,
+    AfterDoctypePublicKeyword,
+    BeforeDoctypePublicIdentifier,
+    DoctypePublicIdentifierDoubleQuoted,
+    DoctypePublicIdentifierSingleQuoted,
+    AfterDoctypePublicIdentifier,
+    BetweenDoctypePublicAndSystemIdentifiers,
+    AfterDoctypeSystemKeyword,
+    BeforeDoctypeSystemIdentifier,
+    DoctypeSystemIdentifierDoubleQuoted,
+    DoctypeSystemIdentifierSingleQuoted,
+    AfterDoctypeSystemIdentifier,
+    BogusDoctype,
+    CharacterReference,
+    NamedCharacterReference,
+    AmbiguousAmpersand,
+    NumericCharacterReference,
+    HexadecimalCharacterReferenceStart,
+    DecimalCharacterReferenceStart,
+    HexadecimalCharacterReference,
+    DecimalCharacterReference,
+    NumericCharacterReferenceEnd,
+    CdataSection,
+    CdataSectionBracket,
+    CdataSectionEnd,
+}
+
+//Everything the spec calls a parse error in the tokenizer, names follow the spec's error codes
+//https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenizerError {
+    AbruptClosingOfEmptyComment,
+    AbruptDoctypePublicIdentifier,
+    AbruptDoctypeSystemIdentifier,
+    AbsenceOfDigitsInNumericCharacterReference,
+    CdataInHtmlContent,
+    CharacterReferenceOutsideUnicodeRange,
+    ControlCharacterReference,
+    DuplicateAttribute,
+    EndTagWithAttributes,
+    EndTagWithTrailingSolidus,
+    EofBeforeTagName,
+    EofInCdata,
+    EofInComment,
+    EofInDoctype,
+    EofInScriptHtmlCommentLikeText,
+    EofInTag,
+    IncorrectlyClosedComment,
+    IncorrectlyOpenedComment,
+    InvalidCharacterSequenceAfterDoctypeName,
+    InvalidFirstCharacterOfTagName,
+    MissingAttributeValue,
+    MissingDoctypeName,
+    MissingDoctypePublicIdentifier,
+    MissingDoctypeSystemIdentifier,
+    MissingEndTagName,
+    MissingQuoteBeforeDoctypePublicIdentifier,
+    MissingQuoteBeforeDoctypeSystemIdentifier,
+    MissingSemicolonAfterCharacterReference,
+    MissingWhitespaceAfterDoctypePublicKeyword,
+    MissingWhitespaceAfterDoctypeSystemKeyword,
+    MissingWhitespaceBeforeDoctypeName,
+    MissingWhitespaceBetweenAttributes,
+    MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers,
+    NestedComment,
+    NoncharacterCharacterReference,
+    NullCharacterReference,
+    SurrogateCharacterReference,
+    UnexpectedCharacterAfterDoctypeSystemIdentifier,
+    UnexpectedCharacterInAttributeName,
+    UnexpectedCharacterInUnquotedAttributeValue,
+    UnexpectedEqualsSignBeforeAttributeName,
+    UnexpectedNullCharacter,
+    UnexpectedQuestionMarkInsteadOfTagName,
+    UnexpectedSolidusInTag,
+    UnknownNamedCharacterReference,
+}
+
+// NOTE 2- Data Structure
+
+pub struct Tokenizer {
+    input: Vec<char>,   //input we haven't finished with yet, already preprocessed (newlines normalized)
+    offset: usize,      //index of input[0] in the whole document, everything before it was dropped
+    input_ended: bool,  //no more chunks are coming, running out of input now means EOF
+    pending_cr: bool,   //a chunk ended with '\r', wait for the next one to see if it's "\r\n"
+    lines: LineIndex,   //turns an index into the document into a line and column
+    pos: usize,         //index (in the whole document) of the next character to consume
+    token_start: usize, //index where the token being read started, so it can get a span
+    state: State,
+    current_tag: Tag,
+    current_tag_is_end: bool,
+    current_attribute: Option<Attribute>, //attribute we are filling, gets added to the tag once it is finished
+    current_comment: String,
+    current_doctype: Doctype,
+    last_start_tag: Option<String>, //name of the last start tag emitted, decides which end tag closes raw text
+    temp_buffer: String,            //characters of a possible end tag inside raw text, emitted as text if it isn't one
+    return_state: State,            //state to go back to after a character reference
+    char_ref_code: u32,             //value of the numeric character reference being read
+    pending: VecDeque<(Token, Span)>, //tokens emitted but not handed out yet, one character can emit more than one token
+    span: Span,                       //span of the token next_token handed out last
+    errors: Vec<(TokenizerError, Span)>,
+    finished: bool,                   //true once the Eof token was emitted
+    in_foreign_content: bool,         //the tree builder is inside <svg> or <math>, only there "<![CDATA[" starts a CDATA section
+}
+
+// NOTE 3- Implement Methods For The DataStructure
+impl Tokenizer {
+    //Tokenizer for a whole document we already have
+    pub fn new(input: &str) -> Tokenizer {
+        let mut tokenizer = Tokenizer::streaming();
+        tokenizer.feed(input);
+        tokenizer.finish();
+        tokenizer
+    }
+
+    //Tokenizer that gets its input in chunks with feed(), next_token returns None when it needs more
+    pub fn streaming() -> Tokenizer {
+        Tokenizer {
+            input: Vec::new(),
+            offset: 0,
+            input_ended: false,
+            pending_cr: false,
+            lines: LineIndex::new(&[]),
+            pos: 0,
+            token_start: 0,
+            state: State::Data,
+            current_tag: Tag::default(),
+            current_tag_is_end: false,
+            current_attribute: None,
+            current_comment: String::new(),
+            current_doctype: Doctype::default(),
+            last_start_tag: None,
+            temp_buffer: String::new(),
+            return_state: State::Data,
+            char_ref_code: 0,
+            pending: VecDeque::new(),
+            span: Span::default(),
+            errors: Vec::new(),
+            finished: false,
+            in_foreign_content: false,
+        }
+    }
+
+    //Add the next chunk of the document, the state machine picks up exactly where it stopped
+    pub fn feed(&mut self, chunk: &str) {
+        //the characters before the token we are in the middle of are done with, no need to keep them around
+        let done = self.pos.min(self.token_start) - self.offset;
+        self.input.drain(..done);
+        self.offset += done;
+
+        let mut text = String::with_capacity(chunk.len() + 1);
+        if self.pending_cr {
+            text.push('\r');
+        }
+        text.push_str(chunk);
+        self.pending_cr = text.ends_with('\r');
+        if self.pending_cr {
+            text.pop();
+        }
+        self.push_input(&text);
+    }
+
+    //No more chunks are coming, whatever is left gets tokenized up to the Eof token
+    pub fn finish(&mut self) {
+        if self.pending_cr {
+            self.pending_cr = false;
+            self.push_input("\n");
+        }
+        self.input_ended = true;
+    }
+
+    fn push_input(&mut self, text: &str) {
+        let chars = preprocess(text);
+        self.lines.extend(&chars, self.offset + self.input.len());
+        self.input.extend(chars);
+    }
+
+    //Main entry point, runs the state machine until it has at least one token to give back
+    //Returns None once the Eof token was handed out, or while it's waiting for the next chunk
+    pub fn next_token(&mut self) -> Option<Token> {
+        while self.pending.is_empty() && !self.finished && self.has_enough_input() {
+            self.step();
+        }
+        let (token, span) = self.pending.pop_front()?;
+        self.span = span;
+        Some(token)
+    }
+
+    //Where the token next_token handed out last came from
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    //Hand over the parse errors found so far, the tree builder merges them with its own
+    pub fn take_errors(&mut self) -> Vec<(TokenizerError, Span)> {
+        mem::take(&mut self.errors)
+    }
+
+    //The tree builder switches states for elements whose content isn't markup (title, textarea, style, script ...)
+    pub fn set_state(&mut self, state: State) {
+        self.state = state;
+    }
+
+    //Fragment parsing starts inside the context element without ever seeing its start tag,
+    //it still has to count as the last start tag so its end tag can close raw text
+    pub fn set_last_start_tag(&mut self, name: &str) {
+        self.last_start_tag = Some(name.to_string());
+    }
+
+    //The tree builder tells us after every token if its adjusted current node is an SVG or MathML element
+    pub fn set_in_foreign_content(&mut self, in_foreign_content: bool) {
+        self.in_foreign_content = in_foreign_content;
+    }
+
+    //ANCHOR Input
+    fn char_at(&self, index: usize) -> Option<char> {
+        self.input.get(index - self.offset).cloned()
+    }
+
+    //Running out of input only means EOF once finish() was called, until then a state that
+    //needs characters we don't have yet waits for the next chunk instead of stepping
+    fn has_enough_input(&self) -> bool {
+        if self.input_ended {
+            return true;
+        }
+        let available = self.offset + self.input.len() - self.pos;
+        match self.state {
+            State::MarkupDeclarationOpen => available >= "[CDATA[".len(),
+            State::AfterDoctypeName => available >= "PUBLIC".len(),
+            //the longest name plus the character after it, unless something that can't be in a name comes first
+            State::NamedCharacterReference => (self.pos..=self.pos + entities::LONGEST_ENTITY_NAME)
+                .map(|i| self.char_at(i))
+                .find(|c| c.is_none_or(|c| !c.is_ascii_alphanumeric()))
+                .is_none_or(|c| c.is_some()),
+            _ => available >= 1,
+        }
+    }
+
+    fn consume(&mut self) -> Option<char> {
+        let c = self.char_at(self.pos);
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    //Put the character we just consumed back, at EOF nothing was consumed so there is nothing to put back
+    fn reconsume(&mut self, c: Option<char>, state: State) {
+        if c.is_some() {
+            self.pos -= 1;
+        }
+        self.state = state;
+    }
+
+    //Check if the upcoming characters are `s`, used by the states that look ahead for keywords
+    fn next_is(&self, s: &str, ignore_case: bool) -> bool {
+        for (i, expected) in (self.pos..).zip(s.chars()) {
+            match self.char_at(i) {
+                Some(c) if c == expected => {}
+                Some(c) if ignore_case && c.eq_ignore_ascii_case(&expected) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    fn skip(&mut self, count: usize) {
+        self.pos += count;
+    }
+
+    //ANCHOR Emit
+    fn emit(&mut self, token: Token) {
+        if token == Token::Eof {
+            self.finished = true;
+        }
+        let span = self.lines.span(self.token_start.min(self.pos), self.pos);
+        self.pending.push_back((token, span));
+    }
+
+    //Errors point at the character that was just consumed (or the end of the input)
+    fn error(&mut self, error: TokenizerError) {
+        let start = if self.pos > self.offset { self.pos - 1 } else { self.pos };
+        let span = self.lines.span(start, self.pos);
+        self.errors.push((error, span));
+    }
+
+    fn emit_char(&mut self, c: char) {
+        self.emit(Token::Character(c));
+    }
+
+    fn emit_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.emit_char(c);
+        }
+    }
+
+    fn emit_current_tag(&mut self) {
+        self.finish_attribute();
+        let tag = mem::take(&mut self.current_tag);
+        if self.current_tag_is_end {
+            if !tag.attributes.is_empty() {
+                self.error(TokenizerError::EndTagWithAttributes);
+            }
+            if tag.self_closing {
+                self.error(TokenizerError::EndTagWithTrailingSolidus);
+            }
+            self.emit(Token::EndTag(tag));
+        } else {
+            self.last_start_tag = Some(tag.name.clone());
+            self.emit(Token::StartTag(tag));
+        }
+    }
+
+    fn emit_current_comment(&mut self) {
+        let comment = mem::take(&mut self.current_comment);
+        self.emit(Token::Comment(comment));
+    }
+
+    fn emit_current_doctype(&mut self) {
+        let doctype = mem::take(&mut self.current_doctype);
+        self.emit(Token::Doctype(doctype));
+    }
+
+    //Doctype that hit the end of the file, always forces quirks
+    fn emit_eof_in_doctype(&mut self) {
+        self.error(TokenizerError::EofInDoctype);
+        self.current_doctype.force_quirks = true;
+        self.emit_current_doctype();
+        self.emit(Token::Eof);
+    }
+
+    //ANCHOR Tags and Attributes
+    fn start_tag(&mut self, is_end: bool) {
+        self.current_tag = Tag::default();
+        self.current_tag_is_end = is_end;
+        self.current_attribute = None;
+    }
+
+    //An end tag is "appropriate" when it closes the last start tag, only those can end raw text
+    fn is_appropriate_end_tag(&self) -> bool {
+        self.current_tag_is_end && self.last_start_tag.as_ref() == Some(&self.current_tag.name)
+    }
+
+    fn start_attribute(&mut self, name: String) {
+        self.finish_attribute();
+        self.current_attribute = Some(Attribute { name, value: String::new() });
+    }
+
+    //The first attribute with a given name wins, any later duplicate is dropped
+    fn finish_attribute(&mut self) {
+        if let Some(attr) = self.current_attribute.take() {
+            if self.current_tag.attributes.iter().any(|a| a.name == attr.name) {
+                self.error(TokenizerError::DuplicateAttribute);
+            } else {
+                self.current_tag.attributes.push(attr);
+            }
+        }
+    }
+
+    fn push_attribute_name(&mut self, c: char) {
+        if let Some(ref mut attr) = self.current_attribute {
+            attr.name.push(c);
+        }
+    }
+
+    fn push_attribute_value(&mut self, c: char) {
+        if let Some(ref mut attr) = self.current_attribute {
+            attr.value.push(c);
+        }
+    }
+
+    fn push_public_id(&mut self, c: char) {
+        if let Some(ref mut id) = self.current_doctype.public_id {
+            id.push(c);
+        }
+    }
+
+    fn push_system_id(&mut self, c: char) {
+        if let Some(ref mut id) = self.current_doctype.system_id {
+            id.push(c);
+        }
+    }
+
+    //ANCHOR State Machine
+    //Consume one character (or one lookahead sequence) and act on it depending on the state we're in
+    fn step(&mut self) {
+        //Text states are where every token starts, tags and comments begin with the '<' consumed in one of them
+        if self.is_text_state() {
+            self.token_start = self.pos;
+        }
+
+        match self.state {
+            State::Data => match self.consume() {
+                Some('&') => self.start_character_reference(State::Data),
+                Some('<') => self.state = State::TagOpen,
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.emit_char('\0');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit(Token::Eof),
+            },
+
+            //ANCHOR Raw Text
+            State::RcData => match self.consume() {
+                Some('&') => self.start_character_reference(State::RcData),
+                Some('<') => self.state = State::RcDataLessThanSign,
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit(Token::Eof),
+            },
+
+            State::RawText => match self.consume() {
+                Some('<') => self.state = State::RawTextLessThanSign,
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit(Token::Eof),
+            },
+
+            State::ScriptData => match self.consume() {
+                Some('<') => self.state = State::ScriptDataLessThanSign,
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit(Token::Eof),
+            },
+
+            State::PlainText => match self.consume() {
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit(Token::Eof),
+            },
+
+            State::RcDataLessThanSign => self.text_less_than_sign(State::RcData, State::RcDataEndTagOpen),
+            State::RcDataEndTagOpen => self.text_end_tag_open(State::RcData, State::RcDataEndTagName),
+            State::RcDataEndTagName => self.text_end_tag_name(State::RcData),
+            State::RawTextLessThanSign => self.text_less_than_sign(State::RawText, State::RawTextEndTagOpen),
+            State::RawTextEndTagOpen => self.text_end_tag_open(State::RawText, State::RawTextEndTagName),
+            State::RawTextEndTagName => self.text_end_tag_name(State::RawText),
+
+            //ANCHOR Script Data
+            //Scripts get extra states so "<!--" inside a script can hide a "</script>" like old browsers did
+            State::ScriptDataLessThanSign => match self.consume() {
+                Some('/') => {
+                    self.temp_buffer.clear();
+                    self.state = State::ScriptDataEndTagOpen;
+                }
+                Some('!') => {
+                    self.state = State::ScriptDataEscapeStart;
+                    self.emit_str("<!");
+                }
+                c => {
+                    self.emit_char('<');
+                    self.reconsume(c, State::ScriptData);
+                }
+            },
+
+            State::ScriptDataEndTagOpen => self.text_end_tag_open(State::ScriptData, State::ScriptDataEndTagName),
+            State::ScriptDataEndTagName => self.text_end_tag_name(State::ScriptData),
+
+            State::ScriptDataEscapeStart => match self.consume() {
+                Some('-') => {
+                    self.state = State::ScriptDataEscapeStartDash;
+                    self.emit_char('-');
+                }
+                c => self.reconsume(c, State::ScriptData),
+            },
+
+            State::ScriptDataEscapeStartDash => match self.consume() {
+                Some('-') => {
+                    self.state = State::ScriptDataEscapedDashDash;
+                    self.emit_char('-');
+                }
+                c => self.reconsume(c, State::ScriptData),
+            },
+
+            State::ScriptDataEscaped => match self.consume() {
+                Some('-') => {
+                    self.state = State::ScriptDataEscapedDash;
+                    self.emit_char('-');
+                }
+                Some('<') => self.state = State::ScriptDataEscapedLessThanSign,
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.eof_in_script_comment(),
+            },
+
+            State::ScriptDataEscapedDash => match self.consume() {
+                Some('-') => {
+                    self.state = State::ScriptDataEscapedDashDash;
+                    self.emit_char('-');
+                }
+                Some('<') => self.state = State::ScriptDataEscapedLessThanSign,
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.state = State::ScriptDataEscaped;
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => {
+                    self.state = State::ScriptDataEscaped;
+                    self.emit_char(c);
+                }
+                None => self.eof_in_script_comment(),
+            },
+
+            State::ScriptDataEscapedDashDash => match self.consume() {
+                Some('-') => self.emit_char('-'),
+                Some('<') => self.state = State::ScriptDataEscapedLessThanSign,
+                Some('>') => {
+                    self.state = State::ScriptData;
+                    self.emit_char('>');
+                }
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.state = State::ScriptDataEscaped;
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => {
+                    self.state = State::ScriptDataEscaped;
+                    self.emit_char(c);
+                }
+                None => self.eof_in_script_comment(),
+            },
+
+            State::ScriptDataEscapedLessThanSign => match self.consume() {
+                Some('/') => {
+                    self.temp_buffer.clear();
+                    self.state = State::ScriptDataEscapedEndTagOpen;
+                }
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.temp_buffer.clear();
+                    self.emit_char('<');
+                    self.reconsume(Some(c), State::ScriptDataDoubleEscapeStart);
+                }
+                c => {
+                    self.emit_char('<');
+                    self.reconsume(c, State::ScriptDataEscaped);
+                }
+            },
+
+            State::ScriptDataEscapedEndTagOpen => {
+                self.text_end_tag_open(State::ScriptDataEscaped, State::ScriptDataEscapedEndTagName)
+            }
+            State::ScriptDataEscapedEndTagName => self.text_end_tag_name(State::ScriptDataEscaped),
+
+            State::ScriptDataDoubleEscapeStart => {
+                self.double_escape_boundary(State::ScriptDataDoubleEscaped, State::ScriptDataEscaped)
+            }
+
+            State::ScriptDataDoubleEscaped => match self.consume() {
+                Some('-') => {
+                    self.state = State::ScriptDataDoubleEscapedDash;
+                    self.emit_char('-');
+                }
+                Some('<') => {
+                    self.state = State::ScriptDataDoubleEscapedLessThanSign;
+                    self.emit_char('<');
+                }
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.eof_in_script_comment(),
+            },
+
+            State::ScriptDataDoubleEscapedDash => match self.consume() {
+                Some('-') => {
+                    self.state = State::ScriptDataDoubleEscapedDashDash;
+                    self.emit_char('-');
+                }
+                Some('<') => {
+                    self.state = State::ScriptDataDoubleEscapedLessThanSign;
+                    self.emit_char('<');
+                }
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.state = State::ScriptDataDoubleEscaped;
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => {
+                    self.state = State::ScriptDataDoubleEscaped;
+                    self.emit_char(c);
+                }
+                None => self.eof_in_script_comment(),
+            },
+
+            State::ScriptDataDoubleEscapedDashDash => match self.consume() {
+                Some('-') => self.emit_char('-'),
+                Some('<') => {
+                    self.state = State::ScriptDataDoubleEscapedLessThanSign;
+                    self.emit_char('<');
+                }
+                Some('>') => {
+                    self.state = State::ScriptData;
+                    self.emit_char('>');
+                }
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.state = State::ScriptDataDoubleEscaped;
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => {
+                    self.state = State::ScriptDataDoubleEscaped;
+                    self.emit_char(c);
+                }
+                None => self.eof_in_script_comment(),
+            },
+
+            State::ScriptDataDoubleEscapedLessThanSign => match self.consume() {
+                Some('/') => {
+                    self.temp_buffer.clear();
+                    self.state = State::ScriptDataDoubleEscapeEnd;
+                    self.emit_char('/');
+                }
+                c => self.reconsume(c, State::ScriptDataDoubleEscaped),
+            },
+
+            State::ScriptDataDoubleEscapeEnd => {
+                self.double_escape_boundary(State::ScriptDataEscaped, State::ScriptDataDoubleEscaped)
+            }
+
+            State::TagOpen => match self.consume() {
+                Some('!') => self.state = State::MarkupDeclarationOpen,
+                Some('/') => self.state = State::EndTagOpen,
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(false);
+                    self.reconsume(Some(c), State::TagName);
+                }
+                Some('?') => {
+                    self.error(TokenizerError::UnexpectedQuestionMarkInsteadOfTagName);
+                    self.current_comment.clear();
+                    self.reconsume(Some('?'), State::BogusComment);
+                }
+                None => {
+                    self.error(TokenizerError::EofBeforeTagName);
+                    self.emit_char('<');
+                    self.emit(Token::Eof);
+                }
+                c => {
+                    self.error(TokenizerError::InvalidFirstCharacterOfTagName);
+                    self.emit_char('<');
+                    self.reconsume(c, State::Data);
+                }
+            },
+
+            State::EndTagOpen => match self.consume() {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(true);
+                    self.reconsume(Some(c), State::TagName);
+                }
+                Some('>') => {
+                    self.error(TokenizerError::MissingEndTagName);
+                    self.state = State::Data;
+                }
+                None => {
+                    self.error(TokenizerError::EofBeforeTagName);
+                    self.emit_str("</");
+                    self.emit(Token::Eof);
+                }
+                c => {
+                    self.error(TokenizerError::InvalidFirstCharacterOfTagName);
+                    self.current_comment.clear();
+                    self.reconsume(c, State::BogusComment);
+                }
+            },
+
+            State::TagName => match self.consume() {
+                Some(c) if is_whitespace(c) => self.state = State::BeforeAttributeName,
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.current_tag.name.push('\u{FFFD}');
+                }
+                Some(c) => self.current_tag.name.push(c.to_ascii_lowercase()),
+                None => {
+                    self.error(TokenizerError::EofInTag);
+                    self.emit(Token::Eof);
+                }
+            },
+
+            State::BeforeAttributeName => match self.consume() {
+                Some(c) if is_whitespace(c) => {}
+                c @ Some('/') | c @ Some('>') | c @ None => {
+                    self.reconsume(c, State::AfterAttributeName);
+                }
+                Some('=') => {
+                    self.error(TokenizerError::UnexpectedEqualsSignBeforeAttributeName);
+                    self.start_attribute(String::from("="));
+                    self.state = State::AttributeName;
+                }
+                c => {
+                    self.start_attribute(String::new());
+                    self.reconsume(c, State::AttributeName);
+                }
+            },
+
+            State::AttributeName => match self.consume() {
+                Some(c) if is_whitespace(c) => self.state = State::AfterAttributeName,
+                c @ Some('/') | c @ Some('>') | c @ None => {
+                    self.reconsume(c, State::AfterAttributeName);
+                }
+                Some('=') => self.state = State::BeforeAttributeValue,
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.push_attribute_name('\u{FFFD}');
+                }
+                Some(c) => {
+                    //'"', '\'' and '<' still end up in the name
+                    if c == '"' || c == '\'' || c == '<' {
+                        self.error(TokenizerError::UnexpectedCharacterInAttributeName);
+                    }
+                    self.push_attribute_name(c.to_ascii_lowercase());
+                }
+            },
+
+            State::AfterAttributeName => match self.consume() {
+                Some(c) if is_whitespace(c) => {}
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('=') => self.state = State::BeforeAttributeValue,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                None => {
+                    self.error(TokenizerError::EofInTag);
+                    self.emit(Token::Eof);
+                }
+                c => {
+                    self.start_attribute(String::new());
+                    self.reconsume(c, State::AttributeName);
+                }
+            },
+
+            State::BeforeAttributeValue => match self.consume() {
+                Some(c) if is_whitespace(c) => {}
+                Some('"') => self.state = State::AttributeValueDoubleQuoted,
+                Some('\'') => self.state = State::AttributeValueSingleQuoted,
+                Some('>') => {
+                    self.error(TokenizerError::MissingAttributeValue);
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                c => self.reconsume(c, State::AttributeValueUnquoted),
+            },
+
+            State::AttributeValueDoubleQuoted => self.quoted_attribute_value('"'),
+            State::AttributeValueSingleQuoted => self.quoted_attribute_value('\''),
+
+            State::AttributeValueUnquoted => match self.consume() {
+                Some(c) if is_whitespace(c) => self.state = State::BeforeAttributeName,
+                Some('&') => self.start_character_reference(State::AttributeValueUnquoted),
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.push_attribute_value('\u{FFFD}');
+                }
+                Some(c) => {
+                    if "\"'<=`".contains(c) {
+                        self.error(TokenizerError::UnexpectedCharacterInUnquotedAttributeValue);
+                    }
+                    self.push_attribute_value(c);
+                }
+                None => {
+                    self.error(TokenizerError::EofInTag);
+                    self.emit(Token::Eof);
+                }
+            },
+
+            State::AfterAttributeValueQuoted => match self.consume() {
+                Some(c) if is_whitespace(c) => self.state = State::BeforeAttributeName,
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                None => {
+                    self.error(TokenizerError::EofInTag);
+                    self.emit(Token::Eof);
+                }
+                c => {
+                    self.error(TokenizerError::MissingWhitespaceBetweenAttributes);
+                    self.reconsume(c, State::BeforeAttributeName);
+                }
+            },
+
+            State::SelfClosingStartTag => match self.consume() {
+                Some('>') => {
+                    self.current_tag.self_closing = true;
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                None => {
+                    self.error(TokenizerError::EofInTag);
+                    self.emit(Token::Eof);
+                }
+                c => {
+                    self.error(TokenizerError::UnexpectedSolidusInTag);
+                    self.reconsume(c, State::BeforeAttributeName);
+                }
+            },
+
+            State::BogusComment => match self.consume() {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.current_comment.push('\u{FFFD}');
+                }
+                Some(c) => self.current_comment.push(c),
+                None => {
+                    self.emit_current_comment();
+                    self.emit(Token::Eof);
+                }
+            },
+
+            State::MarkupDeclarationOpen => {
+                if self.next_is("--", false) {
+                    self.skip(2);
+                    self.current_comment.clear();
+                    self.state = State::CommentStart;
+                } else if self.next_is("DOCTYPE", true) {
+                    self.skip(7);
+                    self.state = State::Doctype;
+                } else if self.next_is("[CDATA[", false) && self.in_foreign_content {
+                    self.skip(7);
+                    self.state = State::CdataSection;
+                } else if self.next_is("[CDATA[", false) {
+                    //CDATA sections only exist inside foreign content (svg, math), in HTML they are a bogus comment
+                    self.error(TokenizerError::CdataInHtmlContent);
+                    self.skip(7);
+                    self.current_comment = String::from("[CDATA[");
+                    self.state = State::BogusComment;
+                } else {
+                    self.error(TokenizerError::IncorrectlyOpenedComment);
+                    self.current_comment.clear();
+                    self.state = State::BogusComment;
+                }
+            }
+
+            //ANCHOR Comments
+            State::CommentStart => match self.consume() {
+                Some('-') => self.state = State::CommentStartDash,
+                Some('>') => {
+                    self.error(TokenizerError::AbruptClosingOfEmptyComment);
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                c => self.reconsume(c, State::Comment),
+            },
+
+            State::CommentStartDash => match self.consume() {
+                Some('-') => self.state = State::CommentEnd,
+                Some('>') => {
+                    self.error(TokenizerError::AbruptClosingOfEmptyComment);
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                None => self.eof_in_comment(),
+                c => {
+                    self.current_comment.push('-');
+                    self.reconsume(c, State::Comment);
+                }
+            },
+
+            State::Comment => match self.consume() {
+                Some('<') => {
+                    self.current_comment.push('<');
+                    self.state = State::CommentLessThanSign;
+                }
+                Some('-') => self.state = State::CommentEndDash,
+                Some('\0') => {
+                    self.error(TokenizerError::UnexpectedNullCharacter);
+                    self.current_comment.push('\u{FFFD}');
+                }
+                Some(c) => self.current_comment.push(c),
+                None => self.eof_in_comment(),
+            },
+
+            State::CommentLessThanSign => match self.consume() {
+                Some('!') => {
+                    self.current_comment.push('!');
+                    self.state = State::CommentLessThanSignBang;
+                }
+                Some('<') => self.current_comment.push('<'),
+                c => self.reconsume(c, State::Comment),
+            },
+
+            State::CommentLessThanSignBang => match self.consume() {
+                Some('-') => self.state = State::CommentLessThanSignBangDash,
+                c => self.reconsume(c, State::Comment),
+            },
+
+            State::CommentLessThanSignBangDash => match self.consume() {
+                Some('-') => self.state = State::CommentLessThanSignBangDashDash,
+                c => self.reconsume(c, State::CommentEndDash),
+            },
+
+            State::CommentLessThanSignBangDashDash => match self.consume() {
+                c @ Some('>') | c @ None => self.reconsume(c, State::CommentEnd),
+                c => {
+                    self.error(TokenizerError::NestedComment);
+                    self.reconsume(c, State::CommentEnd);
+                }
+            },
+
+            State::CommentEndDash => match self.consume() {
+                Some('-') => self.state = State::CommentEnd,
+                None => self.eof_in_comment(),
+                c => {
+                    self.current_comment.push('-');
+                    self.reconsume(c, State::Comment);
+                }
+            },
+
+            State::CommentEnd => match self.consume() {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                Some('!') => self.state = State::CommentEndBang,
+                Some('-') => self.current_comment.push('-'),
+                None => self.eof_in_comment(),
+                c => {
+                    self.current_comment.push_str("--");
+                    self.reconsume(c, State::Comment);
+                }
+            },
+
+            State::CommentEndBang => match self.consume() {
+                Some('-') => {
+                    self.current_comment.push_str("--!");
+                    self.state = State::CommentEndDash;
+                }
+                Some('>') => {
+                    self.error(TokenizerError::IncorrectlyClosedComment);
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                None => self.eof_in_comment(),
+                c => {
+                    self.current_comment.push_str("--!");
+                    self.reconsume(c, State::Comment);
+                }
+            },
+
+            //ANCHOR Doctype
+            State::Doctype => match self.consume() {
+                Some(c) if is_whitespace(c) => self.state = State::BeforeDoctypeName,
+                Some('>') => self.reconsume(Some('>'), State::BeforeDoctypeName),
+                None => {
+                    self.current_doctype = Doctype::default();
+                    self.emit_eof_in_doctype();
+                }
+                c => {
+                    self.error(TokenizerError::MissingWhitespaceBeforeDoctypeName);
+                    self.reconsume(c, State::BeforeDoctypeName);
+                }
+            },
+
+            State::BeforeDoctypeName => match self.consume() {
+                Some(c) if is_whitespace(c) => {}
+                Some('>') => {
+                    self.error(TokenizerError::MissingDoctypeName);
+                    self.current_doctype = Doctype::default();
+                    self.current_doctype.force_quirks = true;
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => {
+                    self.current_doctype = Doctype::default();
+                    self.emit_eof_in_doctype();
+                }
+                Some(c) => {
+                    let c = if c == '\0' {
+                        self.error(TokenizerError::UnexpectedNullCharacter);
+                        '\u{FFFD}'
+                    } else {
+                        c.to_ascii_lowercase()
+                    };
+                    self.current_doctype = Doctype::default();
+                    self.current_doctype.name = Some(c.to_string());
+                    self.state = State::DoctypeName;
+                }
+            },
+
+            State::DoctypeName => match self.consume() {
+                Some(c) if is_whitespace(c) => self.state = State::AfterDoctypeName,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => self.emit_eof_in_doctype(),
+                Some(c) => {
+                    let c = if c == '\0' {
+                        self.error(TokenizerError::UnexpectedNullCharacter);
+                        '\u{FFFD}'
+                    } else {
+                        c.to_ascii_lowercase()
+                    };
+                    if let Some(ref mut name) = self.current_doctype.name {
+                        name.push(c);
+                    }
+                }
+            },
+
+            State::AfterDoctypeName => match self.consume() {
+                Some(c) if is_whitespace(c) => {}
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => self.emit_eof_in_doctype(),
+                c => {
+                    //Look at this character together with the next five for the PUBLIC or SYSTEM keyword
+                    self.reconsume(c, State::AfterDoctypeName);
+                    if self.next_is("PUBLIC", true) {
+                        self.skip(6);
+                        self.state = State::AfterDoctypePublicKeyword;
+                    } else if self.next_is("SYSTEM", true) {
+                        self.skip(6);
+                        self.state = State::AfterDoctypeSystemKeyword;
+                    } else {
+                        self.error(TokenizerError::InvalidCharacterSequenceAfterDoctypeName);
+                        self.current_doctype.force_quirks = true;
+                        self.state = State::BogusDoctype;
+                    }
+                }
+            },
+
+            State::AfterDoctypePublicKeyword => match self.consume() {
+                Some(c) if is_whitespace(c) => self.state = State::BeforeDoctypePublicIdentifier,
+                Some('"') => {
+                    self.error(TokenizerError::MissingWhitespaceAfterDoctypePublicKeyword);
+                    self.current_doctype.public_id = Some(String::new());
+                    self.state = State::DoctypePublicIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.error(TokenizerError::MissingWhitespaceAfterDoctypePublicKeyword);
+                    self.current_doctype.public_id = Some(String::new());
+                    self.state = State::DoctypePublicIdentifierSingleQuoted;
+                }
+                Some('>') => self.missing_doctype_identifier(true),
+                None => self.emit_eof_in_doctype(),
+                c => self.missing_quote_before_doctype_identifier(c, true),
+            },
+
+            State::BeforeDoctypePublicIdentifier => match self.consume() {
+                Some(c) if is_whitespace(c) => {}
+                Some('"') => {
+                    self.current_doctype.public_id = Some(String::new());
+                    self.state = State::DoctypePublicIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.current_doctype.public_id = Some(String::new());
+                    self.state = State::DoctypePublicIdentifierSingleQuoted;
+                }
+                Some('>') => self.missing_doctype_identifier(true),
+                None => self.emit_eof_in_doctype(),
+                c => self.missing_quote_before_doctype_identifier(c, true),
+            },
+
+            State::DoctypePublicIdentifierDoubleQuoted => self.doctype_identifier('"', true),
+            State::DoctypePublicIdentifierSingleQuoted => self.doctype_identifier('\'', true),
+
+            State::AfterDoctypePublicIdentifier => match self.consume() {
+                Some(c) if is_whitespace(c) => {
+                    self.state = State::BetweenDoctypePublicAndSystemIdentifiers;
+                }
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                Some('"') => {
+                    self.error(TokenizerError::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers);
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.error(TokenizerError::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers);
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                }
+                None => self.emit_eof_in_doctype(),
+                c => self.missing_quote_before_doctype_identifier(c, false),
+            },
+
+            State::BetweenDoctypePublicAndSystemIdentifiers => match self.consume() {
+                Some(c) if is_whitespace(c) => {}
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                Some('"') => {
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                }
+                None => self.emit_eof_in_doctype(),
+                c => self.missing_quote_before_doctype_identifier(c, false),
+            },
+
+            State::AfterDoctypeSystemKeyword => match self.consume() {
+                Some(c) if is_whitespace(c) => self.state = State::BeforeDoctypeSystemIdentifier,
+                Some('"') => {
+                    self.error(TokenizerError::MissingWhitespaceAfterDoctypeSystemKeyword);
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.error(TokenizerError::MissingWhitespaceAfterDoctypeSystemKeyword);
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                }
+                Some('>') => self.missing_doctype_identifier(false),
+                None => self.emit_eof_in_doctype(),
+                c => self.missing_quote_before_doctype_identifier(c, false),
+            },
+
+            State::BeforeDoctypeSystemIdentifier => match self.consume() {
+                Some(c) if is_whitespace(c) => {}
+                Some('"') => {
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                }
+                Some('>') => self.missing_doctype_identifier(false),
+                None => self.emit_eof_in_doctype(),
+                c => self.missing_quote_before_doctype_identifier(c, false),
+            },
+
+            State::DoctypeSystemIdentifierDoubleQuoted => self.doctype_identifier('"', false),
+            State::DoctypeSystemIdentifierSingleQuoted => self.doctype_identifier('\'', false),
+
+            State::AfterDoctypeSystemIdentifier => match self.consume() {
+                Some(c) if is_whitespace(c) => {}
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => self.emit_eof_in_doctype(),
+                c => {
+                    self.error(TokenizerError::UnexpectedCharacterAfterDoctypeSystemIdentifier); //does not force quirks
+                    self.reconsume(c, State::BogusDoctype);
+                }
+            },
+
+            State::BogusDoctype => match self.consume() {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                Some(_) => {} //everything else is ignored, including '\0' (unexpected-null-character)
+                None => {
+                    self.emit_current_doctype();
+                    self.emit(Token::Eof);
+                }
+            },
+
+            //ANCHOR CDATA Sections
+            //Only reached inside <svg> or <math>, everything up to "]]>" is plain text, no tags and no character references
+            State::CdataSection => match self.consume() {
+                Some(']') => self.state = State::CdataSectionBracket,
+                Some(c) => self.emit_char(c),
+                None => {
+                    self.error(TokenizerError::EofInCdata);
+                    self.emit(Token::Eof);
+                }
+            },
+
+            State::CdataSectionBracket => match self.consume() {
+                Some(']') => self.state = State::CdataSectionEnd,
+                c => {
+                    self.emit_char(']');
+                    self.reconsume(c, State::CdataSection);
+                }
+            },
+
+            State::CdataSectionEnd => match self.consume() {
+                Some(']') => self.emit_char(']'), //"]]]>", the first ']' is text
+                Some('>') => self.state = State::Data,
+                c => {
+                    self.emit_str("]]");
+                    self.reconsume(c, State::CdataSection);
+                }
+            },
+
+            //ANCHOR Character References
+            //"&amp;", "&#38;" and "&#x26;" all turn into '&', the temp buffer holds what we read so far
+            //so it can go out unchanged when it turns out not to be a reference
+            State::CharacterReference => {
+                self.temp_buffer.clear();
+                self.temp_buffer.push('&');
+                match self.consume() {
+                    Some(c) if c.is_ascii_alphanumeric() => self.reconsume(Some(c), State::NamedCharacterReference),
+                    Some('#') => {
+                        self.temp_buffer.push('#');
+                        self.state = State::NumericCharacterReference;
+                    }
+                    c => {
+                        self.flush_character_reference();
+                        let return_state = self.return_state;
+                        self.reconsume(c, return_state);
+                    }
+                }
+            }
+
+            State::NamedCharacterReference => self.named_character_reference(),
+
+            State::AmbiguousAmpersand => match self.consume() {
+                Some(c) if c.is_ascii_alphanumeric() => {
+                    if self.in_attribute_value() {
+                        self.push_attribute_value(c);
+                    } else {
+                        self.emit_char(c);
+                    }
+                }
+                c => {
+                    if c == Some(';') {
+                        self.error(TokenizerError::UnknownNamedCharacterReference);
+                    }
+                    let return_state = self.return_state;
+                    self.reconsume(c, return_state);
+                }
+            },
+
+            State::NumericCharacterReference => {
+                self.char_ref_code = 0;
+                match self.consume() {
+                    Some(c) if c == 'x' || c == 'X' => {
+                        self.temp_buffer.push(c);
+                        self.state = State::HexadecimalCharacterReferenceStart;
+                    }
+                    c => self.reconsume(c, State::DecimalCharacterReferenceStart),
+                }
+            }
+
+            State::HexadecimalCharacterReferenceStart => match self.consume() {
+                Some(c) if c.is_ascii_hexdigit() => self.reconsume(Some(c), State::HexadecimalCharacterReference),
+                c => self.absence_of_digits(c),
+            },
+
+            State::DecimalCharacterReferenceStart => match self.consume() {
+                Some(c) if c.is_ascii_digit() => self.reconsume(Some(c), State::DecimalCharacterReference),
+                c => self.absence_of_digits(c),
+            },
+
+            State::HexadecimalCharacterReference => self.numeric_digits(16),
+            State::DecimalCharacterReference => self.numeric_digits(10),
+
+            State::NumericCharacterReferenceEnd => {
+                if let Some(error) = numeric_reference_error(self.char_ref_code) {
+                    self.error(error);
+                }
+                self.temp_buffer = numeric_reference_value(self.char_ref_code).to_string();
+                self.flush_character_reference();
+                self.state = self.return_state;
+            }
+        }
+    }
+
+    fn is_text_state(&self) -> bool {
+        matches!(
+            self.state,
+            State::Data
+                | State::RcData
+                | State::RawText
+                | State::ScriptData
+                | State::ScriptDataEscaped
+                | State::ScriptDataDoubleEscaped
+                | State::PlainText
+                | State::CdataSection
+        )
+    }
+
+    //ANCHOR Character Reference Helpers
+    fn start_character_reference(&mut self, return_state: State) {
+        self.return_state = return_state;
+        self.state = State::CharacterReference;
+    }
+
+    fn in_attribute_value(&self) -> bool {
+        matches!(
+            self.return_state,
+            State::AttributeValueDoubleQuoted | State::AttributeValueSingleQuoted | State::AttributeValueUnquoted
+        )
+    }
+
+    //Hand the temp buffer to wherever the reference appeared, the attribute value or the text
+    fn flush_character_reference(&mut self) {
+        let buffer = mem::take(&mut self.temp_buffer);
+        if self.in_attribute_value() {
+            for c in buffer.chars() {
+                self.push_attribute_value(c);
+            }
+        } else {
+            self.emit_str(&buffer);
+        }
+    }
+
+    //Match the longest name from the entity table, so "&notin;" is ∉ and not ¬ followed by "in;"
+    fn named_character_reference(&mut self) {
+        let mut candidate = String::new();
+        let mut i = self.pos;
+        while candidate.len() < entities::LONGEST_ENTITY_NAME {
+            match self.char_at(i) {
+                Some(c) if c.is_ascii_alphanumeric() => candidate.push(c),
+                Some(';') => {
+                    candidate.push(';');
+                    break;
+                }
+                _ => break,
+            }
+            i += 1;
+        }
+
+        let found = (1..=candidate.len())
+            .rev()
+            .filter_map(|len| entities::lookup(&candidate[..len]).map(|value| (len, value)))
+            .next();
+
+        match found {
+            Some((len, value)) => {
+                let name = candidate[..len].to_string();
+                self.skip(len);
+                self.temp_buffer.push_str(&name);
+
+                let next = self.char_at(self.pos);
+                let next_continues = next.is_some_and(|c| c == '=' || c.is_ascii_alphanumeric());
+                if self.in_attribute_value() && !name.ends_with(';') && next_continues {
+                    //"&copy=" inside an attribute (think query strings in urls) is left alone for historical reasons
+                    self.flush_character_reference();
+                } else {
+                    if !name.ends_with(';') {
+                        self.error(TokenizerError::MissingSemicolonAfterCharacterReference);
+                    }
+                    self.temp_buffer = value.to_string();
+                    self.flush_character_reference();
+                }
+                self.state = self.return_state;
+            }
+            None => {
+                self.flush_character_reference();
+                self.state = State::AmbiguousAmpersand;
+            }
+        }
+    }
+
+    fn absence_of_digits(&mut self, c: Option<char>) {
+        self.error(TokenizerError::AbsenceOfDigitsInNumericCharacterReference);
+        self.flush_character_reference();
+        let return_state = self.return_state;
+        self.reconsume(c, return_state);
+    }
+
+    //Accumulate the digits, the value is capped once it's out of the unicode range so it can't overflow
+    fn numeric_digits(&mut self, radix: u32) {
+        match self.consume() {
+            Some(c) if c.is_digit(radix) => {
+                let digit = c.to_digit(radix).unwrap();
+                self.char_ref_code = self.char_ref_code.saturating_mul(radix).saturating_add(digit).min(0x110000);
+            }
+            Some(';') => self.state = State::NumericCharacterReferenceEnd,
+            c => {
+                self.error(TokenizerError::MissingSemicolonAfterCharacterReference);
+                self.reconsume(c, State::NumericCharacterReferenceEnd);
+            }
+        }
+    }
+
+    //ANCHOR Shared State Bodies
+    //"<" inside RCDATA or RAWTEXT, only "</" can start the end tag
+    fn text_less_than_sign(&mut self, text_state: State, end_tag_open: State) {
+        match self.consume() {
+            Some('/') => {
+                self.temp_buffer.clear();
+                self.state = end_tag_open;
+            }
+            c => {
+                self.emit_char('<');
+                self.reconsume(c, text_state);
+            }
+        }
+    }
+
+    fn text_end_tag_open(&mut self, text_state: State, end_tag_name: State) {
+        match self.consume() {
+            Some(c) if c.is_ascii_alphabetic() => {
+                self.start_tag(true);
+                self.reconsume(Some(c), end_tag_name);
+            }
+            c => {
+                self.emit_str("</");
+                self.reconsume(c, text_state);
+            }
+        }
+    }
+
+    //Only the end tag that matches the element we're in ends the text, anything else goes out as characters
+    fn text_end_tag_name(&mut self, text_state: State) {
+        let c = self.consume();
+        match c {
+            Some(ch) if is_whitespace(ch) && self.is_appropriate_end_tag() => {
+                self.state = State::BeforeAttributeName;
+                return;
+            }
+            Some('/') if self.is_appropriate_end_tag() => {
+                self.state = State::SelfClosingStartTag;
+                return;
+            }
+            Some('>') if self.is_appropriate_end_tag() => {
+                self.state = State::Data;
+                self.emit_current_tag();
+                return;
+            }
+            Some(ch) if ch.is_ascii_alphabetic() => {
+                self.current_tag.name.push(ch.to_ascii_lowercase());
+                self.temp_buffer.push(ch);
+                return;
+            }
+            _ => {}
+        }
+
+        self.emit_str("</");
+        let buffer = mem::take(&mut self.temp_buffer);
+        self.emit_str(&buffer);
+        self.reconsume(c, text_state);
+    }
+
+    //"<script" inside an escaped script enters the double escaped state and "</script" leaves it again
+    fn double_escape_boundary(&mut self, script_state: State, other_state: State) {
+        match self.consume() {
+            Some(c) if is_whitespace(c) || c == '/' || c == '>' => {
+                self.state = if self.temp_buffer == "script" { script_state } else { other_state };
+                self.emit_char(c);
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                self.temp_buffer.push(c.to_ascii_lowercase());
+                self.emit_char(c);
+            }
+            c => self.reconsume(c, other_state),
+        }
+    }
+
+    //Attribute value in double or single quotes, only difference is which quote ends it
+    fn quoted_attribute_value(&mut self, quote: char) {
+        match self.consume() {
+            Some(c) if c == quote => self.state = State::AfterAttributeValueQuoted,
+            Some('&') => {
+                let state = self.state;
+                self.start_character_reference(state);
+            }
+            Some('\0') => {
+                self.error(TokenizerError::UnexpectedNullCharacter);
+                self.push_attribute_value('\u{FFFD}');
+            }
+            Some(c) => self.push_attribute_value(c),
+            None => {
+                self.error(TokenizerError::EofInTag);
+                self.emit(Token::Eof);
+            }
+        }
+    }
+
+    //Public or system identifier in double or single quotes
+    fn doctype_identifier(&mut self, quote: char, public: bool) {
+        match self.consume() {
+            Some(c) if c == quote => {
+                self.state = if public {
+                    State::AfterDoctypePublicIdentifier
+                } else {
+                    State::AfterDoctypeSystemIdentifier
+                };
+            }
+            Some('\0') => {
+                self.error(TokenizerError::UnexpectedNullCharacter);
+                if public {
+                    self.push_public_id('\u{FFFD}');
+                } else {
+                    self.push_system_id('\u{FFFD}');
+                }
+            }
+            Some('>') => {
+                self.error(if public {
+                    TokenizerError::AbruptDoctypePublicIdentifier
+                } else {
+                    TokenizerError::AbruptDoctypeSystemIdentifier
+                });
+                self.current_doctype.force_quirks = true;
+                self.state = State::Data;
+                self.emit_current_doctype();
+            }
+            Some(c) => {
+                if public {
+                    self.push_public_id(c);
+                } else {
+                    self.push_system_id(c);
+                }
+            }
+            None => self.emit_eof_in_doctype(),
+        }
+    }
+
+    fn missing_doctype_identifier(&mut self, public: bool) {
+        self.error(if public {
+            TokenizerError::MissingDoctypePublicIdentifier
+        } else {
+            TokenizerError::MissingDoctypeSystemIdentifier
+        });
+        self.current_doctype.force_quirks = true;
+        self.state = State::Data;
+        self.emit_current_doctype();
+    }
+
+    fn missing_quote_before_doctype_identifier(&mut self, c: Option<char>, public: bool) {
+        self.error(if public {
+            TokenizerError::MissingQuoteBeforeDoctypePublicIdentifier
+        } else {
+            TokenizerError::MissingQuoteBeforeDoctypeSystemIdentifier
+        });
+        self.current_doctype.force_quirks = true;
+        self.reconsume(c, State::BogusDoctype);
+    }
+
+    fn eof_in_script_comment(&mut self) {
+        self.error(TokenizerError::EofInScriptHtmlCommentLikeText);
+        self.emit(Token::Eof);
+    }
+
+    fn eof_in_comment(&mut self) {
+        self.error(TokenizerError::EofInComment);
+        self.emit_current_comment();
+        self.emit(Token::Eof);
+    }
+}
+
+//Lets us write `for token in tokenizer` in the tree builder
+impl Iterator for Tokenizer {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        self.next_token()
+    }
+}
+
+// NOTE 4- Helper Methods
+
+//Normalize newlines before tokenizing, "\r\n" and lone "\r" both become "\n"
+fn preprocess(input: &str) -> Vec<char> {
+    let mut chars = Vec::with_capacity(input.len());
+    let mut iter = input.chars().peekable();
+
+    while let Some(c) = iter.next() {
+        if c == '\r' {
+            if iter.peek() == Some(&'\n') {
+                iter.next();
+            }
+            chars.push('\n');
+        } else {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
+//The code point a numeric reference stands for, with the spec's replacements for the invalid ones
+//The value still gets used (or replaced) after these, they only tell the author the reference is wrong
+fn numeric_reference_error(code: u32) -> Option<TokenizerError> {
+    match code {
+        0 => Some(TokenizerError::NullCharacterReference),
+        c if c > 0x10FFFF => Some(TokenizerError::CharacterReferenceOutsideUnicodeRange),
+        0xD800..=0xDFFF => Some(TokenizerError::SurrogateCharacterReference),
+        0xFDD0..=0xFDEF => Some(TokenizerError::NoncharacterCharacterReference),
+        c if c & 0xFFFE == 0xFFFE => Some(TokenizerError::NoncharacterCharacterReference),
+        0x0D | 0x01..=0x08 | 0x0B | 0x0E..=0x1F | 0x7F..=0x9F => Some(TokenizerError::ControlCharacterReference),
+        _ => None,
+    }
+}
+
+fn numeric_reference_value(code: u32) -> char {
+    match code {
+        0 => '\u{FFFD}',                         //null-character-reference
+        0xD800..=0xDFFF => '\u{FFFD}',           //surrogate-character-reference
+        c if c > 0x10FFFF => '\u{FFFD}',         //character-reference-outside-unicode-range
+        0x80..=0x9F => windows_1252_replacement(code),
+        _ => std::char::from_u32(code).unwrap_or('\u{FFFD}'),
+    }
+}
+
+//Old pages use references like "&#150;" meaning the windows-1252 character, not the C1 control
+fn windows_1252_replacement(code: u32) -> char {
+    let replaced = match code {
+        0x80 => 0x20AC,
+        0x82 => 0x201A,
+        0x83 => 0x0192,
+        0x84 => 0x201E,
+        0x85 => 0x2026,
+        0x86 => 0x2020,
+        0x87 => 0x2021,
+        0x88 => 0x02C6,
+        0x89 => 0x2030,
+        0x8A => 0x0160,
+        0x8B => 0x2039,
+        0x8C => 0x0152,
+        0x8E => 0x017D,
+        0x91 => 0x2018,
+        0x92 => 0x2019,
+        0x93 => 0x201C,
+        0x94 => 0x201D,
+        0x95 => 0x2022,
+        0x96 => 0x2013,
+        0x97 => 0x2014,
+        0x98 => 0x02DC,
+        0x99 => 0x2122,
+        0x9A => 0x0161,
+        0x9B => 0x203A,
+        0x9C => 0x0153,
+        0x9E => 0x017E,
+        0x9F => 0x0178,
+        _ => code,
+    };
+    std::char::from_u32(replaced).unwrap_or('\u{FFFD}')
+}
+
+//HTML whitespace is tab, line feed, form feed and space (carriage returns are gone after preprocessing)
+pub fn is_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\u{000C}' | ' ')
+}
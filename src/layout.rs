@@ -403,7 +403,11 @@ fn build_layout_tree<'a>(node: &'a StyledNode) -> LayoutBox<'a> {
         node,
     );
 
+    //generated boxes (::marker, ::before, ::after, the ::first-letter piece of a text) are children like any other
     for child in &node.children {
+        if child.is_collapsible_whitespace() {
+            continue; //whitespace between tags collapses away, it doesn't get a box
+        }
         match child.get_display() {
             Display::Block => layout_node.children.push(build_layout_tree(child)),
             Display::Inline => layout_node.children.push(build_layout_tree(child)),
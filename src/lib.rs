@@ -3,20 +3,995 @@ extern crate gfx;
 extern crate gfx_text;
 extern crate gfx_window_glutin;
 extern crate glutin;
+extern crate encoding_rs;
 
 pub mod render;
 pub mod command;
 pub mod dom;    //Help us to parse the Dom or the document object model
+pub mod document; //Arena Dom with parent and sibling links
+pub mod mutation; //Mutation records and observers for the arena Dom
+pub mod dom_diff; //What changed between two Dom trees
 pub mod html_parse;
+pub mod html_serialize;
+pub mod html_tokenizer;
+pub mod entities;
+pub mod encoding;
+pub mod source;
 pub mod css;
+pub mod css_parser;
+pub mod css_tokenizer;
 pub mod style;
 pub mod layout;
 
 #[cfg(test)]
 mod tests {
+    use crate::css::{CssRule, Device, Nth, PseudoClass};
+    use crate::css_parser::{CssErrorKind, CssParser};
+    use crate::css_tokenizer::{CssToken, CssTokenizer, CssTokenizerError};
+    use crate::document::{Document, DomError, NodeId};
+    use crate::dom_diff::{diff, diff_with, report, DiffOptions, Edit};
+    use crate::dom::{DoctypeData, ElementState, Namespace, Node, NodeType, QuirksMode};
+    use crate::encoding::sniff_encoding;
+    use crate::mutation::{changed_nodes, MutationKind, MutationRecord, ObserveOptions};
+    use crate::html_parse::{HtmlErrorKind, HtmlParser};
+    use crate::html_serialize::{inner_html, outer_html};
+    use crate::html_tokenizer::{Attribute, Doctype, Tag, Token, Tokenizer, TokenizerError};
+    use crate::source::{Position, Span};
+    use crate::style::{collapse_whitespace, StyledNode, WhiteSpace};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use encoding_rs::{ISO_8859_2, SHIFT_JIS, UTF_16LE, UTF_8, WINDOWS_1252};
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
-}
 
+    // NOTE 1- Helpers
+
+    fn tokens(html: &str) -> Vec<Token> {
+        Tokenizer::new(html).collect()
+    }
+
+    fn tag(name: &str, attributes: &[(&str, &str)]) -> Tag {
+        let attributes = attributes.iter().map(|&(name, value)| Attribute { name: name.to_string(), value: value.to_string() }).collect();
+        Tag { name: name.to_string(), self_closing: false, attributes }
+    }
+
+    //A short outline of the tree, "div(p(\"a\") b)"
+    fn outline(nodes: &[Node]) -> String {
+        nodes
+            .iter()
+            .map(|node| match node.node_type {
+                NodeType::Element(ref e) if node.children.is_empty() => e.tag_name.clone(),
+                NodeType::Element(ref e) => format!("{}({})", e.tag_name, outline(&node.children)),
+                NodeType::Text(ref t) => format!("{:?}", t),
+                NodeType::Comment(ref c) => format!("<!--{}-->", c),
+                NodeType::Doctype(ref d) => format!("<!DOCTYPE {}>", d.name),
+                NodeType::Document(_) => format!("#document({})", outline(&node.children)),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn parse_outline(html: &str) -> String {
+        outline(&HtmlParser::new(html).parse_nodes())
+    }
+
+    fn outer_html_all(nodes: &[Node]) -> String {
+        nodes.iter().map(outer_html).collect()
+    }
+
+    //What ends up inside <body> for a whole document
+    fn body_html(html: &str) -> String {
+        let document = HtmlParser::new(html).parse_document();
+        let root = document.children.last().unwrap();
+        inner_html(&root.children[1])
+    }
+
+    //The An+B inside :nth-child(), None when the selector didn't parse
+    fn nth(text: &str) -> Option<Nth> {
+        let mut parser = CssParser::new(&format!(":nth-child({})", text));
+        let selectors = parser.parse_selector_list();
+        if !parser.errors().is_empty() {
+            return None;
+        }
+        match selectors.first()?.simple.first()?.pseudo_classes.first()? {
+            PseudoClass::NthChild(nth, _) => Some(*nth),
+            _ => None,
+        }
+    }
+
+    // NOTE 2- HTML Tokenizer & Tree Builder
+
+    #[test]
+    fn tokenizer_tags_and_attributes() {
+        let expected = vec![
+            Token::StartTag(tag("a", &[("href", "x"), ("title", "y z"), ("id", "")])),
+            Token::Character('t'),
+            Token::EndTag(tag("a", &[])),
+            Token::Eof,
+        ];
+        assert_eq!(tokens("<A HREF=x title='y z' id>t</a >"), expected);
+        //a duplicate attribute is dropped, the first one wins
+        assert_eq!(tokens("<p a=1 a=2>")[0], Token::StartTag(tag("p", &[("a", "1")])));
+        //"<" that doesn't start a tag is just text
+        assert_eq!(tokens("a < b"), "a < b".chars().map(Token::Character).chain(Some(Token::Eof)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tokenizer_comments_and_doctype() {
+        assert_eq!(tokens("<!-- x -- y -->"), vec![Token::Comment(String::from(" x -- y ")), Token::Eof]);
+        assert_eq!(tokens("<!-->"), vec![Token::Comment(String::new()), Token::Eof]);
+        assert_eq!(tokens("<?php x ?>"), vec![Token::Comment(String::from("?php x ?")), Token::Eof]);
+        let doctype = Doctype { name: Some(String::from("html")), ..Doctype::default() };
+        assert_eq!(tokens("<!DOCTYPE HTML>"), vec![Token::Doctype(doctype), Token::Eof]);
+        //the file ends in the middle of a tag, the tag is dropped
+        assert_eq!(tokens("x<div class="), vec![Token::Character('x'), Token::Eof]);
+    }
+
+    #[test]
+    fn tree_from_tokens() {
+        assert_eq!(parse_outline("<div><p>Hi <b>there</b></p><!--c--></div>"), r#"html(head body(div(p("Hi " b("there")) <!--c-->)))"#);
+    }
+
+    #[test]
+    fn tree_builder_fixes_broken_markup() {
+        //the missing html, head and body are made up
+        assert_eq!(parse_outline("x"), r#"html(head body("x"))"#);
+        //implied end tags
+        assert_eq!(parse_outline("<p>a<p>b<ul><li>c<li>d</ul>"), r#"html(head body(p("a") p("b") ul(li("c") li("d"))))"#);
+        //adoption agency
+        assert_eq!(parse_outline("<b>1<i>2</b>3</i>"), r#"html(head body(b("1" i("2")) i("3")))"#);
+        assert_eq!(parse_outline("<a>1<p>2</a>3</p>"), r#"html(head body(a("1") p(a("2") "3")))"#);
+        //foster parenting, text and elements that can't go in a table end up before it
+        assert_eq!(parse_outline("<table>x<tr><td>y</td></tr></table>"), r#"html(head body("x" table(tbody(tr(td("y"))))))"#);
+        assert_eq!(parse_outline("<table><div>z</div></table>"), r#"html(head body(div("z") table))"#);
+        //a stray end tag is ignored, a nested select closes the open one
+        assert_eq!(parse_outline("<div>a</span>b</div><select><option>1<select>"), r#"html(head body(div("ab") select(option("1"))))"#);
+    }
+
+    #[test]
+    fn void_elements_and_raw_text() {
+        //void elements never get children, "<div/>" isn't closed by its slash
+        assert_eq!(parse_outline("<p>a<br>b<img src=x>c<input/>d"), r#"html(head body(p("a" br "b" img "c" input "d")))"#);
+        assert_eq!(parse_outline("<div/>x"), r#"html(head body(div("x")))"#);
+        assert_eq!(parse_outline("</br>"), r#"html(head body(br))"#);
+        //<title> and <textarea> hold text, <script> and <style> hold it without even looking for "&"
+        assert_eq!(parse_outline("<title>a <b> c</title><p>x"), r#"html(head(title("a <b> c")) body(p("x")))"#);
+        assert_eq!(parse_outline("<textarea><p>x</textarea>"), r#"html(head body(textarea("<p>x")))"#);
+        assert_eq!(parse_outline("<script>if (a</b) {}</script>"), r#"html(head(script("if (a</b) {}")) body)"#);
+        assert_eq!(parse_outline("<style>p > a {}</STYLE>"), r#"html(head(style("p > a {}")) body)"#);
+    }
+
+    #[test]
+    fn character_references() {
+        let text = |html: &str| tokens(html).into_iter().filter_map(|t| if let Token::Character(c) = t { Some(c) } else { None }).collect::<String>();
+        assert_eq!(text("&amp; &LT; &copy &notit; &#x41;&#65; &#150; &#0; &bogus;"), "& < \u{a9} \u{ac}it; AA \u{2013} \u{fffd} &bogus;");
+        //in an attribute "&copy=" stays as it is, query strings in urls are full of those
+        let expected = tag("a", &[("href", "?a=1&copy=2&b"), ("title", "\u{a9}")]);
+        assert_eq!(tokens("<a href='?a=1&copy=2&amp;b' title=&copy;>")[0], Token::StartTag(expected));
+        //<script> text isn't decoded
+        assert_eq!(parse_outline("<script>a &amp; b</script>"), r#"html(head(script("a &amp; b")) body)"#);
+    }
+
+    #[test]
+    fn doctype_and_quirks_mode() {
+        let quirks_mode = |html: &str| match HtmlParser::new(html).parse_document().node_type {
+            NodeType::Document(mode) => mode,
+            _ => unreachable!(),
+        };
+        assert_eq!(quirks_mode("<!DOCTYPE html><p>x"), QuirksMode::NoQuirks);
+        assert_eq!(quirks_mode("<p>x"), QuirksMode::Quirks);
+        assert_eq!(quirks_mode("<!DOCTYPE html SYSTEM \"about:legacy-compat\">"), QuirksMode::NoQuirks);
+        let html4 = "<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\"";
+        assert_eq!(quirks_mode(&format!("{}>", html4)), QuirksMode::Quirks);
+        assert_eq!(quirks_mode(&format!("{} \"http://www.w3.org/TR/html4/loose.dtd\">", html4)), QuirksMode::LimitedQuirks);
+        assert_eq!(quirks_mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"x\">"), QuirksMode::LimitedQuirks);
+        assert_eq!(quirks_mode("<!DOCTYPE html PUBLIC \"-//W3O//DTD W3 HTML Strict 3.0//EN//\">"), QuirksMode::Quirks);
+        assert_eq!(quirks_mode("<!DOCTYPE svg>"), QuirksMode::Quirks);
+        //the doctype is the document's first child, a second one is ignored
+        let document = HtmlParser::new("<!-- a --><!DOCTYPE html><!DOCTYPE x>").parse_document();
+        assert_eq!(outline(&[document]), "#document(<!-- a --> <!DOCTYPE html> html(head body))");
+    }
+
+    #[test]
+    fn parse_errors_in_page_order() {
+        let mut parser = HtmlParser::new("<!doctype html>\n<p a a>x</b><div/>\n<!-- y");
+        let document = parser.parse_document();
+        let errors: Vec<(HtmlErrorKind, Position)> = parser.errors().iter().map(|e| (e.kind.clone(), e.span.start)).collect();
+        let expected = vec![
+            (HtmlErrorKind::Tokenizer(TokenizerError::DuplicateAttribute), Position::new(2, 7)),
+            (HtmlErrorKind::UnexpectedEndTag(String::from("b")), Position::new(2, 9)),
+            (HtmlErrorKind::NonVoidSelfClosingTag(String::from("div")), Position::new(2, 13)),
+            (HtmlErrorKind::UnclosedElement(String::from("div")), Position::new(3, 1)),
+            (HtmlErrorKind::Tokenizer(TokenizerError::EofInComment), Position::new(3, 6)),
+        ];
+        assert_eq!(errors, expected);
+
+        //spans run from the start tag to the end tag or whatever came last inside, made up elements cover what ended up in them
+        let body = &document.children[1].children[1];
+        assert_eq!(body.span, Span::new(Position::new(2, 1), Position::new(3, 7)));
+        assert_eq!(body.children[0].span, Span::new(Position::new(2, 1), Position::new(2, 9)));
+        assert_eq!(body.children[0].children[0].span, Span::new(Position::new(2, 8), Position::new(2, 9)));
+    }
+
+    #[test]
+    fn encoding_sniffing() {
+        assert_eq!(sniff_encoding(b"\xEF\xBB\xBF<meta charset=shift_jis>"), UTF_8); //the byte order mark wins
+        assert_eq!(sniff_encoding(b"\xFF\xFE<\0p\0>\0"), UTF_16LE);
+        assert_eq!(sniff_encoding(b"<!DOCTYPE html><meta charset=\"Shift_JIS\">"), SHIFT_JIS);
+        assert_eq!(sniff_encoding(b"<meta http-equiv=Content-Type content='text/html; charset=iso-8859-2'>"), ISO_8859_2);
+        //content needs the http-equiv next to it, a <meta> in a comment or an attribute value doesn't count
+        assert_eq!(sniff_encoding(b"<meta content='text/html; charset=iso-8859-2'>"), WINDOWS_1252);
+        assert_eq!(sniff_encoding(b"<!-- <meta charset=utf-8> --><p title='<meta charset=utf-8>'>"), WINDOWS_1252);
+        //an ASCII page that says UTF-16 is really UTF-8, a label nobody knows is skipped
+        assert_eq!(sniff_encoding(b"<meta charset=utf-16>"), UTF_8);
+        assert_eq!(sniff_encoding(b"<meta charset=nope><meta charset=utf-8>"), UTF_8);
+        assert_eq!(sniff_encoding(b"<p>plain"), WINDOWS_1252);
+
+        let mut parser = HtmlParser::from_bytes(b"<p>caf\xE9 \x93q\x94");
+        assert_eq!(parser.encoding(), WINDOWS_1252);
+        assert_eq!(outline(&parser.parse_nodes()), "html(head body(p(\"caf\u{e9} \u{201c}q\u{201d}\")))");
+    }
+
+    #[test]
+    fn html_fragments() {
+        let fragment = |context: &str, html: &str| outline(&HtmlParser::new_fragment(context, html).parse_fragment());
+        assert_eq!(fragment("ul", "<li>a<li>b"), r#"li("a") li("b")"#);
+        assert_eq!(fragment("tr", "<td>a<td>b"), r#"td("a") td("b")"#);
+        assert_eq!(fragment("TABLE", "<tr><td>x"), r#"tbody(tr(td("x")))"#);
+        //the context decides how the text is read
+        assert_eq!(fragment("textarea", "<b>x</b>"), r#""<b>x</b>""#);
+        assert_eq!(fragment("title", "a &amp; <i>"), r#""a & <i>""#);
+        assert_eq!(fragment("script", "a &amp; <i>"), r#""a &amp; <i>""#);
+        //<body> and <html> in the fragment don't show up, there's only one root
+        assert_eq!(fragment("div", "<body class=x><p>1</p></html>2"), r#"p("1") "2""#);
+        //in a <select> context a nested <select> has nothing to close, it is ignored
+        assert_eq!(fragment("select", "<option>1<select><option>2"), r#"option("1") option("2")"#);
+    }
+
+    #[test]
+    fn foreign_content() {
+        //SVG names get their case back, attributes too, and anything can close itself
+        assert_eq!(body_html("<svg viewbox='0 0 1 1'><lineargradient/><path/>x</svg>"), r#"<svg viewBox="0 0 1 1"><linearGradient></linearGradient><path></path>x</svg>"#);
+        //HTML like <p> breaks out, <foreignObject> and MathML text elements hold HTML
+        assert_eq!(body_html("<svg><g><p>y"), "<svg><g></g></svg><p>y</p>");
+        assert_eq!(body_html("<svg><foreignObject><p>z</p></foreignObject></svg>"), "<svg><foreignObject><p>z</p></foreignObject></svg>");
+        assert_eq!(body_html("<math><mi><b>1</b></mi><mo/></math>"), "<math><mi><b>1</b></mi><mo></mo></math>");
+        assert_eq!(body_html("<svg><![CDATA[<a>]]><style>a &amp; b</style></svg>"), "<svg>&lt;a&gt;<style>a &amp; b</style></svg>");
+        let svg = HtmlParser::new("<svg>").parse_nodes().remove(0).children.remove(1).children.remove(0);
+        assert!(matches!(svg.node_type, NodeType::Element(ref e) if e.namespace == Namespace::Svg));
+    }
+
+    #[test]
+    fn foreign_fragments() {
+        let fragment = |context: &str, namespace, html: &str| outer_html_all(&HtmlParser::new_fragment_in(context, namespace, html).parse_fragment());
+        //inside <svg> tags get SVG names and can close themselves, HTML like <p> breaks out
+        assert_eq!(fragment("svg", Namespace::Svg, "<lineargradient/><path/>x<p>y"), "<linearGradient></linearGradient><path></path>x<p>y</p>");
+        assert_eq!(fragment("svg", Namespace::Svg, "<![CDATA[<a>]]>"), "&lt;a&gt;");
+        //the context keeps its SVG case, and <foreignObject> holds HTML again
+        assert_eq!(fragment("foreignObject", Namespace::Svg, "<div/>z"), "<div>z</div>");
+        assert_eq!(fragment("math", Namespace::MathMl, "<mi/>x"), "<mi></mi>x");
+        //in an HTML context the same markup is HTML, the slash does nothing
+        assert_eq!(fragment("div", Namespace::Html, "<path/>x"), "<path>x</path>");
+    }
+
+    // NOTE 3- Streaming
+
+    #[test]
+    fn chunks_parse_like_the_whole_input() {
+        let html = "<!DOCTYPE html><title>T &amp; t</title><p class=a>Hello <b>wor<i>ld</b>!</i><!-- c -->\r\n<table>x<tr><td>1</table><script>if (a < b) {}</script>&eacute;&#x263A;&notit;";
+        let mut whole = HtmlParser::new(html);
+        let expected = whole.parse_document();
+        let chars: Vec<char> = html.chars().collect();
+        for size in 1..8 {
+            let mut streaming = HtmlParser::streaming();
+            for chunk in chars.chunks(size) {
+                streaming.feed(&chunk.iter().collect::<String>());
+            }
+            assert_eq!(streaming.finish(), expected, "chunks of {}", size);
+            assert_eq!(streaming.errors(), whole.errors(), "chunks of {}", size);
+        }
+    }
+
+    #[test]
+    fn document_while_streaming() {
+        let mut parser = HtmlParser::streaming();
+        parser.feed("<ul><li>a<li>b");
+        //a snapshot of what is there so far, the text in the open <li> can still grow
+        assert_eq!(outline(&[parser.document()]), r#"#document(html(head body(ul(li("a") li("b")))))"#);
+        parser.feed("c</ul><p>d");
+        assert_eq!(outline(&[parser.finish()]), r#"#document(html(head body(ul(li("a") li("bc")) p("d"))))"#);
+    }
+
+    // NOTE 4- Document
+
+    fn names(document: &Document, ids: impl Iterator<Item = NodeId>) -> Vec<String> {
+        ids.map(|id| match *document.node_type(id) {
+            NodeType::Element(ref e) => e.tag_name.clone(),
+            NodeType::Text(ref t) => format!("{:?}", t),
+            _ => String::from("#"),
+        })
+        .collect()
+    }
+
+    //The first element with that tag name
+    fn find(document: &Document, tag_name: &str) -> NodeId {
+        document.descendants(document.root()).find(|&id| document.element(id).is_some_and(|e| e.tag_name == tag_name)).unwrap()
+    }
+
+    #[test]
+    fn arena_links() {
+        let node = HtmlParser::new("<!DOCTYPE html><ul><li>a</li><li><b>b</b></li></ul>").parse_document();
+        let document = Document::from_node(&node);
+        let root = document.root();
+        assert!(matches!(*document.node_type(root), NodeType::Document(QuirksMode::NoQuirks)));
+        assert_eq!(names(&document, document.descendants(root)), vec!["#", "#", "html", "head", "body", "ul", "li", "\"a\"", "li", "b", "\"b\""]);
+
+        let ul = find(&document, "ul");
+        let items: Vec<NodeId> = document.children(ul).collect();
+        assert_eq!(items.len(), 2);
+        assert_eq!(document.first_child(ul), Some(items[0]));
+        assert_eq!(document.last_child(ul), Some(items[1]));
+        assert_eq!(document.next_sibling(items[0]), Some(items[1]));
+        assert_eq!(document.previous_sibling(items[0]), None);
+        assert_eq!(document.parent(items[1]), Some(ul));
+
+        let text = document.descendants(items[1]).last().unwrap();
+        assert_eq!(names(&document, document.ancestors(text)), vec!["b", "li", "ul", "body", "html", "#"]);
+        assert_eq!(document.closest(text, "li"), Some(items[1]));
+        assert_eq!(document.closest(text, "p"), None);
+        //and back out again, the same tree
+        assert_eq!(document.to_node(root), node);
+        assert_eq!(document.len(), 11);
+    }
+
+    #[test]
+    fn tree_mutations() {
+        let mut document = Document::from_node(&HtmlParser::new("<ul><li>a</li></ul><p>").parse_document());
+        let body = |document: &Document| inner_html(&document.to_node(find(document, "body")));
+        let (ul, p, first) = (find(&document, "ul"), find(&document, "p"), find(&document, "li"));
+
+        let second = document.create_element("li");
+        let text = document.create_text_node("b");
+        assert_eq!(document.append_child(second, text), Ok(text));
+        assert_eq!(document.append_child(ul, second), Ok(second));
+        let zero = document.create_element("li");
+        document.insert_before(ul, zero, Some(first)).unwrap();
+        assert_eq!(body(&document), "<ul><li></li><li>a</li><li>b</li></ul><p></p>");
+
+        //a node that's already in the tree moves
+        document.append_child(p, first).unwrap();
+        document.set_attribute(p, "class", "x");
+        assert_eq!(body(&document), r#"<ul><li></li><li>b</li></ul><p class="x"><li>a</li></p>"#);
+
+        let comment = document.create_comment("gone");
+        assert_eq!(document.replace_child(ul, comment, zero), Ok(zero));
+        assert_eq!(document.parent(zero), None);
+        assert_eq!(document.remove_child(p, first), Ok(first));
+        assert_eq!(document.remove_child(p, first), Err(DomError::NotFound));
+        assert_eq!(document.remove_attribute(p, "class"), Some(String::from("x")));
+        assert_eq!(body(&document), "<ul><!--gone--><li>b</li></ul><p></p>");
+
+        //nothing goes into a text node or into itself, the reference has to be a child of the parent
+        assert_eq!(document.append_child(text, zero), Err(DomError::HierarchyRequest));
+        assert_eq!(document.append_child(second, ul), Err(DomError::HierarchyRequest));
+        assert_eq!(document.append_child(ul, ul), Err(DomError::HierarchyRequest));
+        assert_eq!(document.insert_before(ul, zero, Some(p)), Err(DomError::NotFound));
+        assert_eq!(body(&document), "<ul><!--gone--><li>b</li></ul><p></p>");
+
+        //textContent replaces everything inside an element, the document itself is left alone
+        document.set_text_content(ul, "c & d");
+        document.set_text_content(document.root(), "x");
+        assert_eq!(body(&document), "<ul>c &amp; d</ul><p></p>");
+    }
+
+    #[test]
+    fn pre_insertion_checks() {
+        let mut document = Document::from_node(&HtmlParser::new("<!DOCTYPE html><!-- c --><p>").parse_document());
+        let root = document.root();
+        let mut children = document.children(root);
+        let (doctype, comment, html) = (children.next().unwrap(), children.next().unwrap(), children.next().unwrap());
+        let body = find(&document, "body");
+
+        //no text right in the document
+        let text = document.create_text_node("x");
+        assert_eq!(document.append_child(root, text), Err(DomError::HierarchyRequest));
+        assert_eq!(document.replace_child(root, text, comment), Err(DomError::HierarchyRequest));
+
+        //only one element, a new one can only take the place of the one that's there
+        let div = document.create_element("div");
+        assert_eq!(document.append_child(root, div), Err(DomError::HierarchyRequest));
+        assert_eq!(document.insert_before(root, div, Some(comment)), Err(DomError::HierarchyRequest));
+        assert_eq!(document.replace_child(root, div, comment), Err(DomError::HierarchyRequest));
+        assert_eq!(document.replace_child(root, div, doctype), Err(DomError::HierarchyRequest));
+
+        //a doctype only goes into the document, once, and before the element
+        let second = document.create_node(NodeType::Doctype(DoctypeData::new(String::from("html"), String::new(), String::new())));
+        assert_eq!(document.append_child(body, second), Err(DomError::HierarchyRequest));
+        assert_eq!(document.replace_child(body, second, document.first_child(body).unwrap()), Err(DomError::HierarchyRequest));
+        assert_eq!(document.insert_before(root, second, Some(doctype)), Err(DomError::HierarchyRequest));
+        assert_eq!(document.replace_child(root, second, comment), Err(DomError::HierarchyRequest));
+        assert_eq!(document.replace_child(root, second, doctype), Ok(doctype));
+        document.remove_child(root, second).unwrap();
+        assert_eq!(document.append_child(root, second), Err(DomError::HierarchyRequest)); //after the <html>
+        assert_eq!(document.insert_before(root, second, Some(comment)), Ok(second));
+
+        //and an element can't go in front of the doctype
+        assert_eq!(document.replace_child(root, div, html), Ok(html));
+        document.remove_child(root, div).unwrap();
+        assert_eq!(document.insert_before(root, div, Some(second)), Err(DomError::HierarchyRequest));
+        assert_eq!(document.append_child(root, div), Ok(div));
+        assert_eq!(names(&document, document.children(root)), vec!["#", "#", "div"]);
+    }
+
+    #[test]
+    fn queries() {
+        let html = "<div id=main class='a b'><p class=b>1</p><P id=two class='a  b c'>2</P><svg><foreignObject/></svg></div><p id=main>";
+        let document = Document::from_node(&HtmlParser::new(html).parse_document());
+        let root = document.root();
+        let texts = |ids: Vec<NodeId>| names(&document, ids.into_iter());
+        let main = find(&document, "div");
+
+        assert_eq!(document.get_element_by_id("main"), Some(main)); //the first one wins
+        assert_eq!(document.get_element_by_id("nope"), None);
+        assert_eq!(texts(document.get_elements_by_tag_name(root, "P")), vec!["p", "p", "p"]);
+        assert_eq!(document.get_elements_by_tag_name(main, "p").len(), 2); //the scope itself doesn't count
+        assert_eq!(texts(document.get_elements_by_tag_name(root, "foreignObject")), vec!["foreignObject"]);
+        assert!(document.get_elements_by_tag_name(root, "foreignobject").is_empty());
+        assert_eq!(document.get_elements_by_tag_name(main, "*").len(), 4);
+        assert_eq!(document.get_elements_by_class_name(root, " b  a ").len(), 2);
+        assert_eq!(document.get_elements_by_class_name(main, "a b"), vec![document.get_element_by_id("two").unwrap()]);
+        assert!(document.get_elements_by_class_name(root, " ").is_empty());
+        let nbsp = Document::from_node(&HtmlParser::new("<p class='a\u{a0}b'><p class=a>").parse_document());
+        assert_eq!(nbsp.get_elements_by_class_name(nbsp.root(), "a\u{a0}b").len(), 1);
+        assert_eq!(nbsp.get_elements_by_class_name(nbsp.root(), "a").len(), 1);
+
+        assert_eq!(document.query_selector(root, "#two"), Ok(document.get_element_by_id("two")));
+        assert_eq!(document.query_selector_all(root, "p.b, svg").unwrap().len(), 3);
+        assert_eq!(document.query_selector_all(root, "div.a.b").unwrap(), vec![main]);
+        assert_eq!(document.query_selector(root, "table"), Ok(None));
+        assert_eq!(document.query_selector(root, "p!b"), Err(DomError::Syntax));
+        assert_eq!(document.query_selector(root, ""), Err(DomError::Syntax));
+    }
+
+    #[test]
+    fn mutation_observers() {
+        let mut document = Document::from_node(&HtmlParser::new("<ul><li>a</li></ul><p class=x>").parse_document());
+        let (ul, p, li) = (find(&document, "ul"), find(&document, "p"), find(&document, "li"));
+        let text = document.first_child(li).unwrap();
+
+        let seen: Rc<RefCell<Vec<Vec<MutationRecord>>>> = Rc::default();
+        let batches = Rc::clone(&seen);
+        let everything = ObserveOptions { child_list: true, attributes: true, character_data: true, subtree: true, attribute_old_value: true, ..ObserveOptions::default() };
+        document.observe(document.root(), everything, move |records| batches.borrow_mut().push(records.to_vec()));
+        let only_ul = document.observe(ul, ObserveOptions { child_list: true, ..ObserveOptions::default() }, |_| {});
+
+        let item = document.create_element("li");
+        document.append_child(ul, item).unwrap();
+        document.set_attribute(p, "class", "y");
+        document.set_text_content(text, "b");
+        document.set_attribute(li, "id", "x"); //the ul observer doesn't look below the ul
+        assert!(seen.borrow().is_empty()); //nothing is delivered until notify_observers
+
+        let ul_records = document.take_records(only_ul);
+        assert_eq!(ul_records.len(), 1);
+        assert_eq!((ul_records[0].target, ul_records[0].added_nodes.clone(), ul_records[0].previous_sibling), (ul, vec![item], Some(li)));
+
+        document.notify_observers();
+        let batch = seen.borrow()[0].clone();
+        let kinds: Vec<MutationKind> = batch.iter().map(|record| record.kind).collect();
+        assert_eq!(kinds, vec![MutationKind::ChildList, MutationKind::Attributes, MutationKind::CharacterData, MutationKind::Attributes]);
+        assert_eq!((batch[1].attribute_name.as_deref(), batch[1].old_value.as_deref()), (Some("class"), Some("x")));
+        assert_eq!(batch[2].old_value, None); //old text values weren't asked for
+        assert_eq!(batch[3].old_value, None); //there was no id before
+        assert_eq!(changed_nodes(&batch), vec![ul, p, text, li]);
+
+        //one batch per notify, nothing is left over for the next one
+        document.notify_observers();
+        document.remove_child(ul, item).unwrap();
+        document.notify_observers();
+        assert_eq!(seen.borrow().len(), 2);
+        assert_eq!(seen.borrow()[1][0].removed_nodes, vec![item]);
+    }
+
+    #[test]
+    fn typed_attributes_and_class_list() {
+        let html = "<table><tr><td z=1 colspan=' 3px' a=2 tabindex=px class='b  a b' z=dup hidden=false>";
+        let mut document = Document::from_node(&HtmlParser::new(html).parse_document());
+        let td = find(&document, "td");
+        let seen: Rc<RefCell<Vec<MutationRecord>>> = Rc::default();
+        let records = Rc::clone(&seen);
+        let options = ObserveOptions { attributes: true, attribute_old_value: true, ..ObserveOptions::default() };
+        document.observe(td, options, move |batch| records.borrow_mut().extend_from_slice(batch));
+
+        let element = document.element(td).unwrap();
+        let order: Vec<&str> = element.attributes().iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(order, vec!["z", "colspan", "a", "tabindex", "class", "hidden"]); //as written, the first z wins
+        assert_eq!(element.get_attribute("z").map(String::as_str), Some("1"));
+        assert_eq!(element.get_integer_attribute("colspan"), Some(3));
+        assert_eq!(element.get_integer_attribute("tabindex"), None);
+        assert!(element.has_attribute("hidden")); //there at all means on
+        assert_eq!(element.class_list(), vec!["b", "a"]);
+
+        document.add_class(td, "c");
+        assert!(!document.toggle_class(td, "b"));
+        document.remove_class(td, "nope");
+        document.element_mut(td).unwrap().set_boolean_attribute("hidden", false);
+        document.set_attribute(td, "z", "2"); //keeps its place
+        let element = document.element(td).unwrap();
+        assert_eq!(element.get_attribute("class").map(String::as_str), Some("a c"));
+        assert!(element.has_class("c") && !element.has_class("b"));
+        assert_eq!(outer_html(&document.to_node(td)), r#"<td z="2" colspan=" 3px" a="2" tabindex="px" class="a c"></td>"#);
+
+        document.notify_observers();
+        let old_values: Vec<Option<String>> = seen.borrow().iter().map(|record| record.old_value.clone()).collect();
+        assert_eq!(old_values.len(), 4);
+        assert_eq!(old_values[0].as_deref(), Some("b  a b"));
+        assert_eq!(old_values[1].as_deref(), Some("b a c"));
+    }
+
+    #[test]
+    fn text_content_and_inner_text() {
+        let html = "<div>\n  <p>one   <b>two</b>\n</p><p>three<br>four</p><pre> a\n  b</pre><script>x()</script><table><tr><td>1<td>2</table></div>";
+        let mut document = Document::from_node(&HtmlParser::new(html).parse_document());
+        let (div, p, b) = (find(&document, "div"), find(&document, "p"), find(&document, "b"));
+
+        //text comes out of the parser as it was written
+        assert_eq!(document.text_content(p).as_deref(), Some("one   two\n"));
+        assert_eq!(document.text_content(document.root()), None);
+        assert_eq!(document.inner_text(p), "one two");
+        assert_eq!(document.inner_text(div), "one two\n\nthree\nfour\n\n a\n  b\n1\t2");
+
+        //normalize glues neighbouring text nodes together and drops the empty ones
+        let (extra, empty) = (document.create_text_node(" and more"), document.create_text_node(""));
+        document.append_child(b, empty).unwrap();
+        document.append_child(b, extra).unwrap();
+        assert_eq!(document.children(b).count(), 3);
+        document.normalize(div);
+        assert_eq!(names(&document, document.children(b)), vec!["\"two and more\""]);
+
+        assert_eq!(collapse_whitespace(" a \t\n b ", WhiteSpace::Normal), " a b ");
+        assert_eq!(collapse_whitespace(" a  \n  b", WhiteSpace::PreLine), " a\nb");
+        assert_eq!(collapse_whitespace(" a  \n b", WhiteSpace::Pre), " a  \n b");
+        assert_eq!(WhiteSpace::from_keyword("pre-wrap"), Some(WhiteSpace::PreWrap));
+        assert_eq!(WhiteSpace::from_keyword("wrap"), None);
+    }
+
+    #[test]
+    fn dom_diff_report() {
+        let old = HtmlParser::new("<ul><li id=top>a</li><li class=a>b</li><li>c</li></ul>").parse_document();
+        let new = HtmlParser::new("<ul><li>c</li><li class=b>b!</li><li id=top>a</li><li>d</li></ul>").parse_document();
+        assert!(diff(&old, &old).is_empty());
+        let edits = diff(&old, &new);
+        assert_eq!(
+            report(&edits),
+            "> /0/1/0/2 -> /0/1/0/0 <li,>\n\
+             + /0/1/0/1 <li, class=\"b\">\n\
+             @ /0/1/0/3 class removed (was \"a\")\n\
+             ~ /0/1/0/3/0 \"b\" -> \"d\"\n"
+        );
+        assert!(matches!(edits[1], Edit::Insert { ref node, .. } if outer_html(node) == r#"<li class="b">b!</li>"#));
+
+        //indentation and comments only count when asked to
+        let indented = HtmlParser::new("<ul>\n  <li id=top>a</li><!-- b --><li class=a>b</li><li>c</li></ul>").parse_document();
+        assert_eq!(diff(&old, &indented).len(), 2);
+        let options = DiffOptions { ignore_whitespace_text: true, ignore_comments: true };
+        assert!(diff_with(&old, &indented, options).is_empty());
+    }
+
+    #[test]
+    fn dom_diff_doctype_and_quirks_mode() {
+        let html5 = HtmlParser::new("<!DOCTYPE html><p>x").parse_document();
+        let html4 = HtmlParser::new("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\"><p>x").parse_document();
+        let none = HtmlParser::new("<p>x").parse_document();
+
+        let limited = HtmlParser::new("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"x.dtd\"><p>x").parse_document();
+
+        //a different doctype that still gives the same mode
+        let strict = HtmlParser::new("<!DOCTYPE html SYSTEM \"about:legacy-compat\"><p>x").parse_document();
+        assert_eq!(report(&diff(&html5, &strict)), "! /0 <!DOCTYPE html> -> <!DOCTYPE html SYSTEM \"about:legacy-compat\">\n");
+
+        assert_eq!(
+            report(&diff(&html5, &html4)),
+            "! / quirks mode NoQuirks -> Quirks\n\
+             ! /0 <!DOCTYPE html> -> <!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\">\n"
+        );
+        assert!(matches!(
+            diff(&html4, &limited)[..],
+            [Edit::SetQuirksMode { old_mode: QuirksMode::Quirks, mode: QuirksMode::LimitedQuirks, .. }, Edit::SetDoctype { .. }]
+        ));
+        //no doctype at all, it's gone from the tree
+        let edits = diff(&html5, &none);
+        assert_eq!(edits.len(), 2);
+        assert!(matches!(edits[1], Edit::Remove { ref path, .. } if *path == vec![0]));
+    }
+
+    // NOTE 5- Serializer
+
+    #[test]
+    fn serialize_escapes() {
+        assert_eq!(body_html("<p class='b c' hidden id=a>x &amp; y &lt; z</p>"), r#"<p class="b c" hidden="" id="a">x &amp; y &lt; z</p>"#);
+        assert_eq!(body_html("<p a='<&>\"'>x<br/>y&nbsp;</p>"), "<p a=\"&lt;&amp;&gt;&quot;\">x<br>y&nbsp;</p>");
+        assert_eq!(body_html("<div><!-- hi -->a</div>"), "<div><!-- hi -->a</div>");
+        //raw text goes out the way it came in
+        assert_eq!(body_html("<div><style>p > a {}</style></div><xmp>1 < 2</xmp>"), "<div><style>p > a {}</style></div><xmp>1 < 2</xmp>");
+        let document = HtmlParser::new("<!DOCTYPE html><p>x").parse_document();
+        assert_eq!(outer_html(&document), "<!DOCTYPE html><html><head></head><body><p>x</p></body></html>");
+    }
+
+    #[test]
+    fn serialize_round_trips() {
+        for html in [
+            "<!DOCTYPE html><html><head><title>a &lt; b</title></head><body><p title=\"&quot;x&quot; &amp;\">1&nbsp;2</p></body></html>",
+            "<ul><li>a<li>b</ul><br><img src=x.png><input disabled>",
+            "<pre>\n\nkept</pre><textarea>\nx</textarea><listing>\ny</listing><script>a < b && c</script><style>p > a {}</style>",
+            "<table>x<tr><td>1</table><!-- note --><b>1<i>2</b>3</i>",
+            "<svg viewBox='0 0 1 1'><foreignObject><p>x</p></foreignObject><path d=M0/></svg><math><mi>x</mi></math>",
+        ]
+        .iter()
+        {
+            let first = HtmlParser::new(html).parse_document();
+            let again = HtmlParser::new(&outer_html(&first)).parse_document();
+            assert_eq!(outer_html(&again), outer_html(&first));
+            assert_eq!(outline(&[again]), outline(&[first]));
+        }
+    }
+
+    // NOTE 6- CSS
+
+    #[test]
+    fn css_errors_and_spans() {
+        let mut parser = CssParser::new("p { color: red; }\ndiv { margin: 1px");
+        let stylesheet = parser.parse_stylesheet();
+        let errors: Vec<(CssErrorKind, Position)> = parser.errors().iter().map(|e| (e.kind.clone(), e.span.start)).collect();
+        //the declaration at the end is complete, only the rule's block wasn't closed (from its '{' to the end)
+        assert_eq!(errors, vec![(CssErrorKind::UnexpectedEof, Position::new(2, 5))]);
+        assert_eq!(parser.errors()[0].span.end, Position::new(2, 18));
+        assert_eq!(stylesheet.style_rules(&Device::default())[0].declarations[0].span, Span::new(Position::new(1, 5), Position::new(1, 15)));
+    }
+
+    #[test]
+    fn css_tokens() {
+        let mut tokenizer = CssTokenizer::new("a#x>.b{width:10PX;content:'it\\'s'/* note */}@media url(a.png) 50% -.5e1 <!--");
+        let tokens: Vec<CssToken> = tokenizer.tokenize().into_iter().map(|(token, _)| token).collect();
+        let number = |value, integer, signed| crate::css_tokenizer::Number { value, integer, signed };
+        assert_eq!(tokens[..4].to_vec(), vec![
+            CssToken::Ident(String::from("a")),
+            CssToken::Hash { value: String::from("x"), id: true },
+            CssToken::Delim('>'),
+            CssToken::Delim('.'),
+        ]);
+        assert!(tokens.contains(&CssToken::Dimension(number(10.0, true, false), String::from("PX"))));
+        assert!(tokens.contains(&CssToken::String(String::from("it's"))));
+        assert!(tokens.contains(&CssToken::AtKeyword(String::from("media"))));
+        assert!(tokens.contains(&CssToken::Url(String::from("a.png"))));
+        assert!(tokens.contains(&CssToken::Percentage(number(50.0, true, false))));
+        assert!(tokens.contains(&CssToken::Number(number(-5.0, false, true))));
+        assert_eq!(tokens[tokens.len() - 2..].to_vec(), vec![CssToken::Cdo, CssToken::Eof]);
+        assert!(tokenizer.take_errors().is_empty());
+
+        let mut tokenizer = CssTokenizer::new("'a\nb /* open");
+        tokenizer.tokenize();
+        let errors: Vec<CssTokenizerError> = tokenizer.take_errors().into_iter().map(|(error, _)| error).collect();
+        assert_eq!(errors, vec![CssTokenizerError::NewlineInString, CssTokenizerError::EofInComment]);
+    }
+
+    #[test]
+    fn css_values_keep_their_case() {
+        let stylesheet = CssParser::new("P { font-family: Helvetica, 'Open Sans'; color: RED; width: 2EM; display: Block }").parse_stylesheet();
+        let values: Vec<String> = stylesheet.style_rules(&Device::default())[0].declarations.iter().map(|d| format!("{}: {:?}", d.property, d.value)).collect();
+        assert_eq!(values, vec![
+            "font-family: \"Helvetica, \\\"Open Sans\\\"\"",
+            "color: r: 1, g: 0, b: 0, a: 1",
+            "width: 2.0",
+            "display: \"Block\"",
+        ]);
+
+        //SVG tag names care about case, HTML ones don't
+        let html = "<DIV><svg><linearGradient/><foreignObject><Span>x</Span></foreignObject></svg></DIV>";
+        let document = Document::from_node(&HtmlParser::new(html).parse_document());
+        let count = |selector| document.query_selector_all(document.root(), selector).unwrap().len();
+        assert_eq!(count("linearGradient"), 1);
+        assert_eq!(count("lineargradient"), 0);
+        assert_eq!(count("foreignobject"), 0);
+        assert_eq!(count("DIV"), 1);
+        assert_eq!(count("span"), 1);
+    }
+
+    #[test]
+    fn css_error_recovery() {
+        let css = "p$ { color: red }\na { : x; width 3px; color: blue ! IMPORTANT; height: 1px }\n@nope x { b {} }\ni { x: 'a\n}\nb { c: f(d [";
+        let mut parser = CssParser::new(css);
+        let stylesheet = parser.parse_stylesheet();
+        let kinds: Vec<CssErrorKind> = parser.errors().iter().map(|e| e.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            CssErrorKind::InvalidSelector(String::from("$")),
+            CssErrorKind::InvalidDeclaration(String::from(": x")),
+            CssErrorKind::InvalidDeclaration(String::from("width 3px")),
+            CssErrorKind::UnsupportedAtRule(String::from("nope")),
+            CssErrorKind::InvalidDeclaration(String::from("x: \"")),
+            CssErrorKind::BadToken(CssTokenizerError::NewlineInString),
+            CssErrorKind::UnexpectedEof, //once, however many blocks are open
+        ]);
+        let starts: Vec<Position> = parser.errors().iter().map(|e| e.span.start).collect();
+        assert_eq!(starts[..4].to_vec(), vec![Position::new(1, 1), Position::new(2, 5), Position::new(2, 10), Position::new(3, 1)]);
+
+        //the rule with the bad selector is gone, the good declarations around the bad ones stay
+        let rule = stylesheet.style_rules(&Device::default())[0];
+        let declarations: Vec<(&str, bool)> = rule.declarations.iter().map(|d| (d.property.as_str(), d.important)).collect();
+        assert_eq!(declarations, vec![("color", true), ("height", false)]);
+    }
+
+    #[test]
+    fn css_at_rules() {
+        let css = "@import url(print.css) print;\n\
+                   p { color: red }\n\
+                   @media screen and (max-width: 600px) { p { width: 1px } @media print { a {} } }\n\
+                   @media not screen { b {} }\n\
+                   @supports (display: block) and (not (frob: 1)) { i {} }\n\
+                   @supports selector(a > b) or (frob: 1) { u {} }\n\
+                   @font-face { font-family: Mine; src: url(mine.woff) }\n\
+                   @keyframes Spin { from { width: 0 } 50%, to { width: 10px } }";
+        let mut parser = CssParser::new(css);
+        let stylesheet = parser.parse_stylesheet();
+        assert!(parser.errors().is_empty());
+        assert_eq!(stylesheet.rules.len(), 8);
+
+        let tags = |device: &Device| -> Vec<String> {
+            stylesheet.style_rules(device).iter().map(|rule| format!("{:?}", rule.selectors[0])).collect()
+        };
+        let phone = Device { width: 400.0, ..Device::default() };
+        let printer = Device { media_type: String::from("print"), ..Device::default() };
+        assert_eq!(tags(&Device::default()).len(), 2); //p and i
+        assert_eq!(tags(&phone).len(), 3);             //and the p inside @media
+        assert_eq!(tags(&printer).len(), 3);           //p, b and i, the nested print block is inside a screen one
+
+        match stylesheet.rules[0] {
+            CssRule::Import(ref import) => {
+                assert_eq!(import.url, "print.css");
+                assert_eq!(import.media[0].to_string(), "print");
+            }
+            ref other => panic!("not an @import: {:?}", other),
+        }
+        match stylesheet.rules[6] {
+            CssRule::FontFace(ref font) => assert_eq!(font.descriptors.len(), 2),
+            ref other => panic!("not @font-face: {:?}", other),
+        }
+        match stylesheet.rules[7] {
+            CssRule::Keyframes(ref keyframes) => {
+                assert_eq!(keyframes.name, "Spin"); //names keep their case
+                let offsets: Vec<Vec<f32>> = keyframes.keyframes.iter().map(|k| k.offsets.clone()).collect();
+                assert_eq!(offsets, vec![vec![0.0], vec![50.0, 100.0]]);
+            }
+            ref other => panic!("not @keyframes: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn supports_checks_values() {
+        let supports = |condition: &str| -> bool {
+            let mut parser = CssParser::new(&format!("@supports {} {{ p {{}} }}", condition));
+            !parser.parse_stylesheet().style_rules(&Device::default()).is_empty()
+        };
+        assert!(supports("(display: BLOCK)"));
+        assert!(!supports("(display: grid)"));
+        assert!(supports("(white-space: pre-wrap)"));
+        assert!(!supports("(white-space: break-spaces)"));
+        assert!(supports("(color: RED)"));
+        assert!(supports("(color: #0f0)"));
+        assert!(!supports("(color: banana)"));
+        assert!(!supports("(color: #12345)"));
+        assert!(!supports("(color: rgb(0, 0, 0))"));
+        assert!(supports("(width: 1.5em)"));
+        assert!(supports("(width: 50%)"));
+        assert!(!supports("(width: auto)"));
+        assert!(!supports("(width: 10furlongs)"));
+        assert!(supports("(--anything: banana)"));
+        assert!(!supports("(float: left)"));
+        assert!(supports("not (display: grid)"));
+    }
+
+    #[test]
+    fn combinators() {
+        let html = "<div id=a><ul><li id=one><b>x</b></li><li id=two></li><!-- c --><li id=three></li></ul><p id=four></p></div><svg><foreignObject><Span>y</Span></foreignObject></svg>";
+        let document = Document::from_node(&HtmlParser::new(html).parse_document());
+        let ids = |selector| -> Vec<String> {
+            let found = document.query_selector_all(document.root(), selector).unwrap();
+            found.iter().map(|&id| document.element(id).unwrap().get_id().cloned().unwrap_or_default()).collect()
+        };
+        assert_eq!(ids("#a li"), vec!["one", "two", "three"]);
+        assert_eq!(ids("div > li"), Vec::<String>::new());
+        assert_eq!(ids("ul>li>b").len(), 1);
+        assert_eq!(ids("#one + li"), vec!["two"]);
+        assert_eq!(ids("#two + li"), vec!["three"]); //the comment in between doesn't count
+        assert_eq!(ids("#one ~ li"), vec!["two", "three"]);
+        assert_eq!(ids("ul ~ p, #three + *"), vec!["four"]);
+        assert_eq!(ids("div li + li b"), Vec::<String>::new());
+        assert_eq!(ids("svg foreignObject SPAN").len(), 1);
+        assert_eq!(ids("svg foreignobject span").len(), 0);
+
+        //a combinator needs something on both sides
+        for selector in ["li >", "> li", "a + + b", "a, ~ b"].iter() {
+            assert_eq!(document.query_selector_all(document.root(), selector), Err(DomError::Syntax), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn attribute_selectors() {
+        let html = "<a id=1 href='https://x.org/a.pdf' lang=en-US class='btn big' title=Hi></a>\
+                    <a id=2 href='/b.html' lang=en title=hi></a><input id=3 type=TEXT DATA-X=y>\
+                    <svg><path id=4 viewBox='0 0 1 1' type=TEXT/></svg>";
+        let document = Document::from_node(&HtmlParser::new(html).parse_document());
+        let ids = |selector| -> Vec<String> {
+            let found = document.query_selector_all(document.root(), selector).unwrap();
+            found.iter().map(|&id| document.element(id).unwrap().get_id().cloned().unwrap_or_default()).collect()
+        };
+        assert_eq!(ids("[lang]"), vec!["1", "2"]);
+        assert_eq!(ids("a[title=hi]"), vec!["2"]);
+        assert_eq!(ids("[class~=big]"), vec!["1"]);
+        assert_eq!(ids("[class~='btn big']"), Vec::<String>::new());
+        assert_eq!(ids("[lang|=en]"), vec!["1", "2"]);
+        assert_eq!(ids("[href^=https]"), vec!["1"]);
+        assert_eq!(ids("[href$='.html']"), vec!["2"]);
+        assert_eq!(ids("[href*='x.org']"), vec!["1"]);
+        assert_eq!(ids("[href^='']"), Vec::<String>::new());
+
+        //the flags, and HTML's own list of values that don't care about case
+        assert_eq!(ids("[title=hi i]"), vec!["1", "2"]);
+        assert_eq!(ids("[type=text]"), vec!["3"]); //not the SVG one
+        assert_eq!(ids("[type=text s]"), Vec::<String>::new());
+        assert_eq!(ids("[data-x=y]"), vec!["3"]);
+        assert_eq!(ids("[viewBox]"), vec!["4"]);
+        assert_eq!(ids("[viewbox]"), Vec::<String>::new());
+
+        for selector in ["[]", "[=a]", "[a=]", "[a=b c]", "[a~b]"].iter() {
+            assert_eq!(document.query_selector_all(document.root(), selector), Err(DomError::Syntax), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn an_plus_b_parsing() {
+        let parsed = |text| nth(text).map(|nth| (nth.a, nth.b));
+        assert_eq!(parsed("odd"), Some((2, 1)));
+        assert_eq!(parsed("EVEN"), Some((2, 0)));
+        assert_eq!(parsed("3"), Some((0, 3)));
+        assert_eq!(parsed("-n+3"), Some((-1, 3)));
+        assert_eq!(parsed("+n"), Some((1, 0)));
+        assert_eq!(parsed(" 2n - 1 "), Some((2, -1)));
+        assert_eq!(parsed("2n-1"), Some((2, -1)));
+        assert_eq!(parsed("-n- 2"), Some((-1, -2)));
+        //b needs its sign, the sign can't be doubled and numbers are whole
+        assert_eq!(parsed("2n 1"), None);
+        assert_eq!(parsed("2n + -1"), None);
+        assert_eq!(parsed("2.5n"), None);
+        assert_eq!(parsed("n + 1.5"), None);
+        assert_eq!(parsed("foo"), None);
+        //too big for an i32, clamped instead of overflowing
+        assert_eq!(parsed("n - 9999999999"), Some((1, i32::MIN)));
+        assert_eq!(parsed("99999999999n"), Some((i32::MAX, 0)));
+    }
+
+    #[test]
+    fn an_plus_b_matching() {
+        let positions = |text, count| (1..=count).filter(|&p| nth(text).unwrap().matches(p)).collect::<Vec<i32>>();
+        assert_eq!(positions("odd", 6), vec![1, 3, 5]);
+        assert_eq!(positions("3n", 9), vec![3, 6, 9]);
+        assert_eq!(positions("-n+3", 6), vec![1, 2, 3]);
+        assert_eq!(positions("4", 6), vec![4]);
+        assert_eq!(positions("0n+0", 6), Vec::<i32>::new());
+        assert_eq!(positions("n - 2147483648", 3), vec![1, 2, 3]);
+        assert_eq!(positions("-n - 2147483648", 3), Vec::<i32>::new());
+        assert!(Nth { a: -2, b: i32::MAX }.matches(i32::MAX));
+    }
+
+    #[test]
+    fn structural_and_logical_pseudo_classes() {
+        let html = "<ul><li>1</li><!-- c --><li class=x>2</li><li>3<p></p></li><li class=x></li><b>5</b></ul><p><!-- only a comment --></p>";
+        let document = Document::from_node(&HtmlParser::new(html).parse_document());
+        let count = |selector| document.query_selector_all(document.root(), selector).unwrap().len();
+        let texts = |selector| -> Vec<String> {
+            let found = document.query_selector_all(document.root(), selector).unwrap();
+            found.iter().map(|&id| document.text_content(id).unwrap()).collect()
+        };
+        assert_eq!(texts(":root").len(), 1);
+        assert_eq!(texts("li:first-child"), vec!["1"]);
+        assert_eq!(texts("ul > :last-child"), vec!["5"]);
+        assert_eq!(texts("li:last-of-type"), vec![""]);
+        assert_eq!(texts("ul > :only-of-type"), vec!["5"]);
+        assert_eq!(count("p:empty"), 2); //comments don't count
+        assert_eq!(texts("li:nth-child(2n + 1)"), vec!["1", "3"]);
+        assert_eq!(texts("li:nth-last-child(2)"), vec![""]);
+        assert_eq!(texts(":nth-child(2 of .x)"), vec![""]);
+        assert_eq!(texts("ul :nth-of-type(2)"), vec!["2"]);
+        assert_eq!(texts("li:not(.x, :first-child)"), vec!["3"]);
+        assert_eq!(texts(":is(b, .x):where(:last-child)"), vec!["5"]);
+        assert_eq!(texts("li:has(> p)"), vec!["3"]);
+        assert_eq!(texts("li:has(+ .x)"), vec!["1", "3"]);
+        assert_eq!(count("ul:has(b)"), 1);
+        assert!(document.query_selector_all(document.root(), "li:nth-child(2n 1)").is_err());
+        assert!(document.query_selector_all(document.root(), ":bogus").is_err());
+
+        //an element out of the tree isn't the root of anything
+        let mut document = document;
+        let (div, span) = (document.create_element("div"), document.create_element("span"));
+        document.append_child(div, span).unwrap();
+        assert_eq!(document.query_selector_all(div, ":root > span"), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn element_state_pseudo_classes() {
+        let html = "<input id=a type=checkbox checked><input id=b disabled><button id=c></button><option id=d selected><div id=e disabled></div>";
+        let mut document = Document::from_node(&HtmlParser::new(html).parse_document());
+        let ids = |document: &Document, selector| -> Vec<String> {
+            let found = document.query_selector_all(document.root(), selector).unwrap();
+            found.iter().map(|&id| document.element(id).unwrap().get_id().cloned().unwrap()).collect()
+        };
+        assert_eq!(ids(&document, ":checked"), vec!["a", "d"]);
+        assert_eq!(ids(&document, ":disabled"), vec!["b"]); //a div can't be disabled
+        assert_eq!(ids(&document, ":enabled"), vec!["a", "c", "d"]);
+        assert!(ids(&document, ":hover").is_empty());
+
+        //the embedder sets what the markup can't say
+        let (a, c) = (document.get_element_by_id("a").unwrap(), document.get_element_by_id("c").unwrap());
+        document.set_element_state(c, ElementState::HOVER | ElementState::FOCUS, true);
+        document.set_element_state(a, ElementState::CHECKED, false); //the checkbox was clicked
+        assert_eq!(ids(&document, ":hover:focus"), vec!["c"]);
+        assert_eq!(ids(&document, ":checked"), vec!["d"]);
+
+        //changing the attributes puts :checked and :disabled back in line with them
+        document.set_attribute(a, "checked", "");
+        document.set_attribute(c, "disabled", "");
+        assert_eq!(ids(&document, ":checked"), vec!["a", "d"]);
+        assert_eq!(ids(&document, ":disabled"), vec!["b", "c"]);
+        assert_eq!(ids(&document, ":hover"), vec!["c"]); //the rest of the state stays
+    }
+
+    // NOTE 7- Style
+
+    //The text boxes of the style tree in order, generated ones with the pseudo-element they belong to ("::marker 1. ")
+    fn text_boxes(html: &str, css: &str) -> Vec<String> {
+        fn walk(node: &StyledNode, result: &mut Vec<String>) {
+            if let Some(text) = node.text().filter(|_| !node.is_collapsible_whitespace()) {
+                match node.pseudo_element() {
+                    Some(pseudo) => result.push(format!("::{} {}", pseudo.name(), text)),
+                    None => result.push(text),
+                }
+            }
+            for child in &node.children {
+                walk(child, result);
+            }
+        }
+        let document = HtmlParser::new(html).parse_document();
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let mut result = Vec::new();
+        walk(&StyledNode::new(&document, &stylesheet), &mut result);
+        result
+    }
+
+    #[test]
+    fn pseudo_elements() {
+        let css = "p::before { content: '[' attr(title) } p::after { content: open-quote ']' close-quote } p::after { color: red }";
+        assert_eq!(text_boxes("<p title=t>x</p><p>y</p>", css), vec!["::before [t", "x", "::after \u{201C}]\u{201D}", "::before [", "y", "::after \u{201C}]\u{201D}"]);
+        assert_eq!(text_boxes("<p>x</p>", "p::before { content: none } p::after { color: red }"), vec!["x"]);
+
+        //markers count on past i32::MAX and list-style-type doesn't care about case
+        let markers = text_boxes("<ol start=2147483646><li>a<li>b<li>c</ol><ul><li>d</ul>", "ul { list-style-type: UPPER-ROMAN }");
+        assert_eq!(markers, vec!["::marker 2147483646. ", "a", "::marker 2147483647. ", "b", "::marker 2147483648. ", "c", "::marker I. ", "d"]);
+        assert_eq!(text_boxes("<ul><li>a<li>b</ul>", "li { list-style: square inside } li + li::marker { content: '-' }"), vec!["::marker \u{25AA} ", "a", "::marker -", "b"]);
+        assert_eq!(text_boxes("<ul><li>a</ul>", "li { list-style-type: none }"), vec!["a"]);
+        assert_eq!(text_boxes("<ul><li>a<li>b</ul>", "li + li::marker { content: NONE }"), vec!["::marker \u{2022} ", "a", "b"]);
+
+        //::first-letter reaches into inline elements and takes punctuation along, but only on block containers
+        let css = "p, div { display: block } p::first-letter, span::first-letter, div::first-line { color: red }";
+        assert_eq!(text_boxes("<p><b> \"hello</b> world</p><span>ab</span>", css), vec!["::first-letter \"h", "ello", " world", "ab"]);
+        assert_eq!(text_boxes("<div>one two\nthree</div>", &format!("{} div {{ white-space: pre-line }}", css)), vec!["::first-line one two", "\nthree"]);
+
+        //::before is the first text there is, so the first letter comes out of it
+        let css = "p { display: block } p::before { content: '[' } p::first-letter { color: red }";
+        assert_eq!(text_boxes("<p>x</p>", css), vec!["::first-letter [", "x"]);
+        let css = "p { display: block } p::before { content: 'ab' } p::first-letter { color: red }";
+        assert_eq!(text_boxes("<p>x</p>", css), vec!["::first-letter a", "::before b", "x"]);
+    }
+}
@@ -0,0 +1,191 @@
+/* Watching the Dom for changes, the same idea as the browser's MutationObserver
+ * Every change made through the Document's mutation methods becomes a MutationRecord, observers registered on a node
+ * (and optionally everything below it) collect the records they care about
+ * Records aren't handed out one by one, they pile up until Document::notify_observers runs and every observer gets
+ * its whole batch in one call, like the browser delivering them at the end of a task
+ * The records also say exactly which nodes changed, so style and layout can redo just those parts instead of the whole tree
+ */
+
+use crate::document::NodeId;
+
+// NOTE 1- Data Structures
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationKind {
+    ChildList,     //children were added or removed, target is the parent
+    Attributes,    //an attribute was set or removed, target is the element
+    CharacterData, //the text of a text or comment node changed
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MutationRecord {
+    pub kind: MutationKind,
+    pub target: NodeId,
+    pub added_nodes: Vec<NodeId>,
+    pub removed_nodes: Vec<NodeId>,
+    pub previous_sibling: Option<NodeId>, //where in the child list the change happened
+    pub next_sibling: Option<NodeId>,
+    pub attribute_name: Option<String>,
+    pub old_value: Option<String>, //only filled in when the observer asked for old values
+}
+
+//What an observer wants to hear about, like the options of observer.observe()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ObserveOptions {
+    pub child_list: bool,
+    pub attributes: bool,
+    pub character_data: bool,
+    pub subtree: bool, //also changes to nodes below the observed one, not just the node itself
+    pub attribute_old_value: bool,
+    pub character_data_old_value: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(usize);
+
+pub type MutationCallback = Box<dyn FnMut(&[MutationRecord])>;
+
+struct Observer {
+    target: NodeId,
+    options: ObserveOptions,
+    callback: MutationCallback,
+    records: Vec<MutationRecord>, //queued, not delivered yet
+}
+
+//Every observer of one Document, disconnected ones leave a hole so ids stay valid
+#[derive(Default)]
+pub(crate) struct Observers {
+    observers: Vec<Option<Observer>>,
+}
+
+// NOTE 2- Implement Methods For These DataStructures
+
+impl MutationRecord {
+    fn new(kind: MutationKind, target: NodeId) -> MutationRecord {
+        MutationRecord {
+            kind,
+            target,
+            added_nodes: Vec::new(),
+            removed_nodes: Vec::new(),
+            previous_sibling: None,
+            next_sibling: None,
+            attribute_name: None,
+            old_value: None,
+        }
+    }
+
+    pub(crate) fn child_list(
+        target: NodeId,
+        added_nodes: Vec<NodeId>,
+        removed_nodes: Vec<NodeId>,
+        previous_sibling: Option<NodeId>,
+        next_sibling: Option<NodeId>,
+    ) -> MutationRecord {
+        MutationRecord {
+            added_nodes,
+            removed_nodes,
+            previous_sibling,
+            next_sibling,
+            ..MutationRecord::new(MutationKind::ChildList, target)
+        }
+    }
+
+    pub(crate) fn attributes(target: NodeId, name: &str, old_value: Option<String>) -> MutationRecord {
+        MutationRecord {
+            attribute_name: Some(name.to_string()),
+            old_value,
+            ..MutationRecord::new(MutationKind::Attributes, target)
+        }
+    }
+
+    pub(crate) fn character_data(target: NodeId, old_value: String) -> MutationRecord {
+        MutationRecord { old_value: Some(old_value), ..MutationRecord::new(MutationKind::CharacterData, target) }
+    }
+}
+
+impl Observer {
+    //`chain` is the record's target followed by all of its ancestors
+    fn wants(&self, record: &MutationRecord, chain: &[NodeId]) -> bool {
+        let in_range = match chain.iter().position(|&node| node == self.target) {
+            Some(0) => true,
+            Some(_) => self.options.subtree,
+            None => false,
+        };
+        in_range
+            && match record.kind {
+                MutationKind::ChildList => self.options.child_list,
+                MutationKind::Attributes => self.options.attributes,
+                MutationKind::CharacterData => self.options.character_data,
+            }
+    }
+}
+
+impl Observers {
+    pub(crate) fn add(&mut self, target: NodeId, options: ObserveOptions, callback: MutationCallback) -> ObserverId {
+        self.observers.push(Some(Observer { target, options, callback, records: Vec::new() }));
+        ObserverId(self.observers.len() - 1)
+    }
+
+    //Stop observing, records that weren't delivered yet are dropped
+    pub(crate) fn remove(&mut self, id: ObserverId) {
+        if let Some(slot) = self.observers.get_mut(id.0) {
+            *slot = None;
+        }
+    }
+
+    //Nobody is listening, the Document can skip building records altogether
+    pub(crate) fn is_empty(&self) -> bool {
+        self.observers.iter().all(|observer| observer.is_none())
+    }
+
+    pub(crate) fn queue(&mut self, record: &MutationRecord, chain: &[NodeId]) {
+        for observer in self.observers.iter_mut().flatten() {
+            if !observer.wants(record, chain) {
+                continue;
+            }
+            let mut record = record.clone();
+            let keep_old_value = match record.kind {
+                MutationKind::ChildList => false,
+                MutationKind::Attributes => observer.options.attribute_old_value,
+                MutationKind::CharacterData => observer.options.character_data_old_value,
+            };
+            if !keep_old_value {
+                record.old_value = None;
+            }
+            observer.records.push(record);
+        }
+    }
+
+    //Like observer.takeRecords(), the records are handed over instead of delivered to the callback
+    pub(crate) fn take_records(&mut self, id: ObserverId) -> Vec<MutationRecord> {
+        match self.observers.get_mut(id.0) {
+            Some(Some(observer)) => std::mem::take(&mut observer.records),
+            _ => Vec::new(),
+        }
+    }
+
+    //Deliver every queued batch, in the order the observers were registered
+    pub(crate) fn notify(&mut self) {
+        for observer in self.observers.iter_mut().flatten() {
+            if observer.records.is_empty() {
+                continue;
+            }
+            let records = std::mem::take(&mut observer.records);
+            (observer.callback)(&records);
+        }
+    }
+}
+
+// NOTE 3- Helper Methods
+
+//The nodes a batch of records touched, each one once, in the order they first changed
+//Style and layout only have to be redone for these nodes (and what's below them)
+pub fn changed_nodes(records: &[MutationRecord]) -> Vec<NodeId> {
+    let mut nodes: Vec<NodeId> = Vec::new();
+    for record in records {
+        if !nodes.contains(&record.target) {
+            nodes.push(record.target);
+        }
+    }
+    nodes
+}
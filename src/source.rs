@@ -0,0 +1,88 @@
+/* Where things came from in the source text
+ * Both parsers hand out spans so the DOM nodes, CSS rules and parse errors can point back at the markup or stylesheet
+ * that produced them, lines and columns both start at 1 and count characters (not bytes)
+ */
+
+use std::fmt;
+
+// NOTE 1- Data Structures
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+//From start up to (not including) end, nodes the parser made up on its own (like an implied <body>) get an empty span
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+// NOTE 2- Implement Methods For These DataStructures
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+}
+impl Default for Position {   //start of the file
+    fn default() -> Self {
+        Position::new(1, 1)
+    }
+}
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+
+    //Smallest span covering both of them
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+// NOTE 3- Helper Methods
+
+//Keeps the index of every line start so a character index can be turned into a line and column
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(chars: &[char]) -> LineIndex {
+        let mut index = LineIndex { line_starts: vec![0] };
+        index.extend(chars, 0);
+        index
+    }
+
+    //Add more of the text as it arrives, `offset` is the index of its first character
+    pub fn extend(&mut self, chars: &[char], offset: usize) {
+        for (i, c) in chars.iter().enumerate() {
+            if *c == '\n' {
+                self.line_starts.push(offset + i + 1);
+            }
+        }
+    }
+
+    pub fn position(&self, index: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= index);
+        Position::new(line, index - self.line_starts[line - 1] + 1)
+    }
+
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.position(start), self.position(end))
+    }
+}
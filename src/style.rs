@@ -3,13 +3,16 @@
 //If u take a look at mozilla gecko engine, it takes a dom tree and then it produces whats called a frame tree which then used to build 
 //A view tree then chrome's webkit takes in a dom tree and outputs what is called a render tree
 //It has a few other trees which are called layer trees and widget trees
-//In our implementation each node in the Dom tree has exactly 1 node in the style tree 
+//In our implementation each node in the Dom tree has exactly 1 node in the style tree, plus the pseudo-elements CSS
+//makes up: ::before, ::after and ::marker boxes are extra children of their element, and ::first-letter / ::first-line
+//cut the element's first text into pieces that each get their own node
 
 use std::collections::HashMap;
 use std::{fmt, str};            //fmt trait and str trait
 
-use crate::dom::{ElementData, Node, NodeType};
-use crate::css::{Selector, Stylesheet, Value};
+use crate::document::{Document, ElementRef, NodeId};
+use crate::dom::{ElementState, Node, NodeType};
+use crate::css::{ContentItem, Device, PseudoClass, PseudoElement, RelativeSelector, Rule, Selector, SimpleSelector, Stylesheet, Value};
 
 type PropertyMap<'a> = HashMap<&'a str, &'a Value>;
 
@@ -20,7 +23,10 @@ type PropertyMap<'a> = HashMap<&'a str, &'a Value>;
 
 pub struct StyledNode<'a> {
     node: &'a Node,
+    pseudo: Option<PseudoElement>, //a box for a pseudo-element, node is then the element it belongs to (the text node for ::first-letter and ::first-line)
+    content: Option<String>,       //the text of a generated box, or the piece of node's text this one shows
     styles: PropertyMap<'a>,
+    white_space: WhiteSpace, //inherited like in CSS, so a text node knows how its parent wants whitespace handled
     pub children: Vec<StyledNode<'a>>, 
 }
 
@@ -36,36 +42,80 @@ pub enum Display {
     //None will have to styling at all
 }
 
+//The white-space property, the parser keeps text exactly as it was written and this decides how it's shown
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WhiteSpace {
+    Normal,  //runs of spaces, tabs and newlines become one space
+    Pre,     //everything is kept, like <pre>
+    Nowrap,  //collapsed like normal (lines just don't wrap)
+    PreWrap, //kept like pre (lines still wrap)
+    PreLine, //spaces and tabs collapse but newlines are kept
+}
+
 //NOTE: Functions
 impl <'a> StyledNode <'a> {
     pub fn new(node: &'a Node, stylesheet: &'a Stylesheet) -> StyledNode<'a> {
+        StyledNode::with_device(node, stylesheet, &Device::default())
+    }
+
+    //Same as new but @media rules are checked against this device instead of the default 1024x768 screen
+    pub fn with_device(node: &'a Node, stylesheet: &'a Stylesheet, device: &Device) -> StyledNode<'a> {
+        let rules = stylesheet.style_rules(device); //which @media and @supports blocks apply is the same for every node
+        //selectors look all around an element (ancestors, siblings, children for :has), which is easy in a Document,
+        //it's walked along with the tree, node for node
+        let document = Document::from_node(node);
+        StyledNode::build(node, &document, document.root(), &rules, WhiteSpace::Normal)
+    }
+
+    //`id` is the node's copy in `document`
+    fn build(node: &'a Node, document: &Document, id: NodeId, rules: &[&'a Rule], parent_white_space: WhiteSpace) -> StyledNode<'a> {
         //Will recursivelly create our style tree without any of the style rules 
         //& then apply the style rules afterward
-        let mut style_children = Vec::new();
+        let element = ElementRef::new(document, id);
+        let styles = match element {
+            Some(element) => StyledNode::get_styles(element, rules, None),
+            None => PropertyMap::new(),
+        };
+
+        //white-space from the stylesheet, else what the element has by default, else the parent's
+        let white_space = match styles.get("white-space") {
+            Some(&Value::Other(keyword)) => WhiteSpace::from_keyword(keyword),
+            _ => None,
+        };
+        let white_space = white_space.or_else(|| default_white_space(node)).unwrap_or(parent_white_space);
 
-        for child in &node.children {   //every child inside the passed node
+        let mut style_children = Vec::new();
+        for (child, child_id) in node.children.iter().zip(document.children(id)) {   //every child inside the passed node
             match child.node_type {
-                NodeType::Element(_) => style_children.push(StyledNode::new(&child, stylesheet))
+                NodeType::Element(_) | NodeType::Text(_) => style_children.push(StyledNode::build(child, document, child_id, rules, white_space)),
+                _ => {} //comments and doctypes aren't shown
             }
         }
+        if let Some(element) = element {
+            StyledNode::add_pseudo_elements(node, element, &styles, rules, white_space, &mut style_children);
+        }
 
         StyledNode {
             node,   //Passed Node
-            styles: match node.node_type {
-                NodeType::Element(ref e) => StyledNode::get_styles(e, stylesheet),
-                _ => PropertyMap::new(),
-            },
+            pseudo: None,
+            content: None,
+            styles,
+            white_space,
             children: style_children,
         }
     }
 
-    //Return syle of the current node that we're looking at
-    fn get_styles(element: &'a ElementData, stylesheet: &'a Stylesheet) -> PropertyMap<'a> {
+    //Return syle of the current node that we're looking at, or of one of its pseudo-elements
+    fn get_styles(element: ElementRef, rules: &[&'a Rule], pseudo: Option<PseudoElement>) -> PropertyMap<'a> {
         let mut styles = PropertyMap::new();
 
-        for rule in &stylesheet.rules { //for rule in stylesheet.rules
+        for rule in rules { //for rule in stylesheet.rules
             for selector in &rule.selectors { //for selector in rule.selector
-                if selector_matches(element, &selector) {
+                let matched = match pseudo {
+                    Some(pseudo) => pseudo_element_matches(element, selector, pseudo),
+                    None => selector_matches(element, selector),
+                };
+                if matched {
                     for declar in &rule.declarations {  //iterate through them and add them to our propertymap
                         styles.insert(&declar.property, &declar.value);
                     }
@@ -76,6 +126,59 @@ impl <'a> StyledNode <'a> {
         styles
     }
 
+    //ANCHOR Pseudo-elements
+    //Children are already styled, this adds the boxes the element's pseudo-elements make
+    fn add_pseudo_elements(node: &'a Node, element: ElementRef, styles: &PropertyMap<'a>, rules: &[&'a Rule], white_space: WhiteSpace, children: &mut Vec<StyledNode<'a>>) {
+        //::before and ::after only exist when they have something to show
+        let generated = |pseudo: PseudoElement| {
+            let styles = StyledNode::get_styles(element, rules, Some(pseudo));
+            generated_content(&styles, element).map(|content| StyledNode::generated(node, pseudo, content, styles, white_space))
+        };
+        if let Some(before) = generated(PseudoElement::Before) {
+            children.insert(0, before);
+        }
+        if let Some(after) = generated(PseudoElement::After) {
+            children.push(after);
+        }
+
+        //::first-letter and ::first-line cut up the text that's there now, ::before's text is the first there is
+        let first_line = StyledNode::get_styles(element, rules, Some(PseudoElement::FirstLine));
+        let first_letter = StyledNode::get_styles(element, rules, Some(PseudoElement::FirstLetter));
+        if (!first_line.is_empty() || !first_letter.is_empty()) && is_block_container(styles) {
+            let nonempty = |styles: PropertyMap<'a>| Some(styles).filter(|styles| !styles.is_empty());
+            cut_first_line(children, &mut FirstLineCut { line: nonempty(first_line), letter: nonempty(first_letter) });
+        }
+
+        //list items get a bullet (or number) in front of everything, ::marker can style it or replace its text
+        if is_list_item(element, styles) {
+            let marker_styles = StyledNode::get_styles(element, rules, Some(PseudoElement::Marker));
+            let content = match marker_styles.get("content") {
+                Some(&&Value::Content(_)) => generated_content(&marker_styles, element),
+                Some(Value::Other(keyword)) if keyword.eq_ignore_ascii_case("none") => None, //no marker at all
+                _ => marker_text(element, styles, rules),
+            };
+            if let Some(content) = content {
+                children.insert(0, StyledNode::generated(node, PseudoElement::Marker, content, marker_styles, white_space));
+            }
+        }
+    }
+
+    fn generated(node: &'a Node, pseudo: PseudoElement, content: String, styles: PropertyMap<'a>, white_space: WhiteSpace) -> StyledNode<'a> {
+        StyledNode {
+            node,
+            pseudo: Some(pseudo),
+            content: Some(content),
+            styles,
+            white_space,
+            children: Vec::new(),
+        }
+    }
+
+    //Which pseudo-element this node is the box of, None for the nodes that come from the Dom
+    pub fn pseudo_element(&self) -> Option<PseudoElement> {
+        self.pseudo
+    }
+
     //Reason we have && (Double refrence) is bec. the get function, if we were to use 1 reference we would have miss match
     //specific reason: bec. our PropertyMap which has both reference to value & reference to string, & bec. we want
     //to get that value out we need to say that its a reference to the reference to value
@@ -87,7 +190,7 @@ impl <'a> StyledNode <'a> {
     pub fn get_display(&self) -> Display {
         match self.value("display") {   //match on what we get from value function
             Some(s) => match **s { //if get some with value inside of it, then ** (double the reference) s
-                Value::Other(ref v) => match v.as_ref() { //check if value other, match on info inside other as reference
+                Value::Other(ref v) => match v.to_ascii_lowercase().as_str() { //check if value other, match on the keyword inside other
                     "block" => Display::Block,
                     "none" => Display::None,
                     "inline-block" => Display::InlineBlock,
@@ -99,6 +202,38 @@ impl <'a> StyledNode <'a> {
         }
     }
 
+    pub fn get_white_space(&self) -> WhiteSpace {
+        self.white_space
+    }
+
+    //The text of a text node (or a generated box) the way it's shown, with whitespace collapsed the way white-space says
+    //None for elements
+    pub fn text(&self) -> Option<String> {
+        match (&self.content, &self.node.node_type) {
+            (Some(ref content), _) => Some(collapse_whitespace(content, self.white_space)),
+            (None, NodeType::Text(ref t)) => Some(collapse_whitespace(t, self.white_space)),
+            _ => None,
+        }
+    }
+
+    //A text node with nothing but whitespace that collapses away, like the newlines and indentation between tags
+    //Layout doesn't make boxes for these
+    pub fn is_collapsible_whitespace(&self) -> bool {
+        let text = match (&self.content, &self.node.node_type) {
+            (Some(ref content), _) => Some(content),
+            (None, NodeType::Text(ref t)) => Some(t),
+            _ => None,
+        };
+        match text {
+            Some(t) => match self.white_space {
+                WhiteSpace::Normal | WhiteSpace::Nowrap => t.chars().all(|c| c.is_ascii_whitespace()),
+                WhiteSpace::PreLine => t.chars().all(|c| c.is_ascii_whitespace() && c != '\n'),
+                WhiteSpace::Pre | WhiteSpace::PreWrap => false,
+            },
+            _ => false,
+        }
+    }
+
     //Applies to properties that have numbers in them
     //return style property for the current node or a default value
     //our name is the property name of the return value
@@ -116,59 +251,485 @@ impl <'a> StyledNode <'a> {
 
 impl<'a> fmt::Debug for StyledNode<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}: {:?}", self.node, self.styles)
+        match (self.pseudo, &self.content) {
+            (Some(pseudo), Some(ref content)) => write!(f, "::{} {:?}: {:?}", pseudo.name(), content, self.styles),
+            (None, Some(ref content)) => write!(f, "{:?}: {:?}", content, self.styles),
+            _ => write!(f, "{:?}: {:?}", self.node, self.styles),
+        }
+    }
+}
+
+//ANCHOR Generated content
+//The text of a ::before, ::after or ::marker, None when content is none, normal or missing
+fn generated_content(styles: &PropertyMap, element: ElementRef) -> Option<String> {
+    match styles.get("content") {
+        Some(Value::Content(items)) => Some(
+            items
+                .iter()
+                .map(|item| match *item {
+                    ContentItem::String(ref s) => s.clone(),
+                    ContentItem::Attr(ref name) => element.data().get_attribute(name).cloned().unwrap_or_default(),
+                    ContentItem::OpenQuote => String::from("\u{201C}"),
+                    ContentItem::CloseQuote => String::from("\u{201D}"),
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+//display: list-item, which <li> is by default
+fn is_list_item(element: ElementRef, styles: &PropertyMap) -> bool {
+    match styles.get("display") {
+        Some(Value::Other(display)) => display.eq_ignore_ascii_case("list-item"),
+        _ => element.data().is_html() && element.data().tag_name == "li",
+    }
+}
+
+//The list item's bullet or number from list-style-type, it's inherited so the list's own rules count too
+//(<ol> numbers, <ul> bullets), None for list-style-type: none
+fn marker_text(element: ElementRef, styles: &PropertyMap, rules: &[&Rule]) -> Option<String> {
+    let list = element.parent_element();
+    let list_styles = list.map(|list| StyledNode::get_styles(list, rules, None)).unwrap_or_default();
+    let style_type = [styles, &list_styles]
+        .iter()
+        .filter_map(|styles| list_style_type(styles))
+        .next()
+        .unwrap_or(match list {
+            Some(list) if list.data().is_html() && list.data().tag_name == "ol" => "decimal",
+            _ => "disc",
+        });
+
+    //the item's number, <ol start> says where counting starts and <li value> sets it from that item on
+    let mut items: Vec<ElementRef> = siblings_before(element).into_iter().filter(|s| s.data().tag_name == "li").collect();
+    items.reverse();
+    items.push(element);
+    //counted in i64, start and value can be anywhere in i32 and counting on from them mustn't overflow
+    let start = list.and_then(|list| list.data().get_integer_attribute("start")).map_or(1, i64::from);
+    let number = items.iter().fold(start - 1, |n, item| item.data().get_integer_attribute("value").map_or(n + 1, i64::from));
+
+    let marker = match style_type {
+        "none" => return None,
+        "circle" => String::from("\u{25E6}"),
+        "square" => String::from("\u{25AA}"),
+        "decimal" => format!("{}.", number),
+        "decimal-leading-zero" => format!("{:02}.", number),
+        "lower-alpha" | "lower-latin" => format!("{}.", alphabetic(number).to_ascii_lowercase()),
+        "upper-alpha" | "upper-latin" => format!("{}.", alphabetic(number)),
+        "lower-roman" => format!("{}.", roman(number).to_ascii_lowercase()),
+        "upper-roman" => format!("{}.", roman(number)),
+        _ => String::from("\u{2022}"), //disc
+    };
+    Some(marker + " ")
+}
+
+//list-style-type, or the type out of the list-style shorthand ("list-style: square inside"), in lowercase
+fn list_style_type(styles: &PropertyMap) -> Option<&'static str> {
+    const TYPES: [&str; 12] = [
+        "none", "disc", "circle", "square", "decimal", "decimal-leading-zero",
+        "lower-alpha", "lower-latin", "upper-alpha", "upper-latin", "lower-roman", "upper-roman",
+    ];
+    let known = |word: &str| TYPES.iter().find(|t| t.eq_ignore_ascii_case(word)).cloned();
+    match (styles.get("list-style-type"), styles.get("list-style")) {
+        (Some(Value::Other(keyword)), _) if known(keyword).is_some() => known(keyword),
+        (_, Some(Value::Other(shorthand))) => shorthand.split(' ').find_map(known),
+        _ => None,
+    }
+}
+
+//1 A, 26 Z, 27 AA, numbers below 1 stay numbers
+fn alphabetic(number: i64) -> String {
+    if number < 1 {
+        return number.to_string();
+    }
+    let mut letters = Vec::new();
+    let mut n = number;
+    while n > 0 {
+        n -= 1;
+        letters.push((b'A' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+//Roman numerals go from 1 to 3999, anything else stays a number
+fn roman(number: i64) -> String {
+    if !(1..4000).contains(&number) {
+        return number.to_string();
+    }
+    const NUMERALS: [(i64, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut result = String::new();
+    let mut n = number;
+    for &(value, numeral) in NUMERALS.iter() {
+        while n >= value {
+            result.push_str(numeral);
+            n -= value;
+        }
+    }
+    result
+}
+
+//ANCHOR ::first-letter and ::first-line
+//What's left to cut out of a block's first line, the styles stay until that part was found
+struct FirstLineCut<'a> {
+    line: Option<PropertyMap<'a>>,   //::first-line, until the first line is over
+    letter: Option<PropertyMap<'a>>, //::first-letter, until the first letter was found
+}
+
+//Block, inline-block and list-item boxes hold lines of their own, ::first-line and ::first-letter only apply to those
+fn is_block_container(styles: &PropertyMap) -> bool {
+    match styles.get("display") {
+        Some(Value::Other(display)) => matches!(display.to_ascii_lowercase().as_str(), "block" | "inline-block" | "list-item"),
+        _ => false,
+    }
+}
+
+//Goes through the inline content at the start of a block, its text and the text inside inline elements like <b>,
+//and cuts each text into pieces: the first letter, what's on the first line and what comes after it
+//Layout doesn't break lines, so the first line ends at the first kept newline, a <br> or a block, whatever comes first
+//Gives back false once there's nothing left to cut
+fn cut_first_line<'a>(children: &mut Vec<StyledNode<'a>>, cut: &mut FirstLineCut<'a>) -> bool {
+    let mut i = 0;
+    while i < children.len() && (cut.line.is_some() || cut.letter.is_some()) {
+        let (node, display) = (children[i].node, children[i].get_display());
+        match (&children[i].content, &node.node_type) {
+            (Some(content), _) if content.is_empty() => {} //a ::before with nothing in it
+            (Some(_), _) | (None, NodeType::Text(_)) => { //text, or the text of a ::before or ::after
+                let fragments = cut_text(&children[i], cut);
+                let count = fragments.len();
+                children.splice(i..i + 1, fragments);
+                i += count;
+                continue;
+            }
+            (None, NodeType::Element(ref e)) => match display {
+                Display::None => {}
+                Display::Inline if e.is_html() && e.tag_name == "br" => return false, //a line without a letter on it
+                Display::Inline => {
+                    if !cut_first_line(&mut children[i].children, cut) {
+                        return false;
+                    }
+                }
+                Display::Block | Display::InlineBlock => return false,
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+    cut.line.is_some() || cut.letter.is_some()
+}
+
+//One text node (or generated box) in pieces: whitespace in front, the first letter, the rest of the first line and everything after it
+//The letter is inside the first line, so it gets both styles, first-letter winning
+//Pieces of a generated box keep the box's own styles underneath, what's after the first line stays a ::before or ::after
+fn cut_text<'a>(text_node: &StyledNode<'a>, cut: &mut FirstLineCut<'a>) -> Vec<StyledNode<'a>> {
+    let (node, white_space) = (text_node.node, text_node.white_space);
+    let text = match (&text_node.content, &node.node_type) {
+        (Some(ref content), _) => content.as_str(),
+        (None, NodeType::Text(ref t)) => t.as_str(),
+        _ => "",
+    };
+
+    let start = text.len() - text.trim_start().len();
+    let letter_end = match cut.letter {
+        Some(_) if start < text.len() => first_letter_end(text, start),
+        _ => start,
+    };
+    let line_end = match (&cut.line, white_space) {
+        (None, _) => letter_end,
+        (Some(_), WhiteSpace::Pre | WhiteSpace::PreWrap | WhiteSpace::PreLine) => {
+            text[letter_end..].find('\n').map_or(text.len(), |i| letter_end + i)
+        }
+        (Some(_), WhiteSpace::Normal | WhiteSpace::Nowrap) => text.len(),
+    };
+
+    let (pseudo, styles) = (text_node.pseudo, text_node.styles.clone());
+    let line = cut.line.clone();
+    let mut line_styles = styles.clone();
+    line_styles.extend(line.clone().unwrap_or_default());
+    let mut letter_styles = line_styles.clone();
+    if letter_end > start {
+        letter_styles.extend(cut.letter.take().unwrap_or_default());
+    }
+    if line_end < text.len() {
+        cut.line = None; //the newline ends it
+    }
+
+    let line_pseudo = line.map(|_| PseudoElement::FirstLine).or(pseudo);
+    let pieces = vec![
+        (line_pseudo, &text[..start], line_styles.clone()),
+        (Some(PseudoElement::FirstLetter), &text[start..letter_end], letter_styles),
+        (line_pseudo, &text[letter_end..line_end], line_styles),
+        (pseudo, &text[line_end..], styles),
+    ];
+    pieces
+        .into_iter()
+        .filter(|&(_, piece, _)| !piece.is_empty())
+        .map(|(pseudo, piece, styles)| StyledNode { node, pseudo, content: Some(piece.to_string()), styles, white_space, children: Vec::new() })
+        .collect()
+}
+
+//Where the first letter starting at `start` ends, punctuation right before and after it goes with it (“A” for "“A” said")
+fn first_letter_end(text: &str, start: usize) -> usize {
+    let is_punctuation = |c: char| c.is_ascii_punctuation() || "\u{AB}\u{BB}\u{2018}\u{2019}\u{201C}\u{201D}\u{201E}\u{2039}\u{203A}".contains(c);
+    let mut end = start;
+    let mut letter = false;
+    for (i, c) in text[start..].char_indices() {
+        if is_punctuation(c) {
+            end = start + i + c.len_utf8();
+        } else if !letter && !c.is_whitespace() {
+            letter = true;
+            end = start + i + c.len_utf8();
+        } else {
+            break;
+        }
     }
+    end
 }
 
 //NOTE: Helper functions
 //Make sure our selector matches a Dom node
-//Element: Element data for the Dom node that we want to match
+//Element: the Dom node that we want to match, with the Document around it
 //Selector: the selector we want to match to that Dom node
-fn selector_matches(element: &ElementData, selector: &Selector) -> bool {
-    for simple in selector.simple { // Vector of Simple Selector
-        let mut selector_match = true;
+//Matching goes right to left like in browsers: the last compound has to match the element itself, then each
+//combinator says where to look for the one before it (any ancestor, the parent, the previous sibling, any earlier sibling)
+//Also used by document::Document::query_selector so queries match exactly like the stylesheet does
+//A selector with a pseudo-element never matches the element itself, only its pseudo-element
+pub(crate) fn selector_matches(element: ElementRef, selector: &Selector) -> bool {
+    selector.pseudo_element.is_none() && element_matches(element, selector)
+}
 
-        //ANCHOR Checks selector part of our stylesheet
-        match simple.tag_name {
-            Some(ref t) => if *t != element.tag_name { //if some, check that tag name isn't equal to the element tag name
-                continue;
-            },
-            None => {}
+//"li:last-child::marker" is for the marker of an element that matches li:last-child
+fn pseudo_element_matches(element: ElementRef, selector: &Selector, pseudo: PseudoElement) -> bool {
+    selector.pseudo_element == Some(pseudo) && element_matches(element, selector)
+}
+
+fn element_matches(element: ElementRef, selector: &Selector) -> bool {
+    match selector.simple.len() {
+        0 => false,
+        n => matches_from(element, selector, n - 1, None),
+    }
+}
+
+//Does element match simple[i] and everything to the left of it
+//`anchor` is for :has(), the leftmost compound also has to be related to the anchor element by that combinator
+fn matches_from(element: ElementRef, selector: &Selector, i: usize, anchor: Option<(char, ElementRef)>) -> bool {
+    if !compound_matches(element, &selector.simple[i]) {
+        return false;
+    }
+    if i == 0 {
+        return match anchor {
+            Some((combinator, anchor)) => related(anchor, combinator, element),
+            None => true,
         };
-        
-        //ANCHOR Checks for id in our node in stylesheet
-        match element.get_id() { //make sure they are not equivalent
-            Some(i) => match simple.id {
-                Some(ref id) => if *i != *id {
-                    continue;
-                },
-                None => {}
-            },
-            None => match simple.id {
-                Some(_) => {
-                    continue;
-                },
-                _ => {}
-            },
+    }
+    let combinator = selector.combinators.get(i - 1).cloned().unwrap_or(' ');
+    candidates(element, combinator).into_iter().any(|other| matches_from(other, selector, i - 1, anchor))
+}
+
+//The elements the compound to the left of `combinator` could be
+fn candidates(element: ElementRef, combinator: char) -> Vec<ElementRef> {
+    let mut result = Vec::new();
+    let mut next = match combinator {
+        '+' | '~' => element.previous_element_sibling(),
+        _ => element.parent_element(),
+    };
+    while let Some(other) = next {
+        result.push(other);
+        next = match combinator {
+            '~' => other.previous_element_sibling(),
+            ' ' => other.parent_element(),
+            _ => None,
+        };
+    }
+    result
+}
+
+//Is `element` where `combinator` says when we start from `anchor` ("anchor > element", "anchor + element" ...)
+fn related(anchor: ElementRef, combinator: char, element: ElementRef) -> bool {
+    candidates(element, combinator).iter().any(|other| other.id == anchor.id)
+}
+
+//One compound selector against the element's own tag, id, classes, attributes and pseudo-classes
+fn compound_matches(element: ElementRef, simple: &SimpleSelector) -> bool {
+    let data = element.data();
+
+    //ANCHOR Checks selector part of our stylesheet
+    //HTML tag names don't care about case, SVG and MathML ones do (linearGradient, foreignObject)
+    if let Some(ref t) = simple.tag_name {
+        let same_tag = if data.is_html() { t.eq_ignore_ascii_case(&data.tag_name) } else { *t == data.tag_name };
+        if !same_tag { //check that tag name isn't equal to the element tag name
+            return false;
+        }
+    }
+    
+    //ANCHOR Checks for id in our node in stylesheet
+    if let Some(ref id) = simple.id {
+        if data.get_id() != Some(id) { //make sure they are equivalent
+            return false;
         }
+    }
 
-        //ANCHOR See if that class in in our node in stylesheet 
-        //Get all classes for our styled element
-        let element_classes = element.get_classes(); //return HashSet with Ref. to slice of string
+    //ANCHOR See if that class in in our node in stylesheet 
+    //Get all classes for our styled element
+    let element_classes = data.get_classes(); //return HashSet with Ref. to slice of string
 
-        for class in &simple.classes {
-            selector_match &= element_classes.contains::<str>(class);
-            //try to see if element classes contains the class we iterating through
-            //if it does change our boolen selector match
+    //try to see if element classes contains every class of the selector
+    if !simple.classes.iter().all(|class| element_classes.contains::<str>(class)) {
+        return false;
+    }
+
+    //ANCHOR Attribute selectors
+    //attribute names don't care about case on HTML elements, the parser already lowercased those
+    let attributes_match = simple.attributes.iter().all(|selector| {
+        let html = data.is_html();
+        data
+            .attributes()
+            .iter()
+            .find(|&(name, _)| if html { name.eq_ignore_ascii_case(&selector.name) } else { *name == selector.name })
+            .is_some_and(|(_, value)| selector.value_matches(value, html))
+    });
+
+    attributes_match && simple.pseudo_classes.iter().all(|pseudo_class| pseudo_class_matches(element, pseudo_class))
+}
+
+//ANCHOR Pseudo-classes
+fn pseudo_class_matches(element: ElementRef, pseudo_class: &PseudoClass) -> bool {
+    let data = element.data();
+    let same_type = |other: &ElementRef| other.data().tag_name == data.tag_name && other.data().namespace == data.namespace;
+    let any = |list: &[Selector]| list.iter().any(|selector| selector_matches(element, selector));
+
+    match *pseudo_class {
+        PseudoClass::Root => element.is_root(),
+        PseudoClass::Empty => element.is_empty(),
+        PseudoClass::FirstChild => element.previous_element_sibling().is_none(),
+        PseudoClass::LastChild => element.next_element_sibling().is_none(),
+        PseudoClass::OnlyChild => element.previous_element_sibling().is_none() && element.next_element_sibling().is_none(),
+        PseudoClass::FirstOfType => !siblings_before(element).iter().any(same_type),
+        PseudoClass::LastOfType => !siblings_after(element).iter().any(same_type),
+        PseudoClass::OnlyOfType => !siblings_before(element).iter().chain(siblings_after(element).iter()).any(same_type),
+        PseudoClass::NthChild(nth, ref of) | PseudoClass::NthLastChild(nth, ref of) => {
+            //with "of S" the element has to match S and only siblings matching S are counted
+            if !of.is_empty() && !any(of) {
+                return false;
+            }
+            let counted = |other: &&ElementRef| of.is_empty() || of.iter().any(|selector| selector_matches(**other, selector));
+            let siblings = match *pseudo_class {
+                PseudoClass::NthChild(..) => siblings_before(element),
+                _ => siblings_after(element),
+            };
+            nth.matches(siblings.iter().filter(counted).count() as i32 + 1)
         }
+        PseudoClass::NthOfType(nth) => nth.matches(siblings_before(element).iter().filter(|s| same_type(s)).count() as i32 + 1),
+        PseudoClass::NthLastOfType(nth) => nth.matches(siblings_after(element).iter().filter(|s| same_type(s)).count() as i32 + 1),
+        PseudoClass::Not(ref list) => !any(list),
+        PseudoClass::Is(ref list) | PseudoClass::Where(ref list) => any(list),
+        PseudoClass::Has(ref list) => list.iter().any(|relative| has_match(element, relative)),
+        PseudoClass::Hover => data.has_state(ElementState::HOVER),
+        PseudoClass::Focus => data.has_state(ElementState::FOCUS),
+        PseudoClass::Active => data.has_state(ElementState::ACTIVE),
+        PseudoClass::Checked => data.has_state(ElementState::CHECKED),
+        PseudoClass::Disabled => data.has_state(ElementState::DISABLED),
+        PseudoClass::Enabled => data.is_form_control() && !data.has_state(ElementState::DISABLED),
+    }
+}
 
-        if selector_match { //if true return true
-            return true
+//Some element after `anchor` (below it for ' ' and '>', a later sibling or below one for '+' and '~') that
+//matches the selector with its leftmost compound related to the anchor
+fn has_match(anchor: ElementRef, relative: &RelativeSelector) -> bool {
+    let mut scope: Vec<ElementRef> = match relative.combinator {
+        '+' | '~' => siblings_after(anchor),
+        _ => vec![anchor],
+    };
+    if relative.combinator == '+' {
+        scope.truncate(1);
+    }
+    let last = relative.selector.simple.len();
+    last > 0
+        && relative.selector.pseudo_element.is_none()
+        && scope.iter().any(|&top| {
+            let below = top.descendant_elements();
+            let elements: Vec<ElementRef> = if top.id == anchor.id { below.collect() } else { std::iter::once(top).chain(below).collect() };
+            elements.into_iter().any(|element| matches_from(element, &relative.selector, last - 1, Some((relative.combinator, anchor))))
+        })
+}
+
+fn siblings_before(element: ElementRef) -> Vec<ElementRef> {
+    candidates(element, '~')
+}
+
+fn siblings_after(element: ElementRef) -> Vec<ElementRef> {
+    let mut result = Vec::new();
+    let mut next = element.next_element_sibling();
+    while let Some(sibling) = next {
+        result.push(sibling);
+        next = sibling.next_element_sibling();
+    }
+    result
+}
+
+impl WhiteSpace {
+    pub fn from_keyword(keyword: &str) -> Option<WhiteSpace> {
+        match keyword.to_ascii_lowercase().as_str() {
+            "normal" => Some(WhiteSpace::Normal),
+            "pre" => Some(WhiteSpace::Pre),
+            "nowrap" => Some(WhiteSpace::Nowrap),
+            "pre-wrap" => Some(WhiteSpace::PreWrap),
+            "pre-line" => Some(WhiteSpace::PreLine),
+            _ => None,
         }
     }
-    false  //else return false 
 }
 
+//What the browser's own stylesheet gives these elements, everything else inherits
+fn default_white_space(node: &Node) -> Option<WhiteSpace> {
+    match node.node_type {
+        NodeType::Element(ref e) if e.is_html() => match e.tag_name.as_str() {
+            "pre" | "listing" | "plaintext" | "xmp" => Some(WhiteSpace::Pre),
+            "textarea" => Some(WhiteSpace::PreWrap),
+            "nobr" => Some(WhiteSpace::Nowrap),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+//Whitespace processing of CSS Text, a whitespace run becomes one space (pre-line keeps the newlines and drops
+//the spaces around them), pre and pre-wrap keep the text as it is
+//A run at either end still leaves a space, the text next to it in the line may need it
+pub fn collapse_whitespace(text: &str, white_space: WhiteSpace) -> String {
+    let keep_newlines = match white_space {
+        WhiteSpace::Pre | WhiteSpace::PreWrap => return text.to_string(),
+        WhiteSpace::PreLine => true,
+        WhiteSpace::Normal | WhiteSpace::Nowrap => false,
+    };
+
+    let mut result = String::with_capacity(text.len());
+    let mut pending_space = false; //inside a whitespace run that isn't written yet
+    let mut line_start = false;    //right after a kept newline, spaces there are dropped
+    for c in text.chars() {
+        if keep_newlines && c == '\n' {
+            result.push('\n');
+            pending_space = false;
+            line_start = true;
+        } else if c.is_ascii_whitespace() {
+            pending_space |= !line_start;
+        } else {
+            if pending_space {
+                result.push(' ');
+                pending_space = false;
+            }
+            line_start = false;
+            result.push(c);
+        }
+    }
+    if pending_space {
+        result.push(' ');
+    }
+    result
+}
 
 pub fn pretty_print(node: &StyledNode, indent_size: usize) {
     //get our indent size by iterating through indent_size & map it to create whitespaces & collect it to string